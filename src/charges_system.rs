@@ -0,0 +1,182 @@
+use specs::prelude::*;
+
+use super::components::{
+    AreaOfEffect, Charges, CombatStats, Consumable, InBackpack, Name, ProvidesRecharge,
+    WantsToUseItem,
+};
+use super::gamelog::GameLog;
+use super::inventory_system::affected_tiles;
+use super::map::Map;
+
+/// Spends charges on items that have them and applies recharge scrolls. Runs before
+/// `ItemUseSystem`, so an item that is out of charges, or aimed where it can't hit anything,
+/// is never used at all.
+pub struct ChargesSystem {}
+
+impl<'a> System<'a> for ChargesSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, ProvidesRecharge>,
+        ReadStorage<'a, InBackpack>,
+        WriteStorage<'a, Charges>,
+        WriteStorage<'a, Consumable>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, AreaOfEffect>,
+        ReadStorage<'a, CombatStats>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut gamelog,
+            entities,
+            mut wants_use,
+            names,
+            rechargers,
+            backpack,
+            mut charges,
+            mut consumables,
+            map,
+            aoe,
+            combat_stats,
+        ) = data;
+
+        let mut fizzled: Vec<Entity> = Vec::new();
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            if let Some(item_charges) = charges.get_mut(useitem.item) {
+                if item_charges.current < 1 {
+                    fizzled.push(entity);
+                    if entity == *player_entity {
+                        let name = &names.get(useitem.item).unwrap().name;
+                        gamelog
+                            .entries
+                            .insert(0, format!("The {} is out of charges.", name));
+                    }
+                    continue;
+                }
+
+                // Keep the charge if there is nothing there for the item to affect
+                if let Some(target) = useitem.target {
+                    let has_target = affected_tiles(&map, target, aoe.get(useitem.item))
+                        .iter()
+                        .filter_map(|tile| map.xy_idx(tile.x, tile.y))
+                        .flat_map(|idx| map.tile_content[idx].iter())
+                        .any(|mob| combat_stats.get(*mob).is_some());
+                    if !has_target {
+                        fizzled.push(entity);
+                        if entity == *player_entity {
+                            gamelog
+                                .entries
+                                .insert(0, "There is nothing there to target.".to_string());
+                        }
+                        continue;
+                    }
+                }
+
+                item_charges.current -= 1;
+                // Let ItemUseSystem delete it along with the other consumables
+                if item_charges.current < 1 && item_charges.delete_when_empty {
+                    consumables
+                        .insert(useitem.item, Consumable {})
+                        .expect("Unable to insert consumable");
+                }
+            }
+
+            // Refill everything the user is carrying
+            if rechargers.get(useitem.item).is_some() {
+                let mut recharged = false;
+                for (item, item_charges) in (&entities, &mut charges).join() {
+                    let carried = backpack.get(item).is_some_and(|b| b.owner == entity);
+                    if carried && item_charges.current < item_charges.max {
+                        item_charges.current = item_charges.max;
+                        recharged = true;
+                    }
+                }
+
+                if entity == *player_entity {
+                    let message = if recharged {
+                        "Your pack hums with renewed power."
+                    } else {
+                        "You feel a surge of energy, but nothing happens."
+                    };
+                    gamelog.entries.insert(0, message.to_string());
+                }
+            }
+        }
+
+        for entity in fizzled.iter() {
+            wants_use.remove(*entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Has the player aim a carried wand at `target` and returns the charges left on it
+    fn zap_wand(ecs: &mut World, target: rltk::Point) -> i32 {
+        let player = *ecs.fetch::<Entity>();
+        let wand = ecs
+            .create_entity()
+            .with(Name {
+                name: "Wand of Magic Missile".to_string(),
+            })
+            .with(InBackpack { owner: player })
+            .with(Charges {
+                current: 5,
+                max: 5,
+                delete_when_empty: false,
+            })
+            .build();
+        ecs.write_storage::<WantsToUseItem>()
+            .insert(
+                player,
+                WantsToUseItem {
+                    item: wand,
+                    target: Some(target),
+                },
+            )
+            .expect("Unable to insert intent");
+        ChargesSystem {}.run_now(ecs);
+        ecs.maintain();
+        ecs.read_storage::<Charges>().get(wand).unwrap().current
+    }
+
+    #[test]
+    fn wands_aimed_at_nothing_keep_their_charge() {
+        let mut ecs = crate::test_world(1);
+
+        assert_eq!(zap_wand(&mut ecs, rltk::Point::new(3, 3)), 5);
+        let player = *ecs.fetch::<Entity>();
+        assert!(ecs.read_storage::<WantsToUseItem>().get(player).is_none());
+    }
+
+    #[test]
+    fn wands_aimed_at_a_monster_spend_a_charge() {
+        let mut ecs = crate::test_world(1);
+        let monster = ecs
+            .create_entity()
+            .with(CombatStats {
+                max_hp: 10,
+                hp: 10,
+                defense: 0,
+                power: 1,
+            })
+            .build();
+        {
+            let mut map = ecs.fetch_mut::<Map>();
+            let idx = map.xy_idx(3, 3).unwrap();
+            map.tile_content[idx].push(monster);
+        }
+
+        assert_eq!(zap_wand(&mut ecs, rltk::Point::new(3, 3)), 4);
+        let player = *ecs.fetch::<Entity>();
+        assert!(ecs.read_storage::<WantsToUseItem>().get(player).is_some());
+    }
+}
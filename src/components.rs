@@ -4,14 +4,48 @@ use specs::error::NoError;
 use specs::saveload::{ConvertSaveload, Marker};
 use rltk::RGB;
 
+use super::map::Liquid;
+
+/// The magical properties the loot generator rolled onto an item. The item's name already
+/// includes them; the effects are separate components.
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Affixes {
+    pub rarity: Rarity,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+}
+
+/// A trap that rings loud enough to be heard `radius` tiles away, less through walls
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Alarm {
+    pub radius: i32,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct AreaOfEffect {
     pub radius: i32,
 }
 
+/// The goal of the game: carrying it out of the final level wins
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Amulet {}
+
+/// A monster that stays put until a loud enough noise wakes it
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Asleep {}
+
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct BlocksTile {}
 
+/// An item that can be used a limited number of times before it needs recharging. It is only
+/// deleted when it runs out if `delete_when_empty` is set.
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Charges {
+    pub current: i32,
+    pub max: i32,
+    pub delete_when_empty: bool,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct CombatStats {
     pub max_hp: i32,
@@ -28,11 +62,40 @@ pub struct Confusion {
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct Consumable {}
 
+/// Holds items, which are attached to it with `InContainer`
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Container {}
+
+/// Equipment that can't be removed once it is equipped, until the curse is lifted
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Cursed {}
+
 #[derive(Clone, Component, ConvertSaveload)]
 pub struct DefenseBonus {
     pub defense: i32, 
 }
 
+/// An area effect that knocks down any weak walls caught in its blast
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct DestroysWalls {}
+
+/// A tool that, while equipped, digs through a wall in `turns` turns
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Digger {
+    pub turns: i32,
+}
+
+/// Added to an item's `MeleePowerBonus` or `DefenseBonus` while it is equipped; negative
+/// levels make the item worse than usual
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Enchantment {
+    pub level: i32,
+}
+
+/// Carrying more than the entity's capacity; it gets hungry twice as fast
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Encumbered {}
+
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct EntityMoved {}
 
@@ -53,6 +116,32 @@ pub struct Equipped {
     pub slot: EquipmentSlot,
 }
 
+/// A pile of coins lying on the floor; picking it up adds it to the collector's `Purse`
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Gold {
+    pub amount: i32,
+}
+
+/// Extra damage dealt by an equipped weapon on every hit, ignoring the target's defense
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct FireDamage {
+    pub damage: i32,
+}
+
+/// Turns of light left in a lantern. It only burns on dark levels, and gives no light once
+/// it runs dry.
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Fuel {
+    pub turns: i32,
+    pub max: i32,
+}
+
+/// Raises the wearer's maximum hit points while equipped
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct HealthBonus {
+    pub hp: i32,
+}
+
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct Hidden {}
 
@@ -75,14 +164,52 @@ pub struct InBackpack {
     pub owner: Entity,
 }
 
+/// An item stored inside a container
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct InContainer {
+    pub container: Entity,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct InflictsDamage {
     pub damage: i32,
 }
 
+/// A monster heading for somewhere it heard something, until it gets there or sees the player
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Investigating {
+    pub x: i32,
+    pub y: i32,
+}
+
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct Item {}
 
+/// Unlocks any locked container, and is used up doing it
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Key {}
+
+/// Leaves an edible corpse behind when killed
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct LeavesCorpse {}
+
+/// Lights up the tiles around the entity, fading out towards `range`
+#[derive(Clone, Component, ConvertSaveload)]
+pub struct LightSource {
+    pub color: RGB,
+    pub range: i32,
+}
+
+/// Names the table of items an entity may drop when it dies
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct LootTable {
+    pub table: String,
+}
+
+/// A container that can't be opened without a key or picking the lock
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Locked {}
+
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct MagicMapper {}
 
@@ -99,6 +226,27 @@ pub struct Name {
     pub name: String,
 }
 
+/// A non-hostile townsperson. NPCs never get a `Monster` component.
+#[derive(Clone, Component, Deserialize, Serialize)]
+pub struct Npc {
+    pub role: NpcRole,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+pub enum NpcRole {
+    Healer,
+    QuestGiver,
+    Vendor,
+}
+
+/// Where an entity stands on a level the player has left, in place of its `Position`
+#[derive(Clone, Component, ConvertSaveload)]
+pub struct OtherLevelPosition {
+    pub x: i32,
+    pub y: i32,
+    pub depth: i32,
+}
+
 #[derive(Clone, Component, Deserialize, Serialize)]
 pub struct ParticleLifetime {
     pub lifetime_ms: f32,
@@ -113,6 +261,20 @@ pub struct Position {
     pub y: i32,
 }
 
+/// Raises the enchantment of the user's weapon, or their shield if they have no weapon
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct ProvidesEnchantment {}
+
+/// Reveals the true names of everything the user is carrying
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct ProvidesIdentification {}
+
+/// Refills the user's lantern with `turns` of fuel
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct ProvidesFuel {
+    pub turns: i32,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct ProvidesHealing {
     pub heal_amount: i32,
@@ -121,11 +283,51 @@ pub struct ProvidesHealing {
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct ProvidesFood {}
 
+/// Restores the charges of everything the user is carrying
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct ProvidesRecharge {}
+
+/// Lifts the curse from everything the user is wearing or carrying
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct ProvidesRemoveCurse {}
+
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Purse {
+    pub gold: i32,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct Ranged {
     pub range: i32,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Rarity {
+    Common,
+    Magic,
+    Rare,
+}
+
+/// A trap that needs `cooldown` turns to rearm after it goes off; it is inert while
+/// `remaining` counts down
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Rearming {
+    pub cooldown: i32,
+    pub remaining: i32,
+}
+
+/// Heals the wearer by `hp` every few turns while equipped
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Regeneration {
+    pub hp: i32,
+}
+
+/// A trap that fills the area around it with `amount` of poison gas
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct ReleasesGas {
+    pub amount: i32,
+}
+
 #[derive(Clone, Component, ConvertSaveload)]
 pub struct Renderable {
     pub glyph: u8,
@@ -134,12 +336,95 @@ pub struct Renderable {
     pub render_order: i32,
 }
 
+/// Turns left before the entity rots away completely
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Rotting {
+    pub turns: i32,
+}
+
+/// Sets fire to the area it affects, which burns for at least `turns` turns
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct SetsFire {
+    pub turns: i32,
+}
+
+/// Breaks when thrown, applying its effects to everything within a tile of where it lands
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Shatters {}
+
 #[derive(Clone, Component, Debug, Deserialize, Serialize)]
 pub struct SingleActivation {}
 
+/// The player is moving carefully, making less noise with every step
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Sneaking {}
+
+/// Spills a puddle of liquid `amount` deep where it shatters
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct SpillsLiquid {
+    pub liquid: Liquid,
+    pub amount: i32,
+}
+
+/// A trap set off by something other than stepping on it, such as a failed disarm
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Sprung {
+    pub victim: Entity,
+}
+
+/// Identical items in the same backpack merge into one of these, holding `count` of them
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Stackable {
+    pub count: i32,
+}
+
+/// How quietly the entity moves; each point takes one off the volume of its footsteps
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Stealth {
+    pub skill: i32,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct SufferDamage {
     pub amount: i32,
+    pub source: DamageSource,
+}
+
+/// Who or what did some damage, so the run's statistics can credit kills and name the cause
+/// of death
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DamageSource {
+    Player,
+    Other(String),
+}
+
+impl DamageSource {
+    pub fn new(by_player: bool, name: &str) -> DamageSource {
+        if by_player {
+            DamageSource::Player
+        } else {
+            DamageSource::Other(name.to_string())
+        }
+    }
+}
+
+/// A trap that moves its victim to a random spot on the level
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct TeleportsVictim {}
+
+/// A trap that drops its victim to the next level down
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct TrapDoor {}
+
+/// A container whose trap goes off the first time it is opened, using the same effect
+/// components as other traps
+#[derive(Clone, Component, Debug, Deserialize, Serialize)]
+pub struct Trapped {}
+
+/// An item's base price in gold; shops buy and sell relative to it
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Value {
+    pub gold: i32,
 }
 
 #[derive(Clone, Component, ConvertSaveload)]
@@ -149,6 +434,13 @@ pub struct Viewshed {
     pub dirty: bool,
 }
 
+/// Spending this turn digging at the wall at (x, y)
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct WantsToDig {
+    pub x: i32,
+    pub y: i32,
+}
+
 #[derive(Clone, Component, ConvertSaveload, Debug)]
 pub struct WantsToDropItem {
     pub item: Entity,
@@ -176,12 +468,30 @@ pub struct WantsToUseItem {
     pub target: Option<rltk::Point>,
 }
 
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct WantsToThrowItem {
+    pub item: Entity,
+    pub target: rltk::Point,
+}
+
+/// How heavy an item is, in pounds. Items without one weigh nothing.
+#[derive(Clone, Component, ConvertSaveload, Debug)]
+pub struct Weight {
+    pub lbs: i32,
+}
+
 pub struct SerializeMe;
 
 // Special component that exists to help serialize the game data
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
-    pub map : super::map::Map
+    pub map : super::map::Map,
+    #[serde(default)]
+    pub stored_maps : super::map::StoredMaps,
+    #[serde(default)]
+    pub item_knowledge : super::identification::ItemKnowledge,
+    #[serde(default)]
+    pub run_stats : super::stats_system::RunStats,
 }
 
 // // Wrapper for Equipped
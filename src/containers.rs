@@ -0,0 +1,256 @@
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use super::components::{
+    Container, Gold, InBackpack, InContainer, Key, Locked, Name, Position, Purse, Sprung, Trapped,
+};
+use super::gamelog::GameLog;
+use super::gui::TransferMode;
+use super::identification::item_display_name;
+use super::inventory_system::has_free_slot;
+use super::loot;
+use super::map::MAPWIDTH;
+use super::stack_system::split_stack;
+use super::{spawner, RunState};
+
+/// Out of 20, the chance of picking a lock without a key
+const LOCKPICK_CHANCE: i32 = 8;
+
+fn container_name(ecs: &World, container: Entity) -> String {
+    ecs.read_storage::<Name>()
+        .get(container)
+        .map_or("container".to_string(), |name| name.name.to_lowercase())
+}
+
+/// Everything stored in a container
+pub fn contents(ecs: &World, container: Entity) -> Vec<Entity> {
+    let entities = ecs.entities();
+    let in_container = ecs.read_storage::<InContainer>();
+    (&entities, &in_container)
+        .join()
+        .filter(|(_, inside)| inside.container == container)
+        .map(|(item, _)| item)
+        .collect()
+}
+
+/// Moves a newly spawned item off the floor and into a container.
+pub fn store_in(ecs: &mut World, container: Entity, item: Entity) {
+    ecs.write_storage::<Position>().remove(item);
+    ecs.write_storage::<InContainer>()
+        .insert(item, InContainer { container })
+        .expect("Unable to store item");
+}
+
+/// Rolls `rolls` items from a loot table and puts them in the container.
+pub fn fill_container(ecs: &mut World, container: Entity, table: &str, depth: i32, rolls: i32) {
+    let idx = match ecs.read_storage::<Position>().get(container) {
+        Some(pos) => ((pos.y * MAPWIDTH as i32) + pos.x) as usize,
+        None => return,
+    };
+    for _ in 0..rolls {
+        let name = {
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            loot::drop_table(table, depth).roll(&mut rng)
+        };
+        if let Some(item) = spawner::spawn_entity(ecs, &(&idx, &name)) {
+            store_in(ecs, container, item);
+        }
+    }
+}
+
+/// The nearest container under or next to the player
+pub fn adjacent_container(ecs: &World) -> Option<Entity> {
+    let player_pos = ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let containers = ecs.read_storage::<Container>();
+    let positions = ecs.read_storage::<Position>();
+    (&entities, &containers, &positions)
+        .join()
+        .filter(|(_, _, pos)| {
+            i32::abs(pos.x - player_pos.x) <= 1 && i32::abs(pos.y - player_pos.y) <= 1
+        })
+        .min_by_key(|(_, _, pos)| i32::abs(pos.x - player_pos.x) + i32::abs(pos.y - player_pos.y))
+        .map(|(entity, _, _)| entity)
+}
+
+/// Tries to open a container for the player. A locked container uses up one of their keys,
+/// or they try to pick the lock, which takes a turn if it fails. A trapped container sets its
+/// trap off instead of opening.
+pub fn open_container(ecs: &mut World, container: Entity) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = container_name(ecs, container);
+
+    if ecs.read_storage::<Locked>().get(container).is_some() {
+        let key = {
+            let entities = ecs.entities();
+            let keys = ecs.read_storage::<Key>();
+            let backpack = ecs.read_storage::<InBackpack>();
+            (&entities, &keys, &backpack)
+                .join()
+                .find(|(_, _, pack)| pack.owner == player_entity)
+                .map(|(key, _, _)| key)
+        };
+        let message = match key {
+            Some(key) => {
+                let key = split_stack(ecs, key, 1);
+                ecs.delete_entity(key).expect("Unable to delete key");
+                format!("You unlock the {} with a key.", name)
+            }
+            None => {
+                let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 20);
+                if roll > LOCKPICK_CHANCE {
+                    ecs.fetch_mut::<GameLog>().entries.insert(
+                        0,
+                        format!("The {} is locked. You fail to pick the lock.", name),
+                    );
+                    return RunState::PlayerTurn;
+                }
+                format!("You pick the lock on the {}.", name)
+            }
+        };
+        ecs.fetch_mut::<GameLog>().entries.insert(0, message);
+        ecs.write_storage::<Locked>().remove(container);
+    }
+
+    if ecs.read_storage::<Trapped>().get(container).is_some() {
+        spring_trap(ecs, container, player_entity, &name);
+        return RunState::PlayerTurn;
+    }
+
+    RunState::ShowContainer {
+        container,
+        mode: TransferMode::Take,
+    }
+}
+
+/// Sets off a container's trap on whoever opened it; `TriggerSystem` applies its effects as
+/// it does for any other sprung trap. Like a single activation trap, it only goes off once.
+fn spring_trap(ecs: &mut World, container: Entity, victim: Entity, name: &str) {
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("A trap on the {} goes off!", name));
+    ecs.write_storage::<Sprung>()
+        .insert(container, Sprung { victim })
+        .expect("Unable to spring trap");
+    ecs.write_storage::<Trapped>().remove(container);
+}
+
+/// Moves an item from a container into the player's backpack, or their purse if it is gold.
+/// Returns false if there was no room for it.
+pub fn take_item(ecs: &mut World, item: Entity) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+
+    if let Some(gold) = ecs.read_storage::<Gold>().get(item) {
+        if let Some(purse) = ecs.write_storage::<Purse>().get_mut(player_entity) {
+            purse.gold += gold.amount;
+        }
+        ecs.fetch_mut::<GameLog>()
+            .entries
+            .insert(0, format!("You take {} gold.", gold.amount));
+        ecs.entities().delete(item).expect("Unable to delete gold");
+        return true;
+    }
+
+    let name = item_display_name(ecs, item);
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    if !has_free_slot(player_entity, &backpack) {
+        gamelog
+            .entries
+            .insert(0, "Your pack is full; you can't carry any more.".to_string());
+        return false;
+    }
+
+    ecs.write_storage::<InContainer>().remove(item);
+    backpack
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to take item");
+    gamelog.entries.insert(0, format!("You take the {}.", name));
+    true
+}
+
+/// Takes everything out of a container, stopping if the player's pack fills up.
+pub fn take_all(ecs: &mut World, container: Entity) {
+    for item in contents(ecs, container) {
+        if !take_item(ecs, item) {
+            break;
+        }
+    }
+}
+
+/// Moves an item from the player's backpack into a container.
+pub fn put_item(ecs: &mut World, container: Entity, item: Entity) {
+    let name = item_display_name(ecs, item);
+    let container_name = container_name(ecs, container);
+
+    ecs.write_storage::<InBackpack>().remove(item);
+    ecs.write_storage::<InContainer>()
+        .insert(item, InContainer { container })
+        .expect("Unable to put item away");
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("You put the {} in the {}.", name, container_name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::components::{InflictsDamage, ReleasesGas, SufferDamage};
+    use super::super::map::Map;
+    use super::super::trigger_system::TriggerSystem;
+    use super::*;
+
+    /// A trapped chest next to the player, with the given trap effect
+    fn trapped_chest<C: Component + Send + Sync>(ecs: &mut World, effect: C) -> Entity {
+        ecs.create_entity()
+            .with(Position { x: 2, y: 1 })
+            .with(Name {
+                name: "Chest".to_string(),
+            })
+            .with(Container {})
+            .with(Trapped {})
+            .with(effect)
+            .build()
+    }
+
+    /// Opens the chest, then lets the trap go off
+    fn open_and_trigger(ecs: &mut World, chest: Entity) -> RunState {
+        let runstate = open_container(ecs, chest);
+        TriggerSystem {}.run_now(ecs);
+        ecs.maintain();
+        runstate
+    }
+
+    #[test]
+    fn chest_traps_use_trap_effects() {
+        let mut ecs = crate::test_world(1);
+        let chest = trapped_chest(&mut ecs, ReleasesGas { amount: 5 });
+
+        assert!(open_and_trigger(&mut ecs, chest) == RunState::PlayerTurn);
+        let idx = ecs.fetch::<Map>().xy_idx(2, 1).unwrap();
+        assert_eq!(ecs.fetch::<Map>().gas.get(&idx), Some(&5));
+        assert!(ecs.read_storage::<Trapped>().get(chest).is_none());
+    }
+
+    #[test]
+    fn chest_traps_go_off_once() {
+        let mut ecs = crate::test_world(1);
+        let player_entity = *ecs.fetch::<Entity>();
+        let chest = trapped_chest(&mut ecs, InflictsDamage { damage: 6 });
+
+        open_and_trigger(&mut ecs, chest);
+        let damage = ecs.read_storage::<SufferDamage>().get(player_entity).unwrap().amount;
+        assert_eq!(damage, 6);
+        ecs.write_storage::<SufferDamage>().clear();
+
+        match open_and_trigger(&mut ecs, chest) {
+            RunState::ShowContainer { container, .. } => assert_eq!(container, chest),
+            _ => panic!("The chest should open once its trap has gone off"),
+        }
+        assert!(ecs.read_storage::<SufferDamage>().get(player_entity).is_none());
+    }
+}
@@ -1,10 +1,19 @@
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
 
 use super::RunState;
-use super::components::{CombatStats, Name, Player, Position, SufferDamage};
+use super::components::{
+    CombatStats, DamageSource, LeavesCorpse, LootTable, Name, Player, Position, SufferDamage,
+};
+use super::containers;
 use super::gamelog::GameLog;
-use super::map::Map;
+use super::loot;
+use super::map::{Map, MAPWIDTH};
+use super::spawner;
+use super::stats_system::RunStats;
 
+/// Applies the damage everything has suffered this turn, and keeps the run's statistics of
+/// damage dealt and taken, kills, and what finally killed the player.
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
@@ -14,14 +23,45 @@ impl<'a> System<'a> for DamageSystem {
         ReadStorage<'a, Position>,
         WriteExpect<'a, Map>,
         Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunStats>,
+        ReadStorage<'a, Name>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage, positions, mut map, entities) = data;
+        let (
+            mut stats,
+            mut damage,
+            positions,
+            mut map,
+            entities,
+            player_entity,
+            mut run_stats,
+            names,
+        ) = data;
 
         for (entity, mut stats, damage) in (&entities, &mut stats, &damage).join() {
+            let was_alive = stats.hp > 0;
             stats.hp -= damage.amount;
 
+            let is_player = entity == *player_entity;
+            if is_player {
+                run_stats.damage_taken += damage.amount;
+                if was_alive && stats.hp < 1 {
+                    run_stats.cause_of_death = Some(match &damage.source {
+                        DamageSource::Player => "their own hand".to_string(),
+                        DamageSource::Other(cause) => cause.clone(),
+                    });
+                }
+            } else if damage.source == DamageSource::Player {
+                run_stats.damage_dealt += damage.amount;
+                if was_alive && stats.hp < 1 {
+                    if let Some(name) = names.get(entity) {
+                        *run_stats.kills.entry(name.name.clone()).or_insert(0) += 1;
+                    }
+                }
+            }
+
             // Determine if a bloodstain forms
             let pos = positions.get(entity);
             if let Some(pos) = pos {
@@ -34,14 +74,27 @@ impl<'a> System<'a> for DamageSystem {
     }
 }
 
+/// What a dead monster leaves behind where it fell
+struct Remains {
+    x: i32,
+    y: i32,
+    name: String,
+    loot_table: Option<String>,
+    corpse: bool,
+}
+
 pub fn delete_the_dead(ecs: &mut World) {
     let mut dead: Vec<Entity> = Vec::new();
+    let mut remains: Vec<Remains> = Vec::new();
 
     // Using a scope to make the borrow checker happy
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let loot_tables = ecs.read_storage::<LootTable>();
+        let corpses = ecs.read_storage::<LeavesCorpse>();
         let entities = ecs.entities();
         let mut log = ecs.write_resource::<GameLog>();
         for (entity, stats) in (&entities, &combat_stats).join() {
@@ -53,6 +106,16 @@ pub fn delete_the_dead(ecs: &mut World) {
                         if let Some(victim_name) = victim_name {
                             log.entries.insert(0, format!("{} is dead", &victim_name.name));
                         }
+                        if let Some(pos) = positions.get(entity) {
+                            remains.push(Remains {
+                                x: pos.x,
+                                y: pos.y,
+                                name: victim_name
+                                    .map_or("Something".to_string(), |n| n.name.clone()),
+                                loot_table: loot_tables.get(entity).map(|l| l.table.clone()),
+                                corpse: corpses.get(entity).is_some(),
+                            });
+                        }
                         dead.push(entity);
                     },
                     Some(_) => {
@@ -67,4 +130,25 @@ pub fn delete_the_dead(ecs: &mut World) {
     for victim in dead {
         ecs.delete_entity(victim).expect("Unable to delete dead.");
     }
+
+    // Drop loot and corpses where the monsters fell. Loot is left in the corpse if there is one.
+    let depth = ecs.fetch::<Map>().depth;
+    for remains in remains.iter() {
+        let corpse = if remains.corpse {
+            Some(spawner::corpse(ecs, remains.x, remains.y, &remains.name))
+        } else {
+            None
+        };
+        if let Some(table) = &remains.loot_table {
+            let drop = {
+                let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+                loot::drop_table(table, depth).roll(&mut rng)
+            };
+            let idx = ((remains.y * MAPWIDTH as i32) + remains.x) as usize;
+            let item = spawner::spawn_entity(ecs, &(&idx, &drop));
+            if let (Some(corpse), Some(item)) = (corpse, item) {
+                containers::store_in(ecs, corpse, item);
+            }
+        }
+    }
 }
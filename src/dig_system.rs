@@ -0,0 +1,111 @@
+use specs::prelude::*;
+
+use super::components::{
+    AreaOfEffect, DestroysWalls, Digger, Equipped, Viewshed, WantsToDig, WantsToUseItem,
+};
+use super::gamelog::GameLog;
+use super::map::Map;
+use super::noise_system::NoiseBuilder;
+
+/// How loud digging at a wall is
+const DIG_VOLUME: i32 = 6;
+
+/// Tears down walls. Anyone digging with an equipped tool wears the wall down a turn at a time
+/// until it gives way, and blasts from items that destroy walls knock down the weak ones they
+/// reach. Runs before `ItemUseSystem`, which uses up the item.
+pub struct DigSystem {}
+
+impl<'a> System<'a> for DigSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, NoiseBuilder>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToDig>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Digger>,
+        ReadStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, DestroysWalls>,
+        ReadStorage<'a, AreaOfEffect>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            player_entity,
+            mut log,
+            mut noise_builder,
+            entities,
+            mut wants_dig,
+            equipped,
+            diggers,
+            wants_use,
+            destroys_walls,
+            area_of_effect,
+            mut viewsheds,
+        ) = data;
+
+        let mut destroyed = false;
+        for (entity, dig) in (&entities, &wants_dig).join() {
+            let idx = match map.xy_idx(dig.x, dig.y) {
+                Some(idx) if map.is_diggable(idx) => idx,
+                _ => continue,
+            };
+            let turns = match (&equipped, &diggers)
+                .join()
+                .filter(|(equipped, _)| equipped.owner == entity)
+                .map(|(_, digger)| digger.turns)
+                .min()
+            {
+                Some(turns) => turns,
+                None => continue,
+            };
+
+            noise_builder.request(dig.x, dig.y, DIG_VOLUME);
+            let dug = map.dug.entry(idx).or_insert(0);
+            *dug += 1;
+            if *dug >= turns {
+                map.destroy_wall(idx);
+                destroyed = true;
+                if entity == *player_entity {
+                    log.entries
+                        .insert(0, "You break through the wall.".to_string());
+                }
+            } else if entity == *player_entity {
+                log.entries.insert(0, "You dig at the wall.".to_string());
+            }
+        }
+        wants_dig.clear();
+
+        for useitem in (&wants_use).join() {
+            let target = match useitem.target {
+                Some(target) if destroys_walls.get(useitem.item).is_some() => target,
+                _ => continue,
+            };
+            let radius = area_of_effect.get(useitem.item).map_or(0, |aoe| aoe.radius);
+            let walls: Vec<usize> = rltk::field_of_view(target, radius, &*map)
+                .iter()
+                .filter_map(|tile| map.xy_idx(tile.x, tile.y))
+                .filter(|idx| map.is_weak_wall(*idx))
+                .collect();
+            if walls.iter().any(|idx| map.visible_tiles[*idx]) {
+                log.entries
+                    .insert(0, "The blast brings down part of the wall!".to_string());
+            }
+            for idx in walls {
+                map.destroy_wall(idx);
+                destroyed = true;
+            }
+        }
+
+        // Everyone can see further now
+        if destroyed {
+            for viewshed in (&mut viewsheds).join() {
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use rltk::{console, RGB};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::sync::OnceLock;
+
+use super::map::MapPalette;
+use super::random_table::RandomTable;
+
+/// File describing which theme each depth of the dungeon uses.
+pub const PLAN_FILE: &str = "resources/dungeon_plan.json";
+/// The deepest level, used when the plan doesn't set one
+const DEFAULT_FINAL_DEPTH: i32 = 12;
+
+#[derive(Debug)]
+pub enum PlanError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::Io(e) => write!(f, "Unable to read dungeon plan {}: {}", PLAN_FILE, e),
+            PlanError::Parse(e) => write!(f, "Error in dungeon plan {}: {}", PLAN_FILE, e),
+        }
+    }
+}
+
+/// One step of the map builder pipeline for a theme. The first step makes the map and
+/// any later steps modify it.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuilderStep {
+    /// Start from one of these builders, chosen at random
+    Random(Vec<String>),
+    /// Start from a hand-made prefab level
+    Level(String),
+    /// Stamp a prefab section onto the map built so far
+    Section(String),
+    /// Regenerate the map built so far with wave function collapse, one time in `n`
+    WaveformCollapse(i32),
+    /// Start from a wave function collapse map learned from a REX Paint sample
+    WaveformSample(String),
+}
+
+/// A spawn table entry whose weight grows by `per_depth` on every level
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SpawnWeight {
+    pub name: String,
+    pub weight: i32,
+    #[serde(default)]
+    pub per_depth: i32,
+}
+
+/// Map colors as hex strings, e.g. "#00ff00"
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PaletteColors {
+    pub floor: String,
+    #[serde(default)]
+    pub road: Option<String>,
+    pub wall: String,
+    pub stairs: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub min_depth: i32,
+    #[serde(default)]
+    pub max_depth: Option<i32>,
+    pub pipeline: Vec<BuilderStep>,
+    pub spawns: Vec<SpawnWeight>,
+    pub palette: PaletteColors,
+    pub entry_message: String,
+    /// What vendors on this level sell; restocked on every visit
+    #[serde(default)]
+    pub shop_stock: Vec<SpawnWeight>,
+    /// Persistent levels are kept as they were when the player leaves, and the level
+    /// below gets up stairs leading back
+    #[serde(default)]
+    pub persistent: bool,
+    /// Dark levels can only be seen by torchlight, or right next to the player
+    #[serde(default)]
+    pub dark: bool,
+    /// Number of patches of grass grown on the level
+    #[serde(default)]
+    pub grass: i32,
+    /// Number of pools of water flooded onto the level
+    #[serde(default)]
+    pub pools: i32,
+}
+
+impl Theme {
+    pub fn covers(&self, depth: i32) -> bool {
+        depth >= self.min_depth && self.max_depth.is_none_or(|max| depth <= max)
+    }
+
+    pub fn spawn_table(&self, depth: i32) -> RandomTable {
+        weighted_table(&self.spawns, depth)
+    }
+
+    pub fn shop_table(&self, depth: i32) -> RandomTable {
+        weighted_table(&self.shop_stock, depth)
+    }
+
+    /// Converts the theme's colors, keeping the default color for any that don't parse.
+    pub fn palette(&self) -> MapPalette {
+        let default = MapPalette::default();
+        let parse = |name: &str, hex: &str, fallback: RGB| match RGB::from_hex(hex) {
+            Ok(color) => color,
+            Err(_) => {
+                console::log(format!(
+                    "Theme {} has an invalid {} color '{}'",
+                    self.name, name, hex
+                ));
+                fallback
+            }
+        };
+        MapPalette {
+            floor: parse("floor", &self.palette.floor, default.floor),
+            road: match &self.palette.road {
+                Some(road) => parse("road", road, default.road),
+                None => default.road,
+            },
+            wall: parse("wall", &self.palette.wall, default.wall),
+            stairs: parse("stairs", &self.palette.stairs, default.stairs),
+        }
+    }
+}
+
+fn weighted_table(weights: &[SpawnWeight], depth: i32) -> RandomTable {
+    weights.iter().fold(RandomTable::new(), |table, spawn| {
+        table.add(&spawn.name, spawn.weight + (spawn.per_depth * depth))
+    })
+}
+
+/// Themes in the order they are checked; the first one covering a depth is used.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct DungeonPlan {
+    pub themes: Vec<Theme>,
+    /// The deepest level, where the boss guards the amulet and the stairs lead out
+    #[serde(default)]
+    pub final_depth: Option<i32>,
+}
+
+impl DungeonPlan {
+    pub fn load(path: &str) -> Result<DungeonPlan, PlanError> {
+        let text = fs::read_to_string(path).map_err(PlanError::Io)?;
+        serde_json::from_str(&text).map_err(PlanError::Parse)
+    }
+
+    pub fn theme_for_depth(&self, depth: i32) -> Option<&Theme> {
+        self.themes.iter().find(|theme| theme.covers(depth))
+    }
+}
+
+static PLAN: OnceLock<DungeonPlan> = OnceLock::new();
+
+/// The dungeon plan, loaded from `PLAN_FILE` the first time it is needed. If the file
+/// can't be loaded the plan is empty and every level uses the built-in defaults.
+pub fn plan() -> &'static DungeonPlan {
+    PLAN.get_or_init(|| match DungeonPlan::load(PLAN_FILE) {
+        Ok(plan) => plan,
+        Err(e) => {
+            console::log(format!("{}", e));
+            DungeonPlan::default()
+        }
+    })
+}
+
+pub fn theme_for_depth(depth: i32) -> Option<&'static Theme> {
+    plan().theme_for_depth(depth)
+}
+
+pub fn final_depth() -> i32 {
+    plan().final_depth.unwrap_or(DEFAULT_FINAL_DEPTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use rltk::RandomNumberGenerator;
+
+    use super::super::map_builders::random_builder;
+    use super::super::map_builders::validation::validate_map;
+
+    #[test]
+    fn dungeon_plan_is_valid() {
+        for depth in 1..=12 {
+            let mut failures = Vec::new();
+            for seed in 0..5 {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                let mut builder = random_builder(depth, &mut rng);
+                builder.build_map(&mut rng);
+                let map = builder.get_map();
+                let start = builder.get_starting_position();
+                for error in validate_map(&map, &start) {
+                    failures.push(format!("depth {} seed {}: {}", depth, seed, error));
+                }
+            }
+            assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+        }
+    }
+}
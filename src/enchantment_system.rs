@@ -0,0 +1,115 @@
+use specs::prelude::*;
+
+use super::components::{
+    Cursed, Enchantment, EquipmentSlot, Equipped, InBackpack, Name, ProvidesEnchantment,
+    ProvidesRemoveCurse, WantsToUseItem,
+};
+use super::gamelog::GameLog;
+
+/// Applies enchant and remove curse scrolls. Runs before `ItemUseSystem`, which consumes them.
+pub struct EnchantmentSystem {}
+
+impl<'a> System<'a> for EnchantmentSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        ReadStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, ProvidesEnchantment>,
+        ReadStorage<'a, ProvidesRemoveCurse>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, InBackpack>,
+        WriteStorage<'a, Enchantment>,
+        WriteStorage<'a, Cursed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut gamelog,
+            entities,
+            wants_use,
+            names,
+            enchanters,
+            curse_removers,
+            equipped,
+            backpack,
+            mut enchantments,
+            mut cursed,
+        ) = data;
+
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            // Enchant the weapon if there is one, otherwise the shield
+            if enchanters.get(useitem.item).is_some() {
+                let mut target: Option<Entity> = None;
+                for (item, equipped_by) in (&entities, &equipped).join() {
+                    if equipped_by.owner == entity
+                        && (target.is_none() || equipped_by.slot == EquipmentSlot::Melee)
+                    {
+                        target = Some(item);
+                    }
+                }
+
+                match target {
+                    None => {
+                        if entity == *player_entity {
+                            gamelog
+                                .entries
+                                .insert(0, "You feel a tingle, but nothing happens.".to_string());
+                        }
+                    }
+                    Some(item) => {
+                        let level = match enchantments.get_mut(item) {
+                            Some(enchantment) => {
+                                enchantment.level += 1;
+                                enchantment.level
+                            }
+                            None => {
+                                enchantments
+                                    .insert(item, Enchantment { level: 1 })
+                                    .expect("Unable to enchant item");
+                                1
+                            }
+                        };
+                        if entity == *player_entity {
+                            gamelog.entries.insert(
+                                0,
+                                format!(
+                                    "Your {} glows blue. It is now {:+}.",
+                                    names.get(item).unwrap().name,
+                                    level
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+
+            // Lift curses from everything worn or carried
+            if curse_removers.get(useitem.item).is_some() {
+                let mut uncursed: Vec<Entity> = Vec::new();
+                for (item, _cursed) in (&entities, &cursed).join() {
+                    let worn = equipped.get(item).is_some_and(|e| e.owner == entity);
+                    let carried = backpack.get(item).is_some_and(|b| b.owner == entity);
+                    if worn || carried {
+                        uncursed.push(item);
+                    }
+                }
+
+                for item in uncursed.iter() {
+                    cursed.remove(*item);
+                }
+                if entity == *player_entity {
+                    let message = if uncursed.is_empty() {
+                        "You feel as if someone is watching over you."
+                    } else {
+                        "You feel a malevolent aura lift."
+                    };
+                    gamelog.entries.insert(0, message.to_string());
+                }
+            }
+        }
+    }
+}
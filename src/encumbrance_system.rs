@@ -0,0 +1,110 @@
+use specs::prelude::*;
+
+use super::components::{CombatStats, Encumbered, Equipped, InBackpack, Stackable, Weight};
+use super::gamelog::GameLog;
+use super::RunState;
+
+/// How much an entity can carry before it is encumbered. Stronger entities carry more.
+pub fn carry_capacity(stats: &CombatStats) -> i32 {
+    30 + (stats.power * 5)
+}
+
+/// Total weight of everything the owner is carrying or wearing
+fn total_weight(
+    owner: Entity,
+    entities: &Entities,
+    weights: &ReadStorage<Weight>,
+    stackables: &ReadStorage<Stackable>,
+    backpack: &ReadStorage<InBackpack>,
+    equipped: &ReadStorage<Equipped>,
+) -> i32 {
+    (entities, weights)
+        .join()
+        .filter(|(item, _)| {
+            backpack.get(*item).is_some_and(|b| b.owner == owner)
+                || equipped.get(*item).is_some_and(|e| e.owner == owner)
+        })
+        .map(|(item, weight)| weight.lbs * stackables.get(item).map_or(1, |s| s.count))
+        .sum()
+}
+
+/// Returns the weight the owner is carrying and their carry capacity.
+pub fn carried_weight(ecs: &World, owner: Entity) -> (i32, i32) {
+    let weight = total_weight(
+        owner,
+        &ecs.entities(),
+        &ecs.read_storage::<Weight>(),
+        &ecs.read_storage::<Stackable>(),
+        &ecs.read_storage::<InBackpack>(),
+        &ecs.read_storage::<Equipped>(),
+    );
+    let capacity = ecs
+        .read_storage::<CombatStats>()
+        .get(owner)
+        .map_or(0, carry_capacity);
+    (weight, capacity)
+}
+
+/// Marks the player as encumbered while they carry more than their capacity.
+pub struct EncumbranceSystem {}
+
+impl<'a> System<'a> for EncumbranceSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>, // The player
+        ReadExpect<'a, RunState>,
+        ReadStorage<'a, Weight>,
+        ReadStorage<'a, Stackable>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, Encumbered>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            runstate,
+            weights,
+            stackables,
+            backpack,
+            equipped,
+            combat_stats,
+            mut encumbered,
+            mut log,
+        ) = data;
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        let stats = match combat_stats.get(*player_entity) {
+            Some(stats) => stats,
+            None => return,
+        };
+        let weight = total_weight(
+            *player_entity,
+            &entities,
+            &weights,
+            &stackables,
+            &backpack,
+            &equipped,
+        );
+        let overloaded = weight > carry_capacity(stats);
+        let was_overloaded = encumbered.get(*player_entity).is_some();
+
+        if overloaded && !was_overloaded {
+            encumbered
+                .insert(*player_entity, Encumbered {})
+                .expect("Unable to insert encumbrance");
+            log.entries
+                .insert(0, "You are overburdened by the weight of your pack.".to_string());
+        } else if !overloaded && was_overloaded {
+            encumbered.remove(*player_entity);
+            log.entries
+                .insert(0, "You are no longer overburdened.".to_string());
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use std::collections::HashMap;
+
+use super::components::{
+    Amulet, AreaOfEffect, CombatStats, Container, DamageSource, Gold, Item, Name, Position,
+    SetsFire, SufferDamage, Viewshed, WantsToUseItem,
+};
+use super::gamelog::GameLog;
+use super::identification::ItemKnowledge;
+use super::map::{Liquid, Map, Puddle, TileType};
+use super::RunState;
+
+/// Damage done each turn to anything standing in a fire
+const FIRE_DAMAGE: i32 = 4;
+/// Damage done each turn to anything breathing poison gas
+const GAS_DAMAGE: i32 = 1;
+/// Extra turns a tile burns for when its grass catches
+const GRASS_BURN: i32 = 3;
+/// Extra turns a tile burns for when the oil on it catches
+const OIL_BURN: i32 = 6;
+/// How thick the smoke above a fire is
+const FIRE_SMOKE: i32 = 4;
+/// Number of floor tiles in each patch of grass or pool of water grown on a new level
+const PATCH_SIZE: i32 = 20;
+
+/// The cellular simulation of the dungeon's surroundings, run once a turn. Fire burns whatever
+/// stands in it and spreads across grass and oil, leaving smoke behind; smoke and poison gas
+/// drift outwards and thin away; and deep puddles flow into shallower ones. Items that set
+/// fires start them here, before `ItemUseSystem` uses them up.
+pub struct EnvironmentSystem {}
+
+impl<'a> System<'a> for EnvironmentSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadExpect<'a, ItemKnowledge>,
+        Entities<'a>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Gold>,
+        ReadStorage<'a, Container>,
+        ReadStorage<'a, Amulet>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, SetsFire>,
+        ReadStorage<'a, AreaOfEffect>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            player_entity,
+            runstate,
+            mut log,
+            mut rng,
+            item_knowledge,
+            entities,
+            combat_stats,
+            mut suffer_damage,
+            items,
+            gold,
+            containers,
+            amulets,
+            names,
+            positions,
+            wants_use,
+            sets_fire,
+            area_of_effect,
+            mut viewsheds,
+        ) = data;
+
+        if *runstate == RunState::PlayerTurn {
+            let had_clouds = !map.fire.is_empty() || !map.smoke.is_empty() || !map.gas.is_empty();
+
+            // Fire and gas hurt everything standing in them
+            for (entity, _stats, pos) in (&entities, &combat_stats, &positions).join() {
+                let idx = match map.xy_idx(pos.x, pos.y) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
+                let is_player = entity == *player_entity;
+                let mut damage = None;
+                if map.gas.contains_key(&idx) {
+                    damage = Some((GAS_DAMAGE, "poison gas"));
+                    if is_player {
+                        log.entries
+                            .insert(0, "You choke on the poison gas!".to_string());
+                    }
+                }
+                if map.fire.contains_key(&idx) {
+                    damage = Some((damage.map_or(0, |(amount, _)| amount) + FIRE_DAMAGE, "fire"));
+                    if is_player {
+                        log.entries.insert(0, "You are burning!".to_string());
+                    }
+                }
+                if let Some((amount, cause)) = damage {
+                    suffer_damage
+                        .insert(
+                            entity,
+                            SufferDamage {
+                                amount,
+                                source: DamageSource::Other(cause.to_string()),
+                            },
+                        )
+                        .expect("Unable to do damage");
+                }
+            }
+
+            // Items lying in a fire burn up, though coins, anything holding other items and the
+            // amulet don't
+            for (item, _item, pos, name) in (&entities, &items, &positions, &names).join() {
+                let idx = match map.xy_idx(pos.x, pos.y) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
+                if gold.get(item).is_some()
+                    || containers.get(item).is_some()
+                    || amulets.get(item).is_some()
+                    || !map.fire.contains_key(&idx)
+                {
+                    continue;
+                }
+                if map.visible_tiles[idx] {
+                    log.entries.insert(
+                        0,
+                        format!("The {} burns up.", item_knowledge.display_name(&name.name)),
+                    );
+                }
+                entities.delete(item).expect("Unable to burn item");
+            }
+
+            burn(&mut map, &mut rng);
+            map.gas = diffuse(&map, &map.gas);
+            map.smoke = diffuse(&map, &map.smoke);
+            flow(&mut map);
+
+            // Clouds and flames change what everyone can see
+            if had_clouds {
+                for viewshed in (&mut viewsheds).join() {
+                    viewshed.dirty = true;
+                }
+            }
+        }
+
+        // Start any fires set this turn
+        for useitem in (&wants_use).join() {
+            let (fire, target) = match (sets_fire.get(useitem.item), useitem.target) {
+                (Some(fire), Some(target)) => (fire, target),
+                _ => continue,
+            };
+            let radius = area_of_effect.get(useitem.item).map_or(0, |aoe| aoe.radius);
+            let mut tiles = rltk::field_of_view(target, radius, &*map);
+            tiles.retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
+            for tile in tiles.iter() {
+                if let Some(idx) = map.xy_idx(tile.x, tile.y) {
+                    ignite(&mut map, idx, fire.turns);
+                }
+            }
+        }
+    }
+}
+
+/// Sets a tile alight, burning away any grass or oil on it to keep the fire going longer.
+/// Walls and water don't burn.
+fn ignite(map: &mut Map, idx: usize, turns: i32) {
+    if map.tiles[idx] == TileType::Wall {
+        return;
+    }
+    let mut turns = turns;
+    match map.liquids.get(&idx).map(|puddle| puddle.liquid) {
+        Some(Liquid::Water) => return,
+        Some(Liquid::Oil) => {
+            map.liquids.remove(&idx);
+            turns += OIL_BURN;
+        }
+        None => {}
+    }
+    if map.grass.remove(&idx) {
+        turns += GRASS_BURN;
+    }
+    let burning = map.fire.entry(idx).or_insert(0);
+    *burning = i32::max(*burning, turns);
+}
+
+/// Burns every fire down by a turn. Fires smoke, spread to neighbouring grass and oil, and go
+/// out when they run out of fuel or are flooded.
+fn burn(map: &mut Map, rng: &mut RandomNumberGenerator) {
+    let fires: Vec<(usize, i32)> = map.fire.iter().map(|(idx, turns)| (*idx, *turns)).collect();
+    let mut catching: Vec<usize> = Vec::new();
+    for (idx, turns) in fires.iter() {
+        let smoke = map.smoke.entry(*idx).or_insert(0);
+        *smoke = i32::max(*smoke, FIRE_SMOKE);
+
+        for neighbour in neighbours(map, *idx) {
+            if map.fire.contains_key(&neighbour) {
+                continue;
+            }
+            let oily = map
+                .liquids
+                .get(&neighbour)
+                .is_some_and(|puddle| puddle.liquid == Liquid::Oil);
+            if oily || (map.grass.contains(&neighbour) && rng.roll_dice(1, 3) == 1) {
+                catching.push(neighbour);
+            }
+        }
+
+        let flooded = map
+            .liquids
+            .get(idx)
+            .is_some_and(|puddle| puddle.liquid == Liquid::Water);
+        if *turns <= 1 || flooded {
+            map.fire.remove(idx);
+        } else {
+            map.fire.insert(*idx, turns - 1);
+        }
+    }
+
+    for idx in catching {
+        ignite(map, idx, 1);
+    }
+}
+
+/// Spreads a cloud out a turn. A thick cloud leaks into the open tiles around it, and every
+/// tile loses a little until the cloud is gone.
+fn diffuse(map: &Map, cloud: &HashMap<usize, i32>) -> HashMap<usize, i32> {
+    let mut next: HashMap<usize, i32> = HashMap::new();
+    for (idx, amount) in cloud.iter() {
+        if *amount > 1 {
+            let here = next.entry(*idx).or_insert(0);
+            *here = i32::max(*here, amount - 1);
+        }
+        if *amount > 2 {
+            for neighbour in neighbours(map, *idx) {
+                let there = next.entry(neighbour).or_insert(0);
+                *there = i32::max(*there, amount - 2);
+            }
+        }
+    }
+    next
+}
+
+/// Lets each deep puddle flow a step into its shallowest neighbour. Liquids don't mix, so a
+/// puddle only flows into empty floor or more of the same liquid.
+fn flow(map: &mut Map) {
+    let mut changes: HashMap<usize, Puddle> = HashMap::new();
+    for (idx, puddle) in map.liquids.iter() {
+        if puddle.depth < 2 {
+            continue;
+        }
+        let lowest = neighbours(map, *idx)
+            .into_iter()
+            .filter_map(|neighbour| match map.liquids.get(&neighbour) {
+                None => Some((neighbour, 0)),
+                Some(other) if other.liquid == puddle.liquid => Some((neighbour, other.depth)),
+                Some(_) => None,
+            })
+            .min_by_key(|(_, depth)| *depth);
+        if let Some((neighbour, depth)) = lowest {
+            // Another liquid may already be flowing into the same empty tile this turn
+            let mixes = changes
+                .get(&neighbour)
+                .is_some_and(|change| change.liquid != puddle.liquid);
+            if depth < puddle.depth - 1 && !mixes {
+                changes.entry(*idx).or_insert(*puddle).depth -= 1;
+                changes
+                    .entry(neighbour)
+                    .or_insert(Puddle {
+                        liquid: puddle.liquid,
+                        depth,
+                    })
+                    .depth += 1;
+            }
+        }
+    }
+
+    for (idx, puddle) in changes {
+        map.liquids.insert(idx, puddle);
+    }
+}
+
+/// The open tiles next to a tile, not counting diagonals
+fn neighbours(map: &Map, idx: usize) -> Vec<usize> {
+    let x = idx as i32 % map.width;
+    let y = idx as i32 / map.width;
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .filter_map(|(dx, dy)| map.xy_idx(x + dx, y + dy))
+        .filter(|neighbour| map.tiles[*neighbour] != TileType::Wall)
+        .collect()
+}
+
+/// A random clump of connected floor tiles
+fn random_patch(map: &Map, rng: &mut RandomNumberGenerator) -> Vec<usize> {
+    let floor: Vec<usize> = (0..map.tiles.len())
+        .filter(|idx| map.tiles[*idx] == TileType::Floor)
+        .collect();
+    if floor.is_empty() {
+        return Vec::new();
+    }
+
+    let mut idx = floor[(rng.roll_dice(1, floor.len() as i32) - 1) as usize];
+    let mut patch = vec![idx];
+    for _ in 0..PATCH_SIZE {
+        let next = neighbours(map, idx);
+        if next.is_empty() {
+            break;
+        }
+        idx = next[(rng.roll_dice(1, next.len() as i32) - 1) as usize];
+        if map.tiles[idx] == TileType::Floor && !patch.contains(&idx) {
+            patch.push(idx);
+        }
+    }
+    patch
+}
+
+/// Grows `patches` clumps of grass on the level's floor.
+pub fn grow_grass(map: &mut Map, rng: &mut RandomNumberGenerator, patches: i32) {
+    for _ in 0..patches {
+        for idx in random_patch(map, rng) {
+            map.grass.insert(idx);
+        }
+    }
+}
+
+/// Floods `pools` shallow pools of water onto the level's floor.
+pub fn fill_pools(map: &mut Map, rng: &mut RandomNumberGenerator, pools: i32) {
+    for _ in 0..pools {
+        for idx in random_patch(map, rng) {
+            map.liquids.insert(
+                idx,
+                Puddle {
+                    liquid: Liquid::Water,
+                    depth: 1,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn liquids_dont_mix_when_flowing() {
+        let mut map = Map::new(1);
+        for x in 1..4 {
+            let idx = map.xy_idx(x, 1).unwrap();
+            map.tiles[idx] = TileType::Floor;
+        }
+        let oil = map.xy_idx(1, 1).unwrap();
+        let gap = map.xy_idx(2, 1).unwrap();
+        let water = map.xy_idx(3, 1).unwrap();
+        map.liquids.insert(oil, Puddle { liquid: Liquid::Oil, depth: 3 });
+        map.liquids.insert(water, Puddle { liquid: Liquid::Water, depth: 3 });
+
+        flow(&mut map);
+
+        // Whichever liquid got there first keeps the tile, and none of the other is lost
+        let total = |liquid: Liquid| -> i32 {
+            map.liquids
+                .values()
+                .filter(|puddle| puddle.liquid == liquid)
+                .map(|puddle| puddle.depth)
+                .sum()
+        };
+        assert_eq!(total(Liquid::Oil), 3);
+        assert_eq!(total(Liquid::Water), 3);
+        assert_eq!(map.liquids[&gap].depth, 1);
+    }
+}
@@ -0,0 +1,63 @@
+use specs::prelude::*;
+
+use super::components::{Fuel, ProvidesFuel, WantsToUseItem};
+use super::gamelog::GameLog;
+use super::map::Map;
+use super::RunState;
+
+/// Turns of fuel left when the player is warned that their lantern is running low
+const LOW_FUEL: i32 = 25;
+
+/// Burns lantern fuel on dark levels and refills lanterns from flasks of oil. Runs before
+/// `ItemUseSystem`, which uses up the flask.
+pub struct FuelSystem {}
+
+impl<'a> System<'a> for FuelSystem {
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        ReadStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, ProvidesFuel>,
+        WriteStorage<'a, Fuel>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, runstate, map, mut gamelog, entities, wants_use, oil, mut fuel) = data;
+
+        for (entity, useitem) in (&entities, &wants_use).join() {
+            if let Some(refill) = oil.get(useitem.item) {
+                if let Some(lantern) = fuel.get_mut(entity) {
+                    lantern.turns = i32::min(lantern.max, lantern.turns + refill.turns);
+                    if entity == *player_entity {
+                        gamelog
+                            .entries
+                            .insert(0, "You refill your lantern.".to_string());
+                    }
+                }
+            }
+        }
+
+        if *runstate != RunState::PlayerTurn || !map.dark {
+            return;
+        }
+        if let Some(lantern) = fuel.get_mut(*player_entity) {
+            if lantern.turns < 1 {
+                return;
+            }
+            lantern.turns -= 1;
+            if lantern.turns == LOW_FUEL {
+                gamelog.entries.insert(
+                    0,
+                    "Your lantern flickers; it is running low on oil.".to_string(),
+                );
+            } else if lantern.turns == 0 {
+                gamelog
+                    .entries
+                    .insert(0, "Your lantern sputters and goes out.".to_string());
+            }
+        }
+    }
+}
@@ -2,18 +2,25 @@ use rltk::{Console, Point, Rltk, VirtualKeyCode, RGB};
 use specs::prelude::*;
 
 use super::components::{
-    CombatStats, Equipped, Hidden, HungerClock, HungerState, InBackpack, Name, Player, Position,
-    Viewshed,
+    Asleep, Charges, CombatStats, Container, Encumbered, Equipped, Fuel, Hidden, HungerClock,
+    HungerState, InBackpack, InContainer, Name, Player, Position, Purse, Sneaking, Viewshed,
 };
+use super::encumbrance_system::carried_weight;
 use super::gamelog::GameLog;
+use super::hall_of_fame;
+use super::identification;
 use super::map::Map;
 use super::rex_assets::RexAssets;
+use super::shop;
+use super::stack_system;
+use super::stats_system::RunStats;
 use super::{RunState, State};
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum MainMenuSelection {
     NewGame,
     LoadGame,
+    HallOfFame,
     Quit,
 }
 
@@ -30,12 +37,53 @@ pub enum ItemMenuResult {
     Selected,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum ShopMode {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ShopResult {
+    Cancel,
+    NoResponse,
+    Selected,
+    SwitchMode,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TransferMode {
+    Put,
+    Take,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TransferResult {
+    Cancel,
+    NoResponse,
+    Selected,
+    SwitchMode,
+    TakeAll,
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum GameOverResult {
     NoSelection,
     QuitToMenu,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum MapGenAction {
+    NoAction,
+    TogglePause,
+    StepForward,
+    StepBack,
+    Faster,
+    Slower,
+    Finish,
+    Disable,
+}
+
 pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
     let save_exists = super::saveload_system::does_save_exist();
     let runstate = gs.ecs.fetch::<RunState>();
@@ -110,6 +158,23 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
             y += 1;
         }
 
+        if selection == MainMenuSelection::HallOfFame {
+            ctx.print_color_centered(
+                y,
+                RGB::named(rltk::MAGENTA),
+                RGB::named(rltk::BLACK),
+                "Hall of Fame",
+            );
+        } else {
+            ctx.print_color_centered(
+                y,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                "Hall of Fame",
+            );
+        }
+        y += 1;
+
         if selection == MainMenuSelection::Quit {
             ctx.print_color_centered(
                 y,
@@ -138,13 +203,14 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                     match selection {
                         MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
                         MainMenuSelection::LoadGame => newselection = MainMenuSelection::NewGame,
-                        MainMenuSelection::Quit => {
+                        MainMenuSelection::HallOfFame => {
                             if save_exists {
                                 newselection = MainMenuSelection::LoadGame;
                             } else {
                                 newselection = MainMenuSelection::NewGame;
                             }
                         }
+                        MainMenuSelection::Quit => newselection = MainMenuSelection::HallOfFame,
                     }
                     return MainMenuResult::NoSelection {
                         selected: newselection,
@@ -157,10 +223,11 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
                             if save_exists {
                                 newselection = MainMenuSelection::LoadGame;
                             } else {
-                                newselection = MainMenuSelection::Quit;
+                                newselection = MainMenuSelection::HallOfFame;
                             }
                         }
-                        MainMenuSelection::LoadGame => newselection = MainMenuSelection::Quit,
+                        MainMenuSelection::LoadGame => newselection = MainMenuSelection::HallOfFame,
+                        MainMenuSelection::HallOfFame => newselection = MainMenuSelection::Quit,
                         MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame,
                     }
                     return MainMenuResult::NoSelection {
@@ -186,10 +253,23 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
     }
 }
 
+/// Prints the owner's load against their capacity in the top right of an item menu's box.
+fn draw_carried_weight(ecs: &World, ctx: &mut Rltk, owner: Entity, y: i32) {
+    let (weight, capacity) = carried_weight(ecs, owner);
+    let text = format!("{}/{} lb", weight, capacity);
+    let color = if weight > capacity {
+        RGB::named(rltk::ORANGE)
+    } else {
+        RGB::named(rltk::WHITE)
+    };
+    ctx.print_color(45 - text.len() as i32, y, color, RGB::named(rltk::BLACK), &text);
+}
+
 pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
+    let charges = gs.ecs.read_storage::<Charges>();
     let entities = gs.ecs.entities();
 
     let inventory = (&backpack, &names)
@@ -213,6 +293,7 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         RGB::named(rltk::BLACK),
         "Inventory",
     );
+    draw_carried_weight(&gs.ecs, ctx, *player_entity, y - 2);
     ctx.print_color(
         18,
         y + count as i32 + 1,
@@ -223,7 +304,7 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
 
     let mut equippable: Vec<Entity> = Vec::new();
     let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    for (entity, _pack, _name) in (&entities, &backpack, &names)
         .join()
         .filter(|item| item.1.owner == *player_entity)
     {
@@ -249,7 +330,16 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        // Wands that are out of charges can't be used, so grey them out
+        let empty = charges.get(entity).is_some_and(|c| c.current < 1);
+        let fg = if empty { RGB::named(rltk::GREY) } else { RGB::named(rltk::WHITE) };
+        ctx.print_color(
+            21,
+            y,
+            fg,
+            RGB::named(rltk::BLACK),
+            &identification::item_display_name(&gs.ecs, entity),
+        );
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -301,6 +391,95 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
         RGB::named(rltk::BLACK),
         "Drop Which Item?",
     );
+    draw_carried_weight(&gs.ecs, ctx, *player_entity, y - 2);
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to cancel",
+    );
+
+    let mut equippable: Vec<Entity> = Vec::new();
+    let mut j = 0;
+    for (entity, _pack, _name) in (&entities, &backpack, &names)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+    {
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &identification::item_display_name(&gs.ecs, entity));
+        equippable.push(entity);
+        y += 1;
+        j += 1;
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(equippable[selection as usize]),
+                    );
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+pub fn throw_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&backpack, &names)
+        .join()
+        .filter(|item| item.0.owner == *player_entity);
+    let count = inventory.count();
+
+    let mut y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        y - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Throw Which Item?",
+    );
+    draw_carried_weight(&gs.ecs, ctx, *player_entity, y - 2);
     ctx.print_color(
         18,
         y + count as i32 + 1,
@@ -311,7 +490,7 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
 
     let mut equippable: Vec<Entity> = Vec::new();
     let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    for (entity, _pack, _name) in (&entities, &backpack, &names)
         .join()
         .filter(|item| item.1.owner == *player_entity)
     {
@@ -337,7 +516,7 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, &identification::item_display_name(&gs.ecs, entity));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -398,7 +577,7 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
 
     let mut equippable: Vec<Entity> = Vec::new();
     let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    for (entity, _pack, _name) in (&entities, &backpack, &names)
         .join()
         .filter(|item| item.1.owner == *player_entity)
     {
@@ -424,7 +603,7 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, &identification::item_display_name(&gs.ecs, entity));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -448,28 +627,281 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
     }
 }
 
-pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
-    ctx.print_color_centered(
+/// Lists the vendor's stock (when buying) or the player's backpack (when selling) with
+/// prices, and lets the player pick an item to trade.
+pub fn show_shop(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    vendor: Entity,
+    mode: ShopMode,
+) -> (ShopResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let purses = gs.ecs.read_storage::<Purse>();
+    let entities = gs.ecs.entities();
+
+    let (owner, title, switch_hint) = match mode {
+        ShopMode::Buy => (vendor, "Buy", "TAB to sell"),
+        ShopMode::Sell => (*player_entity, "Sell", "TAB to buy"),
+    };
+    let gold = purses.get(*player_entity).map_or(0, |purse| purse.gold);
+
+    let inventory = (&backpack, &names)
+        .join()
+        .filter(|item| item.0.owner == owner);
+    let count = inventory.count();
+
+    let mut y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
         15,
+        y - 2,
+        51,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Your journey has ended!",
+        &format!("{} (you have {} gold)", title, gold),
     );
-    ctx.print_color_centered(
-        17,
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &format!("{}, ESCAPE to leave", switch_hint),
+    );
+
+    let mut tradeable: Vec<Entity> = Vec::new();
+    let mut j = 0;
+    for (entity, _pack, _name) in (&entities, &backpack, &names)
+        .join()
+        .filter(|item| item.1.owner == owner)
+    {
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &identification::item_display_name(&gs.ecs, entity));
+        let value = shop::item_value(&gs.ecs, entity);
+        let price = match mode {
+            ShopMode::Buy => shop::buy_price(value),
+            ShopMode::Sell => shop::sell_price(value),
+        };
+        ctx.print_color(
+            56,
+            y,
+            RGB::named(rltk::GOLD),
+            RGB::named(rltk::BLACK),
+            &format!("{:>5} gp", price),
+        );
+        tradeable.push(entity);
+        y += 1;
+        j += 1;
+    }
+
+    match ctx.key {
+        None => (ShopResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ShopResult::Cancel, None),
+            VirtualKeyCode::Tab => (ShopResult::SwitchMode, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (ShopResult::Selected, Some(tradeable[selection as usize]));
+                }
+                (ShopResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+/// Lists what is in a container to take out, or what the player carries to put in it
+pub fn show_container(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    container: Entity,
+    mode: TransferMode,
+) -> (TransferResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let in_container = gs.ecs.read_storage::<InContainer>();
+    let containers = gs.ecs.read_storage::<Container>();
+    let entities = gs.ecs.entities();
+
+    let container_name = names.get(container).map_or("Container", |name| &name.name);
+    let (title, hint) = match mode {
+        TransferMode::Take => (
+            format!("Take from {}", container_name),
+            "TAB to put, ENTER to take all, ESCAPE to leave",
+        ),
+        TransferMode::Put => (
+            format!("Put in {}", container_name),
+            "TAB to take, ESCAPE to leave",
+        ),
+    };
+
+    let items: Vec<Entity> = match mode {
+        TransferMode::Take => (&entities, &in_container, &names)
+            .join()
+            .filter(|(_, inside, _)| inside.container == container)
+            .map(|(item, _, _)| item)
+            .collect(),
+        // Containers don't go inside other containers
+        TransferMode::Put => (&entities, &backpack, &names, !&containers)
+            .join()
+            .filter(|(_, pack, _, _)| pack.owner == *player_entity)
+            .map(|(item, _, _, _)| item)
+            .collect(),
+    };
+    let count = items.len();
+
+    let y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        y - 2,
+        51,
+        (count + 3) as i32,
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
-        "One day, we'll tell you all about how you did.",
     );
-    ctx.print_color_centered(
+    ctx.print_color(
         18,
-        RGB::named(rltk::WHITE),
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &title,
+    );
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "That day, sadly, is not in this chapter..",
+        hint,
     );
 
+    for (j, item) in items.iter().enumerate() {
+        let row = y + j as i32;
+        ctx.set(
+            17,
+            row,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            row,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            row,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, row, &identification::item_display_name(&gs.ecs, *item));
+    }
+
+    match ctx.key {
+        None => (TransferResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (TransferResult::Cancel, None),
+            VirtualKeyCode::Tab => (TransferResult::SwitchMode, None),
+            VirtualKeyCode::Return if mode == TransferMode::Take => (TransferResult::TakeAll, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (TransferResult::Selected, Some(items[selection as usize]));
+                }
+                (TransferResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+/// The screen shown when the player dies, summing up how the run went.
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
     ctx.print_color_centered(
-        20,
+        12,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Your journey has ended!",
+    );
+    run_summary(ecs, ctx, false)
+}
+
+/// The screen shown when the player escapes with the amulet, summing up how the run went.
+pub fn victory(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    ctx.print_color_centered(
+        12,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "You escaped the dungeon with the Amulet of Ages!",
+    );
+    run_summary(ecs, ctx, true)
+}
+
+/// Prints the run's statistics under the game over or victory heading, and waits for a key.
+fn run_summary(ecs: &World, ctx: &mut Rltk, victory: bool) -> GameOverResult {
+    let stats = ecs.fetch::<RunStats>();
+    let gold = hall_of_fame::player_gold(ecs);
+    let lines = [
+        hall_of_fame::outcome(ecs, victory),
+        format!("Score: {}", stats.score(gold, victory)),
+        String::new(),
+        format!("Turns taken: {}", stats.turns),
+        format!("Deepest level: {}", stats.deepest_depth),
+        format!("Monsters killed: {}", stats.total_kills()),
+        format!("Damage dealt: {}, taken: {}", stats.damage_dealt, stats.damage_taken),
+        format!("Items used: {}", stats.items_used.values().sum::<i32>()),
+        format!("Gold: {}", gold),
+        format!("Seed: {}", stats.seed),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        ctx.print_color_centered(
+            14 + i as i32,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            line,
+        );
+    }
+
+    ctx.print_color_centered(
+        26,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        &format!("The full story has been written to {}.", hall_of_fame::MORGUE_DIR),
+    );
+    ctx.print_color_centered(
+        28,
         RGB::named(rltk::MAGENTA),
         RGB::named(rltk::BLACK),
         "Press any key to return to the menu.",
@@ -481,6 +913,130 @@ pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
     }
 }
 
+/// Lists the best runs so far. Returns true once the player is done looking.
+pub fn hall_of_fame(ctx: &mut Rltk) -> bool {
+    let scores = hall_of_fame::load_high_scores();
+
+    ctx.draw_box_double(
+        4,
+        8,
+        71,
+        24,
+        RGB::named(rltk::WHEAT),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color_centered(
+        10,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Hall of Fame",
+    );
+
+    if scores.is_empty() {
+        ctx.print_color_centered(
+            14,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            "No runs have been recorded yet.",
+        );
+    } else {
+        ctx.print_color(
+            8,
+            12,
+            RGB::named(rltk::GRAY),
+            RGB::named(rltk::BLACK),
+            &format!("{:>10}  {:<44} {:>6}", "Score", "How it ended", "Turns"),
+        );
+        for (i, score) in scores.iter().enumerate() {
+            ctx.print_color(
+                8,
+                14 + i as i32,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                &format!(
+                    "{:>2}. {:>6}  {:<44} {:>6}",
+                    i + 1,
+                    score.score,
+                    score.outcome,
+                    score.turns
+                ),
+            );
+        }
+    }
+
+    ctx.print_color_centered(
+        30,
+        RGB::named(rltk::MAGENTA),
+        RGB::named(rltk::BLACK),
+        "Press any key to return to the menu.",
+    );
+
+    ctx.key.is_some()
+}
+
+/// Draws the label and controls for the map generation visualizer and reads its keys.
+pub fn mapgen_visualizer(
+    ctx: &mut Rltk,
+    phase: &str,
+    index: usize,
+    count: usize,
+    paused: bool,
+    delay: f32,
+) -> MapGenAction {
+    ctx.draw_box(
+        0,
+        43,
+        79,
+        6,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+
+    let status = if paused { "Paused" } else { "Playing" };
+    ctx.print_color(
+        2,
+        44,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &format!("{} ({} / {})", phase, index + 1, count),
+    );
+    ctx.print_color(
+        2,
+        45,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        &format!("{}, {}ms per step", status, delay as i32),
+    );
+    ctx.print_color(
+        2,
+        47,
+        RGB::named(rltk::GREY),
+        RGB::named(rltk::BLACK),
+        "Space: pause  Left/Right: step  Up/Down: speed",
+    );
+    ctx.print_color(
+        2,
+        48,
+        RGB::named(rltk::GREY),
+        RGB::named(rltk::BLACK),
+        "Enter: finish  V: turn the visualizer off",
+    );
+
+    match ctx.key {
+        None => MapGenAction::NoAction,
+        Some(key) => match key {
+            VirtualKeyCode::Space => MapGenAction::TogglePause,
+            VirtualKeyCode::Right => MapGenAction::StepForward,
+            VirtualKeyCode::Left => MapGenAction::StepBack,
+            VirtualKeyCode::Up => MapGenAction::Faster,
+            VirtualKeyCode::Down => MapGenAction::Slower,
+            VirtualKeyCode::Return | VirtualKeyCode::Escape => MapGenAction::Finish,
+            VirtualKeyCode::V => MapGenAction::Disable,
+            _ => MapGenAction::NoAction,
+        },
+    }
+}
+
 pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     ctx.draw_box(
         0,
@@ -534,6 +1090,49 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
             y += 1;
         }
 
+        if ecs.read_storage::<Sneaking>().get(*ecs.fetch::<Entity>()).is_some() {
+            ctx.print_color(
+                22,
+                42,
+                RGB::named(rltk::LIGHT_BLUE),
+                RGB::named(rltk::BLACK),
+                "Sneaking",
+            );
+        }
+
+        if map.dark {
+            if let Some(fuel) = ecs.read_storage::<Fuel>().get(*ecs.fetch::<Entity>()) {
+                let color = if fuel.turns > 0 { rltk::YELLOW } else { rltk::GREY };
+                ctx.print_color(
+                    32,
+                    42,
+                    RGB::named(color),
+                    RGB::named(rltk::BLACK),
+                    &format!("Lantern: {}", fuel.turns),
+                );
+            }
+        }
+
+        if ecs.read_storage::<Encumbered>().get(*ecs.fetch::<Entity>()).is_some() {
+            ctx.print_color(
+                48,
+                42,
+                RGB::named(rltk::ORANGE),
+                RGB::named(rltk::BLACK),
+                "Burdened",
+            );
+        }
+
+        if let Some(purse) = ecs.read_storage::<Purse>().get(*ecs.fetch::<Entity>()) {
+            ctx.print_color(
+                58,
+                42,
+                RGB::named(rltk::GOLD),
+                RGB::named(rltk::BLACK),
+                &format!("Gold: {}", purse.gold),
+            );
+        }
+
         match hc.state {
             HungerState::WellFed => ctx.print_color(
                 71,
@@ -566,6 +1165,90 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     draw_tooltips(ecs, ctx);
 }
 
+/// The digit a key stands for, from either the number row or the keypad
+fn key_digit(key: VirtualKeyCode) -> Option<i32> {
+    match key {
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Some(0),
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Some(1),
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Some(2),
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Some(3),
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Some(4),
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Some(5),
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Some(6),
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Some(7),
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Some(8),
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Some(9),
+        _ => None,
+    }
+}
+
+/// Asks how many items to drop from a stack. `quantity` is what has been typed so far; the
+/// updated amount comes back with `NoResponse` until ENTER is pressed. Pressing ENTER without
+/// typing anything drops the whole stack.
+pub fn drop_quantity_prompt(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    item: Entity,
+    quantity: i32,
+) -> (ItemMenuResult, i32) {
+    let count = stack_system::stack_count(&gs.ecs, item);
+    let name = identification::item_display_name(&gs.ecs, item);
+
+    ctx.draw_box(
+        15,
+        22,
+        51,
+        5,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        22,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Drop How Many?",
+    );
+    ctx.print(18, 24, &name);
+    let typed = if quantity > 0 { quantity.to_string() } else { String::new() };
+    ctx.print_color(
+        18,
+        25,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        &format!("> {}_", typed),
+    );
+    ctx.print_color(
+        18,
+        27,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &format!("1-{}, ENTER to drop, ESCAPE to cancel", count),
+    );
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, quantity),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, 0),
+            VirtualKeyCode::Return => {
+                if quantity > 0 {
+                    (ItemMenuResult::Selected, quantity)
+                } else {
+                    (ItemMenuResult::Selected, count)
+                }
+            }
+            VirtualKeyCode::Back => (ItemMenuResult::NoResponse, quantity / 10),
+            _ => match key_digit(key) {
+                Some(digit) => (
+                    ItemMenuResult::NoResponse,
+                    i32::min(count, (quantity * 10) + digit),
+                ),
+                None => (ItemMenuResult::NoResponse, quantity),
+            },
+        },
+    }
+}
+
 pub fn ranged_target(
     gs: &mut State,
     ctx: &mut Rltk,
@@ -627,18 +1310,24 @@ pub fn ranged_target(
 
 fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
     let hidden = ecs.read_storage::<Hidden>();
+    let asleep = ecs.read_storage::<Asleep>();
 
     let mouse_pos = ctx.mouse_pos();
     if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
         return;
     }
     let mut tooltip: Vec<String> = Vec::new();
-    for (name, position, _hidden) in (&names, &positions, !&hidden).join() {
+    for (entity, _name, position, _hidden) in (&entities, &names, &positions, !&hidden).join() {
         if position.x == mouse_pos.0 && position.y == mouse_pos.1 {
-            tooltip.push(name.name.to_string());
+            let mut name = identification::item_display_name(ecs, entity);
+            if asleep.get(entity).is_some() {
+                name.push_str(" (asleep)");
+            }
+            tooltip.push(name);
         }
     }
 
@@ -0,0 +1,205 @@
+use rltk::console;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::cmp::Reverse;
+use std::fs;
+
+use super::components::{Equipped, InBackpack, Name, Purse};
+use super::gamelog::GameLog;
+use super::map::Map;
+use super::stats_system::RunStats;
+
+/// Where morgue files are written, one for each finished run
+pub const MORGUE_DIR: &str = "./morgue";
+/// The high score table, kept between games
+const HIGH_SCORE_FILE: &str = "./highscores.json";
+/// How many runs the Hall of Fame remembers
+const HIGH_SCORES_KEPT: usize = 10;
+/// How many of the last log messages go in a morgue file
+const MORGUE_MESSAGES: usize = 10;
+
+/// A finished run in the Hall of Fame
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HighScore {
+    pub score: i32,
+    /// How the run ended, e.g. "Killed by Orc on depth 5"
+    pub outcome: String,
+    pub turns: i32,
+    pub seed: u64,
+}
+
+/// The best runs so far, best first. A missing or unreadable table is empty.
+pub fn load_high_scores() -> Vec<HighScore> {
+    fs::read_to_string(HIGH_SCORE_FILE)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// How the run ended, as the morgue file and the Hall of Fame put it
+pub fn outcome(ecs: &World, victory: bool) -> String {
+    if victory {
+        return "Escaped with the Amulet of Ages".to_string();
+    }
+    let stats = ecs.fetch::<RunStats>();
+    let cause = stats.cause_of_death.as_deref().unwrap_or("misadventure");
+    format!("Killed by {} on depth {}", cause, ecs.fetch::<Map>().depth)
+}
+
+/// The gold the player has in their purse
+pub fn player_gold(ecs: &World) -> i32 {
+    ecs.read_storage::<Purse>()
+        .get(*ecs.fetch::<Entity>())
+        .map_or(0, |purse| purse.gold)
+}
+
+/// Adds a run to the table, keeping it best first and no longer than `HIGH_SCORES_KEPT`.
+/// A run that ties an older one goes below it.
+#[cfg(not(target_arch = "wasm32"))]
+fn add_high_score(scores: &mut Vec<HighScore>, entry: HighScore) {
+    scores.push(entry);
+    scores.sort_by_key(|score| Reverse(score.score));
+    scores.truncate(HIGH_SCORES_KEPT);
+}
+
+/// Writes the finished run to a morgue file and adds it to the Hall of Fame. Does nothing if
+/// the run has already been recorded.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn record_run(ecs: &mut World, victory: bool) {
+    if ecs.fetch::<RunStats>().recorded {
+        return;
+    }
+    ecs.write_resource::<RunStats>().recorded = true;
+
+    let morgue = morgue_text(ecs, victory);
+    let seed = ecs.fetch::<RunStats>().seed;
+    let path = format!("{}/morgue-{}.txt", MORGUE_DIR, seed);
+    if let Err(e) = fs::create_dir_all(MORGUE_DIR).and_then(|_| fs::write(&path, morgue)) {
+        console::log(format!("Unable to write morgue file {}: {}", path, e));
+    }
+
+    let entry = {
+        let stats = ecs.fetch::<RunStats>();
+        HighScore {
+            score: stats.score(player_gold(ecs), victory),
+            outcome: outcome(ecs, victory),
+            turns: stats.turns,
+            seed: stats.seed,
+        }
+    };
+    let mut scores = load_high_scores();
+    add_high_score(&mut scores, entry);
+    let written = serde_json::to_string_pretty(&scores)
+        .map_err(|e| e.to_string())
+        .and_then(|text| fs::write(HIGH_SCORE_FILE, text).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        console::log(format!("Unable to write high scores {}: {}", HIGH_SCORE_FILE, e));
+    }
+}
+
+// This is needed until WASM save support is implemented in RLTK.
+#[cfg(target_arch = "wasm32")]
+pub fn record_run(_ecs: &mut World, _victory: bool) {}
+
+/// A readable account of the run: how it ended, the statistics, what the player was carrying
+/// and the last things that happened.
+fn morgue_text(ecs: &World, victory: bool) -> String {
+    let stats = ecs.fetch::<RunStats>();
+    let gold = player_gold(ecs);
+    let mut lines = vec![
+        "Morgue file".to_string(),
+        "===========".to_string(),
+        String::new(),
+        format!("{}.", outcome(ecs, victory)),
+        format!("Score: {}", stats.score(gold, victory)),
+        String::new(),
+        format!("Seed:          {}", stats.seed),
+        format!("Turns taken:   {}", stats.turns),
+        format!("Deepest level: {}", stats.deepest_depth),
+        format!("Damage dealt:  {}", stats.damage_dealt),
+        format!("Damage taken:  {}", stats.damage_taken),
+        format!("Gold:          {}", gold),
+    ];
+
+    lines.push(String::new());
+    lines.push(format!("Kills ({})", stats.total_kills()));
+    for (name, count) in stats.kills.iter() {
+        lines.push(format!("  {:<20} {}", name, count));
+    }
+
+    lines.push(String::new());
+    lines.push("Items used".to_string());
+    for (name, count) in stats.items_used.iter() {
+        lines.push(format!("  {:<20} {}", name, count));
+    }
+
+    let player_entity = *ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    lines.push(String::new());
+    lines.push("Equipment".to_string());
+    for (equipped, name) in (&ecs.read_storage::<Equipped>(), &names).join() {
+        if equipped.owner == player_entity {
+            lines.push(format!("  {}", name.name));
+        }
+    }
+    lines.push(String::new());
+    lines.push("Backpack".to_string());
+    for (pack, name) in (&ecs.read_storage::<InBackpack>(), &names).join() {
+        if pack.owner == player_entity {
+            lines.push(format!("  {}", name.name));
+        }
+    }
+
+    lines.push(String::new());
+    lines.push("Last messages".to_string());
+    let log = ecs.fetch::<GameLog>();
+    for message in log.entries.iter().take(MORGUE_MESSAGES).rev() {
+        lines.push(format!("  {}", message));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high_score(score: i32, seed: u64) -> HighScore {
+        HighScore {
+            score,
+            outcome: "Killed by Orc on depth 1".to_string(),
+            turns: 100,
+            seed,
+        }
+    }
+
+    #[test]
+    fn high_scores_are_kept_best_first() {
+        let mut scores = Vec::new();
+        for (seed, score) in [300, 100, 500, 300].iter().enumerate() {
+            add_high_score(&mut scores, high_score(*score, seed as u64));
+        }
+        let order: Vec<(i32, u64)> = scores.iter().map(|s| (s.score, s.seed)).collect();
+        assert_eq!(order, vec![(500, 2), (300, 0), (300, 3), (100, 1)]);
+    }
+
+    #[test]
+    fn only_the_best_high_scores_are_kept() {
+        let mut scores = Vec::new();
+        for score in 1..=HIGH_SCORES_KEPT as i32 {
+            add_high_score(&mut scores, high_score(score * 10, 0));
+        }
+        assert_eq!(scores.len(), HIGH_SCORES_KEPT);
+
+        // A run worse than everything in a full table doesn't make it in
+        add_high_score(&mut scores, high_score(5, 1));
+        assert_eq!(scores.len(), HIGH_SCORES_KEPT);
+        assert!(scores.iter().all(|score| score.seed == 0));
+
+        // A better one pushes the worst out
+        add_high_score(&mut scores, high_score(1000, 2));
+        assert_eq!(scores.len(), HIGH_SCORES_KEPT);
+        assert_eq!(scores[0].seed, 2);
+        assert_eq!(scores.last().unwrap().score, 20);
+    }
+}
@@ -1,6 +1,6 @@
 use specs::prelude::*;
 
-use super::components::{HungerClock, HungerState, SufferDamage};
+use super::components::{DamageSource, Encumbered, HungerClock, HungerState, SufferDamage};
 use super::gamelog::GameLog;
 use super::RunState;
 
@@ -15,11 +15,19 @@ impl<'a> System<'a> for HungerSystem {
         ReadExpect<'a, RunState>,
         WriteStorage<'a, SufferDamage>,
         WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Encumbered>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut hunger_clock, player_entity, runstate, mut inflict_damage, mut log) =
-            data;
+        let (
+            entities,
+            mut hunger_clock,
+            player_entity,
+            runstate,
+            mut inflict_damage,
+            mut log,
+            encumbered,
+        ) = data;
         for (entity, mut clock) in (&entities, &mut hunger_clock).join() {
             let mut proceed = false;
 
@@ -38,7 +46,8 @@ impl<'a> System<'a> for HungerSystem {
             }
 
             if proceed {
-                clock.duration -= 1;
+                // Hauling a heavy load is hungry work
+                clock.duration -= if encumbered.get(entity).is_some() { 2 } else { 1 };
                 if clock.duration < 1 {
                     match clock.state {
                         HungerState::WellFed => {
@@ -74,7 +83,8 @@ impl<'a> System<'a> for HungerSystem {
                             inflict_damage.insert(
                                 entity,
                                 SufferDamage {
-                                    amount: 1
+                                    amount: 1,
+                                    source: DamageSource::Other("starvation".to_string()),
                                 }
                             ).expect("Unable to do damage");
                         }
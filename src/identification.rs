@@ -0,0 +1,132 @@
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use super::components::{Charges, Cursed, Enchantment, Name, Stackable};
+
+/// Scrolls whose names are hidden until they are identified
+const SCROLLS: [&str; 8] = [
+    "Confusion Scroll",
+    "Enchant Scroll",
+    "Fireball Scroll",
+    "Identify Scroll",
+    "Magic Mapping Scroll",
+    "Magic Missile Scroll",
+    "Recharge Scroll",
+    "Remove Curse Scroll",
+];
+
+/// Potions whose names are hidden until they are identified
+const POTIONS: [&str; 2] = ["Confusion Potion", "Health Potion"];
+
+const SYLLABLES: [&str; 16] = [
+    "ab", "bex", "dor", "el", "fru", "gar", "ith", "kel", "lor", "ma", "nok", "ol", "qua", "ta",
+    "xy", "zzy",
+];
+
+const POTION_LOOKS: [&str; 12] = [
+    "bubbling", "cloudy", "fizzy", "glowing", "milky", "murky", "oily", "pungent", "smoky",
+    "sparkling", "swirling", "viscous",
+];
+
+/// What the player knows about magic items this run. Every scroll and potion type is given a
+/// random appearance from the run's seed, which is shown in place of its name until the type
+/// is identified.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct ItemKnowledge {
+    pub seed: u64,
+    appearances: HashMap<String, String>,
+    identified: HashSet<String>,
+}
+
+impl ItemKnowledge {
+    pub fn new(seed: u64) -> ItemKnowledge {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let mut appearances: HashMap<String, String> = HashMap::new();
+
+        for scroll in SCROLLS.iter() {
+            let appearance = unused_appearance(&appearances, || {
+                let syllables = rng.range(2, 4);
+                let word: String = (0..syllables)
+                    .map(|_| *rng.random_slice_entry(&SYLLABLES).unwrap())
+                    .collect();
+                format!("scroll of {}", word.to_uppercase())
+            });
+            appearances.insert(scroll.to_string(), appearance);
+        }
+
+        for potion in POTIONS.iter() {
+            let appearance = unused_appearance(&appearances, || {
+                format!("{} potion", rng.random_slice_entry(&POTION_LOOKS).unwrap())
+            });
+            appearances.insert(potion.to_string(), appearance);
+        }
+
+        ItemKnowledge {
+            seed,
+            appearances,
+            identified: HashSet::new(),
+        }
+    }
+
+    /// The name the player sees for an item: its appearance if its type is still unknown,
+    /// otherwise its real name.
+    pub fn display_name(&self, name: &str) -> String {
+        match self.appearances.get(name) {
+            Some(appearance) if !self.identified.contains(name) => appearance.clone(),
+            _ => name.to_string(),
+        }
+    }
+
+    pub fn is_identified(&self, name: &str) -> bool {
+        !self.appearances.contains_key(name) || self.identified.contains(name)
+    }
+
+    /// Reveals an item type for the rest of the run. Returns true if it wasn't already known.
+    pub fn identify(&mut self, name: &str) -> bool {
+        if self.is_identified(name) {
+            return false;
+        }
+        self.identified.insert(name.to_string())
+    }
+}
+
+/// The name the player sees for an item, including any enchantment, curse, charges or stack
+/// size on it.
+pub fn item_display_name(ecs: &World, item: Entity) -> String {
+    let name = match ecs.read_storage::<Name>().get(item) {
+        Some(name) => ecs.fetch::<ItemKnowledge>().display_name(&name.name),
+        None => return "item".to_string(),
+    };
+    let name = match ecs.read_storage::<Enchantment>().get(item) {
+        Some(enchantment) if enchantment.level != 0 => format!("{:+} {}", enchantment.level, name),
+        _ => name,
+    };
+    let name = if ecs.read_storage::<Cursed>().get(item).is_some() {
+        format!("{} (cursed)", name)
+    } else {
+        name
+    };
+    let name = match ecs.read_storage::<Charges>().get(item) {
+        Some(charges) => format!("{} ({}/{})", name, charges.current, charges.max),
+        None => name,
+    };
+    match ecs.read_storage::<Stackable>().get(item) {
+        Some(stack) if stack.count > 1 => format!("{} (x{})", name, stack.count),
+        _ => name,
+    }
+}
+
+/// Keeps generating appearances until one turns up that no other item type has.
+fn unused_appearance<F: FnMut() -> String>(
+    appearances: &HashMap<String, String>,
+    mut generate: F,
+) -> String {
+    loop {
+        let appearance = generate();
+        if !appearances.values().any(|used| *used == appearance) {
+            return appearance;
+        }
+    }
+}
@@ -2,14 +2,43 @@ use specs::prelude::*;
 
 use super::RunState;
 use super::components::{
-    AreaOfEffect, CombatStats, Confusion, Consumable, Equippable, Equipped, HungerClock,
-    HungerState, InBackpack, InflictsDamage, MagicMapper, Name, Position, ProvidesFood, ProvidesHealing,
-    SufferDamage, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
+    AreaOfEffect, CombatStats, Confusion, Consumable, Cursed, DamageSource, Equippable, Equipped,
+    Gold, HealthBonus, HungerClock, HungerState, InBackpack, InContainer, InflictsDamage,
+    MagicMapper, Name, Position, ProvidesFood, ProvidesHealing, ProvidesIdentification, Purse,
+    Stackable, SufferDamage, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
 };
 use super::gamelog::GameLog;
+use super::identification::ItemKnowledge;
 use super::map::Map;
 use super::particle_system::ParticleBuilder;
 
+/// One backpack slot per letter of the alphabet
+pub const MAX_INVENTORY_SLOTS: usize = 26;
+
+/// Whether the owner has a free backpack slot
+pub fn has_free_slot(owner: Entity, backpack: &WriteStorage<InBackpack>) -> bool {
+    backpack.join().filter(|item| item.owner == owner).count() < MAX_INVENTORY_SLOTS
+}
+
+/// The tiles an item aimed at `target` affects: just the target, or the whole blast if it has
+/// an area of effect
+pub fn affected_tiles(
+    map: &Map,
+    target: rltk::Point,
+    area: Option<&AreaOfEffect>,
+) -> Vec<rltk::Point> {
+    match area {
+        None => vec![target],
+        Some(area_effect) => {
+            let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, map);
+            blast_tiles.retain(|p| {
+                p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1
+            });
+            blast_tiles
+        }
+    }
+}
+
 pub struct ItemCollectionSystem {}
 
 impl<'a> System<'a> for ItemCollectionSystem {
@@ -21,14 +50,80 @@ impl<'a> System<'a> for ItemCollectionSystem {
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, InBackpack>,
+        Entities<'a>,
+        ReadStorage<'a, Gold>,
+        WriteStorage<'a, Purse>,
+        ReadExpect<'a, ItemKnowledge>,
+        ReadStorage<'a, Stackable>,
+        WriteStorage<'a, InContainer>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) =
-            data;
+        let (
+            player_entity,
+            mut gamelog,
+            mut wants_pickup,
+            mut positions,
+            names,
+            mut backpack,
+            entities,
+            gold,
+            mut purses,
+            item_knowledge,
+            stackables,
+            mut in_container,
+        ) = data;
 
         for pickup in wants_pickup.join() {
+            // Gold and items that join a stack already in the pack don't need a free slot
+            let is_gold = gold.get(pickup.item).is_some();
+            let name = &names.get(pickup.item).unwrap().name;
+            let joins_stack = stackables.get(pickup.item).is_some()
+                && (&backpack, &names, &stackables)
+                    .join()
+                    .any(|(pack, other, _)| {
+                        pack.owner == pickup.collected_by && other.name == *name
+                    });
+            if !is_gold && !joins_stack && !has_free_slot(pickup.collected_by, &backpack) {
+                if pickup.collected_by == *player_entity {
+                    gamelog
+                        .entries
+                        .insert(0, "Your pack is full; you can't carry any more.".to_string());
+                }
+                continue;
+            }
+
+            // Anything inside a container that gets picked up falls out where it lay
+            if let Some(pos) = positions.get(pickup.item).cloned() {
+                let spilled: Vec<Entity> = (&entities, &in_container)
+                    .join()
+                    .filter(|(_, inside)| inside.container == pickup.item)
+                    .map(|(item, _)| item)
+                    .collect();
+                for item in spilled {
+                    in_container.remove(item);
+                    positions
+                        .insert(item, Position { x: pos.x, y: pos.y })
+                        .expect("Unable to spill item");
+                }
+            }
+
             positions.remove(pickup.item);
+
+            // Coins go straight into the purse rather than taking up a backpack slot
+            if let Some(gold) = gold.get(pickup.item) {
+                if let Some(purse) = purses.get_mut(pickup.collected_by) {
+                    purse.gold += gold.amount;
+                }
+                if pickup.collected_by == *player_entity {
+                    gamelog
+                        .entries
+                        .insert(0, format!("You pick up {} gold.", gold.amount));
+                }
+                entities.delete(pickup.item).expect("Unable to delete gold");
+                continue;
+            }
+
             backpack
                 .insert(
                     pickup.item,
@@ -41,7 +136,10 @@ impl<'a> System<'a> for ItemCollectionSystem {
             if pickup.collected_by == *player_entity {
                 gamelog.entries.insert(
                     0,
-                    format!("You pick up the {}.", names.get(pickup.item).unwrap().name),
+                    format!(
+                        "You pick up the {}.",
+                        item_knowledge.display_name(&names.get(pickup.item).unwrap().name)
+                    ),
                 );
             }
         }
@@ -77,6 +175,10 @@ impl<'a> System<'a> for ItemUseSystem {
         WriteStorage<'a, HungerClock>,
         ReadStorage<'a, MagicMapper>,
         WriteExpect<'a, RunState>,
+        WriteExpect<'a, ItemKnowledge>,
+        ReadStorage<'a, ProvidesIdentification>,
+        ReadStorage<'a, Cursed>,
+        ReadStorage<'a, HealthBonus>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -102,12 +204,27 @@ impl<'a> System<'a> for ItemUseSystem {
             provides_food,
             mut hunger_clock,
             magic_mapper,
-            mut runstate
+            mut runstate,
+            mut item_knowledge,
+            identifies,
+            cursed,
+            health_bonuses,
         ) = data;
 
         for (entity, useitem) in (&entities, &wants_use).join() {
             let mut used_item = true;
 
+            // Using an item reveals what it is
+            if entity == *player_entity {
+                let name = &names.get(useitem.item).unwrap().name;
+                let appearance = item_knowledge.display_name(name);
+                if item_knowledge.identify(name) {
+                    gamelog
+                        .entries
+                        .insert(0, format!("The {} was a {}!", appearance, name));
+                }
+            }
+
             // Targeting
             let mut targets: Vec<Entity> = Vec::new();
             match useitem.target {
@@ -116,35 +233,20 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
                 Some(target) => {
                     let area_effect = aoe.get(useitem.item);
-                    match area_effect {
-                        None => {
-                            // Single target in tile
-                            let idx = map.xy_idx(target.x, target.y).unwrap();
-                            for mob in map.tile_content[idx].iter() {
-                                targets.push(*mob);
-                            }
+                    for tile_idx in affected_tiles(&map, target, area_effect).iter() {
+                        let idx = map.xy_idx(tile_idx.x, tile_idx.y).unwrap();
+                        for mob in map.tile_content[idx].iter() {
+                            targets.push(*mob);
                         }
-                        Some(area_effect) => {
-                            // AoE
-                            let mut blast_tiles =
-                                rltk::field_of_view(target, area_effect.radius, &*map);
-                            blast_tiles.retain(|p| {
-                                p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1
-                            });
-                            for tile_idx in blast_tiles.iter() {
-                                let idx = map.xy_idx(tile_idx.x, tile_idx.y).unwrap();
-                                for mob in map.tile_content[idx].iter() {
-                                    targets.push(*mob);
-                                }
-                                particle_builder.request(
-                                    tile_idx.x,
-                                    tile_idx.y,
-                                    rltk::RGB::named(rltk::ORANGE),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('░'),
-                                    200.0,
-                                );
-                            }
+                        if area_effect.is_some() {
+                            particle_builder.request(
+                                tile_idx.x,
+                                tile_idx.y,
+                                rltk::RGB::named(rltk::ORANGE),
+                                rltk::RGB::named(rltk::BLACK),
+                                rltk::to_cp437('░'),
+                                200.0,
+                            );
                         }
                     }
                 }
@@ -158,22 +260,39 @@ impl<'a> System<'a> for ItemUseSystem {
                     let target_slot = can_equip.slot;
                     let target = targets[0];
 
-                    // Remove any items target has in the item's slot
+                    // Remove any items target has in the item's slot, unless one is cursed
                     let mut to_unequip: Vec<Entity> = Vec::new();
+                    let mut stuck: Option<String> = None;
                     for (item_entity, already_equipped, name) in
                         (&entities, &equipped, &names).join()
                     {
                         if already_equipped.owner == target && already_equipped.slot == target_slot
                         {
-                            to_unequip.push(item_entity);
-                            if target == *player_entity {
-                                gamelog
-                                    .entries
-                                    .insert(0, format!("You unequip {}.", name.name));
+                            if cursed.get(item_entity).is_some() {
+                                stuck = Some(name.name.clone());
                             }
+                            to_unequip.push(item_entity);
+                        }
+                    }
+                    if let Some(stuck) = stuck {
+                        if target == *player_entity {
+                            gamelog.entries.insert(
+                                0,
+                                format!("You can't remove your {}; it is cursed!", stuck),
+                            );
+                        }
+                        continue;
+                    }
+                    if target == *player_entity {
+                        for item_entity in to_unequip.iter() {
+                            gamelog.entries.insert(
+                                0,
+                                format!("You unequip {}.", names.get(*item_entity).unwrap().name),
+                            );
                         }
                     }
                     for item in to_unequip.iter() {
+                        adjust_max_hp(&mut combat_stats, health_bonuses.get(*item), target, -1);
                         equipped.remove(*item);
                         backpack
                             .insert(*item, InBackpack { owner: target })
@@ -190,12 +309,18 @@ impl<'a> System<'a> for ItemUseSystem {
                             },
                         )
                         .expect("Unable to insert equipped component");
+                    adjust_max_hp(&mut combat_stats, health_bonuses.get(useitem.item), target, 1);
                     backpack.remove(useitem.item);
                     if target == *player_entity {
                         gamelog.entries.insert(
                             0,
                             format!("You equip {}.", names.get(useitem.item).unwrap().name),
                         );
+                        if cursed.get(useitem.item).is_some() {
+                            gamelog
+                                .entries
+                                .insert(0, "It clings to you. It is cursed!".to_string());
+                        }
                     }
                 }
             }
@@ -232,6 +357,31 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Identify everything the user is carrying
+            if identifies.get(useitem.item).is_some() {
+                used_item = true;
+                let carried: Vec<String> = (&backpack, &names)
+                    .join()
+                    .filter(|(pack, _)| pack.owner == entity)
+                    .map(|(_, name)| name.name.clone())
+                    .collect();
+                let mut learned_something = false;
+                for name in carried.iter() {
+                    let appearance = item_knowledge.display_name(name);
+                    if item_knowledge.identify(name) && entity == *player_entity {
+                        learned_something = true;
+                        gamelog
+                            .entries
+                            .insert(0, format!("You identify the {} as a {}.", appearance, name));
+                    }
+                }
+                if !learned_something && entity == *player_entity {
+                    gamelog
+                        .entries
+                        .insert(0, "You learn nothing new.".to_string());
+                }
+            }
+
             // Apply healing if item heals.
             let item_heals = healing.get(useitem.item);
             match item_heals {
@@ -281,6 +431,10 @@ impl<'a> System<'a> for ItemUseSystem {
                                 *mob,
                                 SufferDamage {
                                     amount: damage.damage,
+                                    source: DamageSource::new(
+                                        entity == *player_entity,
+                                        &names.get(useitem.item).unwrap().name,
+                                    ),
                                 },
                             )
                             .expect("Unable to insert damage");
@@ -384,6 +538,7 @@ impl<'a> System<'a> for ItemDropSystem {
         ReadStorage<'a, Name>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, InBackpack>,
+        ReadExpect<'a, ItemKnowledge>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -395,6 +550,7 @@ impl<'a> System<'a> for ItemDropSystem {
             names,
             mut positions,
             mut backpack,
+            item_knowledge,
         ) = data;
 
         for (entity, to_drop) in (&entities, &wants_drop).join() {
@@ -418,7 +574,10 @@ impl<'a> System<'a> for ItemDropSystem {
             if entity == *player_entity {
                 gamelog.entries.insert(
                     0,
-                    format!("You drop the {}.", names.get(to_drop.item).unwrap().name),
+                    format!(
+                        "You drop the {}.",
+                        item_knowledge.display_name(&names.get(to_drop.item).unwrap().name)
+                    ),
                 );
             }
         }
@@ -436,12 +595,43 @@ impl<'a> System<'a> for ItemRemoveSystem {
         WriteStorage<'a, WantsToRemoveItem>,
         WriteStorage<'a, Equipped>,
         WriteStorage<'a, InBackpack>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Cursed>,
+        ReadStorage<'a, HealthBonus>,
+        WriteStorage<'a, CombatStats>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut wants_remove, mut equipped, mut backpack) = data;
+        let (
+            entities,
+            mut wants_remove,
+            mut equipped,
+            mut backpack,
+            player_entity,
+            mut gamelog,
+            names,
+            cursed,
+            health_bonuses,
+            mut combat_stats,
+        ) = data;
 
         for (entity, to_remove) in (&entities, &wants_remove).join() {
+            if cursed.get(to_remove.item).is_some() {
+                if entity == *player_entity {
+                    gamelog.entries.insert(
+                        0,
+                        format!(
+                            "You can't remove the {}; it is cursed!",
+                            names.get(to_remove.item).unwrap().name
+                        ),
+                    );
+                }
+                continue;
+            }
+
+            adjust_max_hp(&mut combat_stats, health_bonuses.get(to_remove.item), entity, -1);
             equipped.remove(to_remove.item);
             backpack
                 .insert(to_remove.item, InBackpack { owner: entity })
@@ -451,3 +641,17 @@ impl<'a> System<'a> for ItemRemoveSystem {
         wants_remove.clear();
     }
 }
+
+/// Applies (with `sign` 1) or takes away (with `sign` -1) an item's bonus to its wearer's
+/// maximum hit points.
+fn adjust_max_hp(
+    combat_stats: &mut WriteStorage<CombatStats>,
+    bonus: Option<&HealthBonus>,
+    wearer: Entity,
+    sign: i32,
+) {
+    if let (Some(bonus), Some(stats)) = (bonus, combat_stats.get_mut(wearer)) {
+        stats.max_hp += bonus.hp * sign;
+        stats.hp = i32::max(1, i32::min(stats.max_hp, stats.hp + (bonus.hp * sign)));
+    }
+}
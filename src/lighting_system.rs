@@ -0,0 +1,88 @@
+use rltk::{field_of_view, Point, RGB};
+use specs::prelude::*;
+
+use super::components::{Fuel, LightSource, Position, Viewshed};
+use super::map::{Map, TileType};
+
+/// Works out how much light falls on every tile of the map. Each light shines as far as it
+/// can see, fading with distance, and overlapping lights add together. Lights mounted in a
+/// wall shine out from the open tiles beside it.
+pub struct LightingSystem {}
+
+impl<'a> System<'a> for LightingSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Entity>,
+        Entities<'a>,
+        ReadStorage<'a, LightSource>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Fuel>,
+        WriteStorage<'a, Viewshed>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, player_entity, entities, lights, positions, fuel, mut viewsheds) = data;
+
+        let mut light = vec![RGB::from_f32(0.0, 0.0, 0.0); map.tiles.len()];
+        for (entity, source, pos) in (&entities, &lights, &positions).join() {
+            // A lantern with no fuel left gives no light
+            if fuel.get(entity).is_some_and(|f| f.turns < 1) {
+                continue;
+            }
+
+            let origin = Point::new(pos.x, pos.y);
+            for tile in lit_tiles(&map, origin, source.range) {
+                let idx = match map.xy_idx(tile.x, tile.y) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
+                let distance = rltk::DistanceAlg::Pythagoras.distance2d(origin, tile);
+                let intensity = 1.0 - (distance / source.range as f32);
+                if intensity <= 0.0 {
+                    continue;
+                }
+                let lit = light[idx] + (source.color * intensity);
+                light[idx] = RGB::from_f32(
+                    f32::min(lit.r, 1.0),
+                    f32::min(lit.g, 1.0),
+                    f32::min(lit.b, 1.0),
+                );
+            }
+        }
+        map.light = light;
+
+        // What the player can see in the dark changes as the lights do
+        if map.dark {
+            if let Some(viewshed) = viewsheds.get_mut(*player_entity) {
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
+
+/// The tiles a light at `origin` reaches
+fn lit_tiles(map: &Map, origin: Point, range: i32) -> Vec<Point> {
+    let idx = match map.xy_idx(origin.x, origin.y) {
+        Some(idx) => idx,
+        None => return Vec::new(),
+    };
+    if map.tiles[idx] != TileType::Wall {
+        return field_of_view(origin, range, map);
+    }
+
+    let mut tiles = vec![origin];
+    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+        let open = Point::new(origin.x + dx, origin.y + dy);
+        if map
+            .xy_idx(open.x, open.y)
+            .is_some_and(|neighbour| map.tiles[neighbour] != TileType::Wall)
+        {
+            for tile in field_of_view(open, range - 1, map) {
+                if !tiles.contains(&tile) {
+                    tiles.push(tile);
+                }
+            }
+        }
+    }
+    tiles
+}
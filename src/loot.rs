@@ -0,0 +1,223 @@
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+
+use super::components::{
+    Affixes, DefenseBonus, EquipmentSlot, Equippable, FireDamage, HealthBonus, MeleePowerBonus,
+    Name, Rarity, Regeneration, Renderable, Value,
+};
+use super::map::MAPWIDTH;
+use super::random_table::RandomTable;
+use super::spawner;
+
+#[derive(Clone, Copy)]
+enum AffixEffect {
+    Power(i32),
+    Defense(i32),
+    MaxHp(i32),
+    FireDamage(i32),
+    Regeneration(i32),
+}
+
+struct AffixDef {
+    name: &'static str,
+    /// The only kind of equipment the affix can appear on, if it is restricted
+    slot: Option<EquipmentSlot>,
+    effect: AffixEffect,
+}
+
+const fn affix(name: &'static str, slot: Option<EquipmentSlot>, effect: AffixEffect) -> AffixDef {
+    AffixDef { name, slot, effect }
+}
+
+const PREFIXES: [AffixDef; 6] = [
+    affix("Sharp", Some(EquipmentSlot::Melee), AffixEffect::Power(1)),
+    affix("Keen", Some(EquipmentSlot::Melee), AffixEffect::Power(2)),
+    affix("Flaming", Some(EquipmentSlot::Melee), AffixEffect::FireDamage(2)),
+    affix("Sturdy", Some(EquipmentSlot::Shield), AffixEffect::Defense(1)),
+    affix("Reinforced", Some(EquipmentSlot::Shield), AffixEffect::Defense(2)),
+    affix("Hale", None, AffixEffect::MaxHp(5)),
+];
+
+const SUFFIXES: [AffixDef; 5] = [
+    affix("of Vitality", None, AffixEffect::MaxHp(10)),
+    affix("of Regeneration", None, AffixEffect::Regeneration(1)),
+    affix("of Embers", Some(EquipmentSlot::Melee), AffixEffect::FireDamage(1)),
+    affix("of Might", Some(EquipmentSlot::Melee), AffixEffect::Power(1)),
+    affix("of Warding", Some(EquipmentSlot::Shield), AffixEffect::Defense(1)),
+];
+
+/// Base items the loot generator picks from
+pub fn loot_table(depth: i32) -> RandomTable {
+    RandomTable::new()
+        .add("Dagger", 10)
+        .add("Shield", 10)
+        .add("Longsword", depth)
+        .add("Tower Shield", depth)
+}
+
+/// What a monster may drop when it dies, by the name in its `LootTable`, or what a container
+/// may hold. Most of the time a monster drops nothing.
+pub fn drop_table(table: &str, depth: i32) -> RandomTable {
+    match table {
+        "Goblin" => RandomTable::new()
+            .add("None", 12)
+            .add("Gold", 6)
+            .add("Rations", 2)
+            .add("Health Potion", 2),
+        "Orc" => RandomTable::new()
+            .add("None", 10)
+            .add("Gold", 8)
+            .add("Health Potion", 3)
+            .add("Confusion Potion", 1)
+            .add("Magic Missile Scroll", 2)
+            .add("Treasure", 1 + (depth / 2)),
+        "Orc Warlord" => RandomTable::new().add("Treasure", 1),
+        "Chest" => RandomTable::new()
+            .add("Gold", 6)
+            .add("Treasure", 4 + (depth / 2))
+            .add("Health Potion", 3)
+            .add("Magic Missile Scroll", 2)
+            .add("Identify Scroll", 2)
+            .add("Enchant Scroll", 1)
+            .add("Wand of Magic Missile", 1),
+        "Barrel" => RandomTable::new()
+            .add("None", 6)
+            .add("Rations", 4)
+            .add("Health Potion", 2)
+            .add("Gold", 2)
+            .add("Key", 1),
+        _ => RandomTable::new(),
+    }
+}
+
+/// Rolls a base item for the depth and spawns it, with any affixes, at (x, y).
+pub fn spawn_loot(ecs: &mut World, x: i32, y: i32, depth: i32) -> Option<Entity> {
+    let base = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        loot_table(depth).roll(&mut rng)
+    };
+    let idx = ((y * MAPWIDTH as i32) + x) as usize;
+    spawner::spawn_entity(ecs, &(&idx, &base))
+}
+
+/// Deeper levels make magic and rare items more likely.
+fn roll_rarity(rng: &mut RandomNumberGenerator, depth: i32) -> Rarity {
+    let roll = rng.roll_dice(1, 100);
+    let rare_chance = i32::min(30, depth * 2);
+    let magic_chance = i32::min(60, 10 + (depth * 3));
+    if roll <= rare_chance {
+        Rarity::Rare
+    } else if roll <= rare_chance + magic_chance {
+        Rarity::Magic
+    } else {
+        Rarity::Common
+    }
+}
+
+fn pick_affix<'a>(
+    rng: &mut RandomNumberGenerator,
+    affixes: &'a [AffixDef],
+    slot: EquipmentSlot,
+) -> Option<&'a AffixDef> {
+    let allowed: Vec<&AffixDef> = affixes
+        .iter()
+        .filter(|affix| affix.slot.is_none_or(|s| s == slot))
+        .collect();
+    rng.random_slice_entry(&allowed).copied()
+}
+
+/// Rolls a rarity for a piece of equipment and gives it affixes to match: magic items get a
+/// prefix or a suffix, rare items get both. The item is renamed and recolored, and its value
+/// goes up with its rarity.
+pub fn roll_affixes(ecs: &mut World, item: Entity, depth: i32) {
+    let slot = match ecs.read_storage::<Equippable>().get(item) {
+        Some(equippable) => equippable.slot,
+        None => return,
+    };
+
+    let (rarity, prefix, suffix) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let rarity = roll_rarity(&mut rng, depth);
+        let (use_prefix, use_suffix) = match rarity {
+            Rarity::Common => return,
+            Rarity::Magic => {
+                let prefix = rng.roll_dice(1, 2) == 1;
+                (prefix, !prefix)
+            }
+            Rarity::Rare => (true, true),
+        };
+        let prefix = if use_prefix { pick_affix(&mut rng, &PREFIXES, slot) } else { None };
+        let suffix = if use_suffix { pick_affix(&mut rng, &SUFFIXES, slot) } else { None };
+        (rarity, prefix, suffix)
+    };
+
+    for affix in prefix.iter().chain(suffix.iter()) {
+        apply_effect(ecs, item, affix.effect);
+    }
+
+    if let Some(name) = ecs.write_storage::<Name>().get_mut(item) {
+        name.name = match (prefix, suffix) {
+            (Some(p), Some(s)) => format!("{} {} {}", p.name, name.name, s.name),
+            (Some(p), None) => format!("{} {}", p.name, name.name),
+            (None, Some(s)) => format!("{} {}", name.name, s.name),
+            (None, None) => name.name.clone(),
+        };
+    }
+    if let Some(renderable) = ecs.write_storage::<Renderable>().get_mut(item) {
+        renderable.fg = match rarity {
+            Rarity::Rare => RGB::named(rltk::GOLD),
+            _ => RGB::named(rltk::LIGHT_BLUE),
+        };
+    }
+    if let Some(value) = ecs.write_storage::<Value>().get_mut(item) {
+        value.gold *= match rarity {
+            Rarity::Rare => 4,
+            _ => 2,
+        };
+    }
+    ecs.write_storage::<Affixes>()
+        .insert(
+            item,
+            Affixes {
+                rarity,
+                prefix: prefix.map(|p| p.name.to_string()),
+                suffix: suffix.map(|s| s.name.to_string()),
+            },
+        )
+        .expect("Unable to insert affixes");
+}
+
+/// Adds an affix's effect to the item, stacking with anything it already has.
+fn apply_effect(ecs: &mut World, item: Entity, effect: AffixEffect) {
+    const DEAD_ITEM: &str = "Unable to add affix to a dead item";
+    match effect {
+        AffixEffect::Power(power) => {
+            let mut bonuses = ecs.write_storage::<MeleePowerBonus>();
+            let bonus =
+                bonuses.entry(item).expect(DEAD_ITEM).or_insert(MeleePowerBonus { power: 0 });
+            bonus.power += power;
+        }
+        AffixEffect::Defense(defense) => {
+            let mut bonuses = ecs.write_storage::<DefenseBonus>();
+            let bonus =
+                bonuses.entry(item).expect(DEAD_ITEM).or_insert(DefenseBonus { defense: 0 });
+            bonus.defense += defense;
+        }
+        AffixEffect::MaxHp(hp) => {
+            let mut bonuses = ecs.write_storage::<HealthBonus>();
+            let bonus = bonuses.entry(item).expect(DEAD_ITEM).or_insert(HealthBonus { hp: 0 });
+            bonus.hp += hp;
+        }
+        AffixEffect::FireDamage(damage) => {
+            let mut fire = ecs.write_storage::<FireDamage>();
+            let fire = fire.entry(item).expect(DEAD_ITEM).or_insert(FireDamage { damage: 0 });
+            fire.damage += damage;
+        }
+        AffixEffect::Regeneration(hp) => {
+            let mut regeneration = ecs.write_storage::<Regeneration>();
+            let regeneration =
+                regeneration.entry(item).expect(DEAD_ITEM).or_insert(Regeneration { hp: 0 });
+            regeneration.hp += hp;
+        }
+    }
+}
@@ -1,5 +1,5 @@
 rltk::add_wasm_support!();
-use rltk::{Console, GameState, Point, Rltk};
+use rltk::{console, Console, GameState, Point, RandomNumberGenerator, Rltk};
 use specs::prelude::*;
 use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
 #[macro_use]
@@ -7,21 +7,46 @@ extern crate specs_derive;
 
 mod components;
 pub use components::{
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DefenseBonus, EntityMoved,
-    EntryTrigger, Equippable, Equipped, Hidden, HungerClock, HungerState, InBackpack,
-    InflictsDamage, Item, MagicMapper, MeleePowerBonus, Monster, Name, ParticleLifetime, Player,
-    Position, ProvidesFood, ProvidesHealing, Ranged, Renderable, SerializationHelper, SerializeMe,
-    SingleActivation, SufferDamage, Viewshed, WantsToDropItem, WantsToMelee, WantsToPickupItem,
-    WantsToRemoveItem, WantsToUseItem,
+    Affixes, Alarm, Amulet, AreaOfEffect, Asleep, BlocksTile, Charges, CombatStats, Confusion,
+    Consumable, Container, Cursed, DefenseBonus, DestroysWalls, Digger, Enchantment, Encumbered,
+    EntityMoved, EntryTrigger, Equippable, Equipped, FireDamage, Fuel, Gold, HealthBonus, Hidden,
+    HungerClock, HungerState, InBackpack, InContainer, InflictsDamage, Investigating, Item, Key,
+    LeavesCorpse, LightSource, Locked, LootTable, MagicMapper, MeleePowerBonus, Monster, Name, Npc,
+    OtherLevelPosition, ParticleLifetime, Player, Position, ProvidesEnchantment, ProvidesFood,
+    ProvidesFuel, ProvidesHealing, ProvidesIdentification, ProvidesRecharge, ProvidesRemoveCurse,
+    Purse, Ranged, Rearming, Regeneration, ReleasesGas, Renderable, Rotting, SerializationHelper,
+    SerializeMe, SetsFire, Shatters, SingleActivation, Sneaking, SpillsLiquid, Sprung, Stackable,
+    Stealth, SufferDamage, TeleportsVictim, TrapDoor, Trapped, Value, Viewshed, WantsToDig,
+    WantsToDropItem, WantsToMelee, WantsToPickupItem, WantsToRemoveItem, WantsToThrowItem,
+    WantsToUseItem, Weight,
 };
+mod charges_system;
+pub use charges_system::ChargesSystem;
+mod containers;
 mod damage_system;
 pub use damage_system::DamageSystem;
+mod dig_system;
+pub use dig_system::DigSystem;
+mod dungeon_plan;
+mod enchantment_system;
+pub use enchantment_system::EnchantmentSystem;
+mod encumbrance_system;
+pub use encumbrance_system::EncumbranceSystem;
+mod environment_system;
+pub use environment_system::EnvironmentSystem;
+mod fuel_system;
+pub use fuel_system::FuelSystem;
 mod gamelog;
 mod gui;
+mod hall_of_fame;
 mod hunger_system;
+mod identification;
 pub use hunger_system::HungerSystem;
 mod inventory_system;
 pub use inventory_system::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem};
+mod lighting_system;
+pub use lighting_system::LightingSystem;
+mod loot;
 mod map;
 pub use map::*;
 pub mod map_builders;
@@ -31,27 +56,44 @@ mod melee_combat_system;
 pub use melee_combat_system::MeleeCombatSystem;
 mod monster_ai_system;
 pub use monster_ai_system::MonsterAI;
+mod noise_system;
+pub use noise_system::NoiseSystem;
 mod particle_system;
 pub use particle_system::ParticleSpawnSystem;
 mod player;
 pub use player::*;
 mod random_table;
 mod rect;
+mod regeneration_system;
+pub use regeneration_system::RegenerationSystem;
 pub use rect::*;
 mod rex_assets;
+mod rot_system;
+pub use rot_system::RotSystem;
 mod saveload_system;
+mod shop;
 mod spawner;
+mod stack_system;
+pub use stack_system::StackSystem;
+mod stats_system;
+pub use stats_system::StatsSystem;
+mod throw_system;
+pub use throw_system::ThrowSystem;
+mod traps;
 mod trigger_system;
 pub use trigger_system::TriggerSystem;
 mod visibility_system;
 pub use visibility_system::VisibilitySystem;
 
-const SHOW_MAPGEN_VISUALIZER: bool = true;
+const MAPGEN_DEFAULT_DELAY: f32 = 300.0;
+const MAPGEN_MIN_DELAY: f32 = 25.0;
+const MAPGEN_MAX_DELAY: f32 = 2400.0;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum RunState {
     AwaitingInput,
     GameOver,
+    HallOfFame,
     MagicMapReveal {
         row: i32,
     },
@@ -61,28 +103,54 @@ pub enum RunState {
     MapGeneration,
     MonsterTurn,
     NextLevel,
+    OpenContainer {
+        container: Entity,
+    },
     PlayerTurn,
     PreRun,
+    PreviousLevel,
     SaveGame,
+    ShowContainer {
+        container: Entity,
+        mode: gui::TransferMode,
+    },
     ShowDropItem,
+    ShowDropQuantity {
+        item: Entity,
+        quantity: i32,
+    },
     ShowInventory,
     ShowRemoveItem,
+    ShowShop {
+        vendor: Entity,
+        mode: gui::ShopMode,
+    },
     ShowTargeting {
         range: i32,
         item: Entity,
     },
+    ShowThrowItem,
+    ShowThrowTargeting {
+        item: Entity,
+    },
+    Victory,
 }
 
 pub struct State {
     pub ecs: World,
     mapgen_next_state: Option<RunState>,
-    mapgen_history: Vec<Map>,
+    mapgen_history: Vec<map_builders::MapSnapshot>,
     mapgen_index: usize,
     mapgen_timer: f32,
+    mapgen_delay: f32,
+    mapgen_paused: bool,
+    pub show_mapgen_visualizer: bool,
 }
 
 impl State {
     fn run_systems(&mut self) {
+        let mut lighting = LightingSystem {};
+        lighting.run_now(&self.ecs);
         let mut vis = VisibilitySystem {};
         vis.run_now(&self.ecs);
         let mut mob = MonsterAI {};
@@ -91,20 +159,44 @@ impl State {
         mapindex.run_now(&self.ecs);
         let mut trigger = TriggerSystem {};
         trigger.run_now(&self.ecs);
+        let mut environment = EnvironmentSystem {};
+        environment.run_now(&self.ecs);
         let mut melee = MeleeCombatSystem {};
         melee.run_now(&self.ecs);
+        let mut noise = NoiseSystem {};
+        noise.run_now(&self.ecs);
         let mut damage = DamageSystem {};
         damage.run_now(&self.ecs);
         let mut pickup = ItemCollectionSystem {};
         pickup.run_now(&self.ecs);
+        let mut charges = ChargesSystem {};
+        charges.run_now(&self.ecs);
+        let mut fuel = FuelSystem {};
+        fuel.run_now(&self.ecs);
+        let mut dig = DigSystem {};
+        dig.run_now(&self.ecs);
+        let mut stats = StatsSystem {};
+        stats.run_now(&self.ecs);
+        let mut enchantments = EnchantmentSystem {};
+        enchantments.run_now(&self.ecs);
         let mut items = ItemUseSystem {};
         items.run_now(&self.ecs);
+        let mut throw_items = ThrowSystem {};
+        throw_items.run_now(&self.ecs);
         let mut drop_items = ItemDropSystem {};
         drop_items.run_now(&self.ecs);
         let mut item_remove = ItemRemoveSystem {};
         item_remove.run_now(&self.ecs);
+        let mut stacks = StackSystem {};
+        stacks.run_now(&self.ecs);
+        let mut encumbrance = EncumbranceSystem {};
+        encumbrance.run_now(&self.ecs);
         let mut hunger = HungerSystem {};
         hunger.run_now(&self.ecs);
+        let mut regeneration = RegenerationSystem {};
+        regeneration.run_now(&self.ecs);
+        let mut rot = RotSystem {};
+        rot.run_now(&self.ecs);
         let mut particles = ParticleSpawnSystem {};
         particles.run_now(&self.ecs);
 
@@ -112,20 +204,11 @@ impl State {
     }
 
     fn goto_next_level(&mut self) {
-        // Delete entities that are not the player or their equipment.
-        let to_delete = self.entities_to_remove_on_level_change();
-        for target in to_delete {
-            self.ecs
-                .delete_entity(target)
-                .expect("Unable to delete entity");
-        }
-
-        // Build a new map and place the player
         let current_depth = {
             let worldmap_resource = self.ecs.fetch::<Map>();
             worldmap_resource.depth
         };
-        self.generate_world_map(current_depth+1);
+        self.change_level(current_depth + 1);
 
         // Notify the player and give them some health
         let player_entity = self.ecs.fetch::<Entity>();
@@ -141,6 +224,141 @@ impl State {
         }
     }
 
+    fn goto_previous_level(&mut self) {
+        let current_depth = {
+            let worldmap_resource = self.ecs.fetch::<Map>();
+            worldmap_resource.depth
+        };
+        self.change_level(current_depth - 1);
+
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        gamelog
+            .entries
+            .insert(0, "You climb back up the stairs.".to_string());
+    }
+
+    /// Leaves the current level, either storing it (if its theme is persistent) or deleting
+    /// everything on it, then moves the player to the new depth.
+    fn change_level(&mut self, new_depth: i32) {
+        let current_depth = {
+            let worldmap_resource = self.ecs.fetch::<Map>();
+            worldmap_resource.depth
+        };
+        let persistent =
+            dungeon_plan::theme_for_depth(current_depth).is_some_and(|theme| theme.persistent);
+        if persistent {
+            self.store_current_level();
+        } else {
+            // Delete entities that are not the player or their equipment.
+            let to_delete = self.entities_to_remove_on_level_change();
+            for target in to_delete {
+                self.ecs
+                    .delete_entity(target)
+                    .expect("Unable to delete entity");
+            }
+        }
+
+        let stored = {
+            let mut stored_maps = self.ecs.write_resource::<StoredMaps>();
+            stored_maps.maps.remove(&new_depth)
+        };
+        match stored {
+            Some(map) => self.restore_level(map, new_depth < current_depth),
+            None => self.generate_world_map(new_depth),
+        }
+    }
+
+    /// Keeps the current map and moves everything on it out of the way with an
+    /// `OtherLevelPosition`, so the level can be restored when the player returns.
+    fn store_current_level(&mut self) {
+        let to_store = self.entities_to_remove_on_level_change();
+        let map = (*self.ecs.fetch::<Map>()).clone();
+        let mut to_delete: Vec<Entity> = Vec::new();
+        {
+            let mut positions = self.ecs.write_storage::<Position>();
+            let mut other_level_positions = self.ecs.write_storage::<OtherLevelPosition>();
+            let particles = self.ecs.read_storage::<ParticleLifetime>();
+            let in_container = self.ecs.read_storage::<InContainer>();
+            for entity in to_store {
+                // Items in containers stay with the container they are in
+                if in_container.get(entity).is_some() {
+                    continue;
+                }
+                match positions.get(entity) {
+                    Some(pos) if particles.get(entity).is_none() => {
+                        other_level_positions
+                            .insert(
+                                entity,
+                                OtherLevelPosition {
+                                    x: pos.x,
+                                    y: pos.y,
+                                    depth: map.depth,
+                                },
+                            )
+                            .expect("Unable to insert other level position");
+                        positions.remove(entity);
+                    }
+                    _ => to_delete.push(entity),
+                }
+            }
+        }
+        for target in to_delete {
+            self.ecs
+                .delete_entity(target)
+                .expect("Unable to delete entity");
+        }
+
+        let mut stored_maps = self.ecs.write_resource::<StoredMaps>();
+        stored_maps.maps.insert(map.depth, map);
+    }
+
+    /// Puts a stored level back, returning its entities to their places. The player arrives
+    /// on the down stairs when coming up from below, otherwise on the up stairs.
+    fn restore_level(&mut self, mut map: Map, from_below: bool) {
+        self.mapgen_history.clear();
+        map.tile_content = vec![Vec::new(); map.tiles.len()];
+        let arrival = if from_below {
+            TileType::DownStairs
+        } else {
+            TileType::UpStairs
+        };
+        let start_idx = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == arrival)
+            .unwrap_or(0) as i32;
+        let player_start = Position {
+            x: start_idx % map.width,
+            y: start_idx / map.width,
+        };
+        let depth = map.depth;
+        {
+            let mut worldmap_resource = self.ecs.write_resource::<Map>();
+            *worldmap_resource = map;
+        }
+
+        {
+            let entities = self.ecs.entities();
+            let mut positions = self.ecs.write_storage::<Position>();
+            let mut other_level_positions = self.ecs.write_storage::<OtherLevelPosition>();
+            let mut restored: Vec<Entity> = Vec::new();
+            for (entity, pos) in (&entities, &other_level_positions).join() {
+                if pos.depth == depth {
+                    positions
+                        .insert(entity, Position { x: pos.x, y: pos.y })
+                        .expect("Unable to insert position");
+                    restored.push(entity);
+                }
+            }
+            for entity in restored {
+                other_level_positions.remove(entity);
+            }
+        }
+
+        self.place_player(&player_start);
+        spawner::restock_vendors(&mut self.ecs);
+    }
+
     fn entities_to_remove_on_level_change(&mut self) -> Vec<Entity> {
         let entities = self.ecs.entities();
         let player = self.ecs.read_storage::<Player>();
@@ -197,6 +415,15 @@ impl State {
             *player_entity_writer = player_entity;
         }
 
+        // Forget any levels kept from the last game, and everything the player identified, and
+        // start the new run from a fresh seed
+        self.ecs.write_resource::<StoredMaps>().maps.clear();
+        let seed = self.ecs.write_resource::<RandomNumberGenerator>().next_u64();
+        *self.ecs.write_resource::<RandomNumberGenerator>() = RandomNumberGenerator::seeded(seed);
+        *self.ecs.write_resource::<identification::ItemKnowledge>() =
+            identification::ItemKnowledge::new(seed);
+        *self.ecs.write_resource::<stats_system::RunStats>() = stats_system::RunStats::new(seed);
+
         // Build a new map and place the player
         self.generate_world_map(1);
     }
@@ -204,20 +431,68 @@ impl State {
     fn generate_world_map(&mut self, new_depth : i32) {
         self.mapgen_index = 0;
         self.mapgen_timer = 0.0;
+        self.mapgen_paused = false;
         self.mapgen_history.clear();
-        let mut builder = map_builders::random_builder(new_depth);
-        builder.build_map();
+        map_builders::record_snapshots(self.show_mapgen_visualizer);
+        let mut builder = {
+            let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+            let mut builder = map_builders::random_builder(new_depth, &mut rng);
+            builder.build_map(&mut rng);
+            builder
+        };
         self.mapgen_history = builder.get_snapshot_history();
         let player_start = {
             let mut worldmap_resource = self.ecs.write_resource::<Map>();
             *worldmap_resource = builder.get_map();
+            if let Some(theme) = dungeon_plan::theme_for_depth(new_depth) {
+                worldmap_resource.palette = theme.palette();
+                worldmap_resource.dark = theme.dark;
+                let mut rng = self.ecs.write_resource::<RandomNumberGenerator>();
+                environment_system::grow_grass(&mut worldmap_resource, &mut rng, theme.grass);
+                environment_system::fill_pools(&mut worldmap_resource, &mut rng, theme.pools);
+                let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+                gamelog.entries.insert(0, theme.entry_message.clone());
+            }
             builder.get_starting_position()
         };
+
+        // Report anything that would make the level unplayable
+        {
+            use map_builders::validation;
+            let map = self.ecs.fetch::<Map>();
+            for error in validation::validate_map(&map, &player_start) {
+                console::log(format!("Map validation failed: {}", error));
+            }
+            console::log(format!("Map metrics: {}", validation::map_metrics(&map, &player_start)));
+        }
+
+        // A persistent level above can be reached again by stairs where the player arrives
+        if dungeon_plan::theme_for_depth(new_depth - 1).is_some_and(|theme| theme.persistent) {
+            let mut map = self.ecs.write_resource::<Map>();
+            if let Some(idx) = map.xy_idx(player_start.x, player_start.y) {
+                map.tiles[idx] = TileType::UpStairs;
+            }
+        }
     
         // Spawn bad guys
         builder.spawn_entities(&mut self.ecs);
-    
-        // Place the player and update resources
+        spawner::restock_vendors(&mut self.ecs);
+        if new_depth > 1 {
+            spawner::spawn_wall_torches(&mut self.ecs);
+        }
+        if new_depth == dungeon_plan::final_depth() {
+            spawner::spawn_boss(&mut self.ecs);
+            self.ecs.fetch_mut::<gamelog::GameLog>().entries.insert(
+                0,
+                "This is the bottom of the dungeon. The way out lies down the stairs.".to_string(),
+            );
+        }
+
+        self.place_player(&player_start);
+    }
+
+    /// Moves the player to a new level's starting position and updates resources
+    fn place_player(&mut self, player_start: &Position) {
         let (player_x, player_y) = (player_start.x, player_start.y);
         let mut player_position = self.ecs.write_resource::<Point>();
         *player_position = Point::new(player_x, player_y);
@@ -250,7 +525,7 @@ impl GameState for State {
         particle_system::cull_dead_particles(&mut self.ecs, ctx);
 
         match newrunstate {
-            RunState::MainMenu { .. } => {}
+            RunState::MainMenu { .. } | RunState::HallOfFame => {}
             _ => {
                 draw_map(&self.ecs.fetch::<Map>(), ctx);
                 {
@@ -280,7 +555,8 @@ impl GameState for State {
                 newrunstate = player_input(self, ctx);
             }
             RunState::GameOver => {
-                let result = gui::game_over(ctx);
+                hall_of_fame::record_run(&mut self.ecs, false);
+                let result = gui::game_over(&self.ecs, ctx);
                 match result {
                     gui::GameOverResult::NoSelection => {}
                     gui::GameOverResult::QuitToMenu => {
@@ -291,6 +567,13 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::HallOfFame => {
+                if gui::hall_of_fame(ctx) {
+                    newrunstate = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::HallOfFame,
+                    };
+                }
+            }
             RunState::MagicMapReveal { row } => {
                 let mut map = self.ecs.fetch_mut::<Map>();
                 // let width = map.width;
@@ -323,6 +606,7 @@ impl GameState for State {
                             newrunstate = RunState::AwaitingInput;
                             saveload_system::delete_save();
                         }
+                        HallOfFame => newrunstate = RunState::HallOfFame,
                         Quit => {
                             ::std::process::exit(0);
                         }
@@ -330,17 +614,66 @@ impl GameState for State {
                 }
             }
             RunState::MapGeneration => {
-                if !SHOW_MAPGEN_VISUALIZER {
+                if !self.show_mapgen_visualizer || self.mapgen_history.is_empty() {
                     newrunstate = self.mapgen_next_state.unwrap();
-                }
-                ctx.cls();
-                draw_map(&self.mapgen_history[self.mapgen_index], ctx);
-
-                self.mapgen_timer += ctx.frame_time_ms;
-                if self.mapgen_timer > 300.0 {
-                    self.mapgen_timer = 0.0;
-                    self.mapgen_index += 1;
-                    if self.mapgen_index >= self.mapgen_history.len() {
+                } else {
+                    ctx.cls();
+                    let snapshot = &self.mapgen_history[self.mapgen_index];
+                    draw_map(&snapshot.map, ctx);
+
+                    let last_index = self.mapgen_history.len() - 1;
+                    let action = gui::mapgen_visualizer(
+                        ctx,
+                        &snapshot.phase,
+                        self.mapgen_index,
+                        self.mapgen_history.len(),
+                        self.mapgen_paused,
+                        self.mapgen_delay,
+                    );
+                    let mut finished = false;
+                    match action {
+                        gui::MapGenAction::NoAction => {}
+                        gui::MapGenAction::TogglePause => self.mapgen_paused = !self.mapgen_paused,
+                        gui::MapGenAction::StepForward => {
+                            self.mapgen_paused = true;
+                            if self.mapgen_index < last_index {
+                                self.mapgen_index += 1;
+                            } else {
+                                finished = true;
+                            }
+                        }
+                        gui::MapGenAction::StepBack => {
+                            self.mapgen_paused = true;
+                            if self.mapgen_index > 0 {
+                                self.mapgen_index -= 1;
+                            }
+                        }
+                        gui::MapGenAction::Faster => {
+                            self.mapgen_delay = f32::max(self.mapgen_delay / 2.0, MAPGEN_MIN_DELAY);
+                        }
+                        gui::MapGenAction::Slower => {
+                            self.mapgen_delay = f32::min(self.mapgen_delay * 2.0, MAPGEN_MAX_DELAY);
+                        }
+                        gui::MapGenAction::Finish => finished = true,
+                        gui::MapGenAction::Disable => {
+                            self.show_mapgen_visualizer = false;
+                            finished = true;
+                        }
+                    }
+
+                    if !self.mapgen_paused {
+                        self.mapgen_timer += ctx.frame_time_ms;
+                        if self.mapgen_timer > self.mapgen_delay {
+                            self.mapgen_timer = 0.0;
+                            if self.mapgen_index < last_index {
+                                self.mapgen_index += 1;
+                            } else {
+                                finished = true;
+                            }
+                        }
+                    }
+
+                    if finished {
                         newrunstate = self.mapgen_next_state.unwrap();
                     }
                 }
@@ -352,7 +685,11 @@ impl GameState for State {
             }
             RunState::NextLevel => {
                 self.goto_next_level();
-                newrunstate = RunState::PreRun;
+                self.mapgen_next_state = Some(RunState::PreRun);
+                newrunstate = RunState::MapGeneration;
+            }
+            RunState::OpenContainer { container } => {
+                newrunstate = containers::open_container(&mut self.ecs, container);
             }
             RunState::PlayerTurn => {
                 self.run_systems();
@@ -361,6 +698,7 @@ impl GameState for State {
                     RunState::MagicMapReveal { .. } => {
                         newrunstate = RunState::MagicMapReveal { row: 0 }
                     }
+                    RunState::NextLevel => newrunstate = RunState::NextLevel,
                     _ => newrunstate = RunState::MonsterTurn,
                 }
             }
@@ -369,12 +707,52 @@ impl GameState for State {
                 self.ecs.maintain();
                 newrunstate = RunState::AwaitingInput;
             }
+            RunState::PreviousLevel => {
+                self.goto_previous_level();
+                self.mapgen_next_state = Some(RunState::PreRun);
+                newrunstate = RunState::MapGeneration;
+            }
             RunState::SaveGame => {
                 saveload_system::save_game(&mut self.ecs);
                 newrunstate = RunState::MainMenu {
                     menu_selection: gui::MainMenuSelection::LoadGame,
                 };
             }
+            RunState::ShowContainer { container, mode } => {
+                let result = gui::show_container(self, ctx, container, mode);
+                match result.0 {
+                    gui::TransferResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::TransferResult::NoResponse => {}
+                    gui::TransferResult::SwitchMode => {
+                        let mode = match mode {
+                            gui::TransferMode::Put => gui::TransferMode::Take,
+                            gui::TransferMode::Take => gui::TransferMode::Put,
+                        };
+                        newrunstate = RunState::ShowContainer { container, mode };
+                    }
+                    gui::TransferResult::Selected => {
+                        let item = result.1.unwrap();
+                        match mode {
+                            gui::TransferMode::Put => {
+                                containers::put_item(&mut self.ecs, container, item)
+                            }
+                            gui::TransferMode::Take => {
+                                containers::take_item(&mut self.ecs, item);
+                            }
+                        }
+                        let mut stacks = StackSystem {};
+                        stacks.run_now(&self.ecs);
+                        self.ecs.maintain();
+                    }
+                    gui::TransferResult::TakeAll => {
+                        containers::take_all(&mut self.ecs, container);
+                        let mut stacks = StackSystem {};
+                        stacks.run_now(&self.ecs);
+                        self.ecs.maintain();
+                        newrunstate = RunState::AwaitingInput;
+                    }
+                }
+            }
             RunState::ShowDropItem => {
                 let result = gui::drop_item_menu(self, ctx);
                 match result.0 {
@@ -382,11 +760,41 @@ impl GameState for State {
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
                         let item_entity = result.1.unwrap();
+                        if stack_system::stack_count(&self.ecs, item_entity) > 1 {
+                            newrunstate = RunState::ShowDropQuantity {
+                                item: item_entity,
+                                quantity: 0,
+                            };
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToDropItem>();
+                            intent
+                                .insert(
+                                    *self.ecs.fetch::<Entity>(),
+                                    WantsToDropItem { item: item_entity },
+                                )
+                                .expect("Unable to insert intent");
+                            newrunstate = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowDropQuantity { item, quantity } => {
+                let result = gui::drop_quantity_prompt(self, ctx, item, quantity);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {
+                        newrunstate = RunState::ShowDropQuantity {
+                            item,
+                            quantity: result.1,
+                        };
+                    }
+                    gui::ItemMenuResult::Selected => {
+                        let dropped = stack_system::split_stack(&mut self.ecs, item, result.1);
                         let mut intent = self.ecs.write_storage::<WantsToDropItem>();
                         intent
                             .insert(
                                 *self.ecs.fetch::<Entity>(),
-                                WantsToDropItem { item: item_entity },
+                                WantsToDropItem { item: dropped },
                             )
                             .expect("Unable to insert intent");
                         newrunstate = RunState::PlayerTurn;
@@ -400,14 +808,34 @@ impl GameState for State {
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
                         let item_entity = result.1.unwrap();
-                        let is_ranged = self.ecs.read_storage::<Ranged>();
-                        let is_item_ranged = is_ranged.get(item_entity);
-                        if let Some(is_item_ranged) = is_item_ranged {
+                        let empty = self
+                            .ecs
+                            .read_storage::<Charges>()
+                            .get(item_entity)
+                            .is_some_and(|charges| charges.current < 1);
+                        let range = self
+                            .ecs
+                            .read_storage::<Ranged>()
+                            .get(item_entity)
+                            .map(|ranged| ranged.range);
+                        if empty {
+                            // Empty wands don't get as far as targeting or costing a turn
+                            let names = self.ecs.read_storage::<Name>();
+                            let name = &names.get(item_entity).unwrap().name;
+                            let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+                            gamelog
+                                .entries
+                                .insert(0, format!("The {} is out of charges.", name));
+                            newrunstate = RunState::AwaitingInput;
+                        } else if let Some(range) = range {
                             newrunstate = RunState::ShowTargeting {
-                                range: is_item_ranged.range,
+                                range,
                                 item: item_entity,
                             };
                         } else {
+                            // Only one item off the stack gets used
+                            let item_entity =
+                                stack_system::split_stack(&mut self.ecs, item_entity, 1);
                             let mut intent = self.ecs.write_storage::<WantsToUseItem>();
                             intent
                                 .insert(
@@ -441,12 +869,38 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ShowShop { vendor, mode } => {
+                let result = gui::show_shop(self, ctx, vendor, mode);
+                match result.0 {
+                    gui::ShopResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ShopResult::NoResponse => {}
+                    gui::ShopResult::SwitchMode => {
+                        let mode = match mode {
+                            gui::ShopMode::Buy => gui::ShopMode::Sell,
+                            gui::ShopMode::Sell => gui::ShopMode::Buy,
+                        };
+                        newrunstate = RunState::ShowShop { vendor, mode };
+                    }
+                    gui::ShopResult::Selected => {
+                        let item = result.1.unwrap();
+                        match mode {
+                            gui::ShopMode::Buy => shop::buy_item(&mut self.ecs, item),
+                            gui::ShopMode::Sell => shop::sell_item(&mut self.ecs, vendor, item),
+                        }
+                        // Bought and sold items join any stack they match right away
+                        let mut stacks = StackSystem {};
+                        stacks.run_now(&self.ecs);
+                        self.ecs.maintain();
+                    }
+                }
+            }
             RunState::ShowTargeting { range, item } => {
                 let result = gui::ranged_target(self, ctx, range);
                 match result.0 {
                     gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
+                        let item = stack_system::split_stack(&mut self.ecs, item, 1);
                         let mut intent = self.ecs.write_storage::<WantsToUseItem>();
                         intent
                             .insert(
@@ -461,6 +915,53 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ShowThrowItem => {
+                let result = gui::throw_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        newrunstate = RunState::ShowThrowTargeting {
+                            item: result.1.unwrap(),
+                        };
+                    }
+                }
+            }
+            RunState::ShowThrowTargeting { item } => {
+                let result = gui::ranged_target(self, ctx, throw_system::THROW_RANGE);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        // Only one item off the stack gets thrown
+                        let item = stack_system::split_stack(&mut self.ecs, item, 1);
+                        let mut intent = self.ecs.write_storage::<WantsToThrowItem>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToThrowItem {
+                                    item,
+                                    target: result.1.unwrap(),
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::Victory => {
+                hall_of_fame::record_run(&mut self.ecs, true);
+                let result = gui::victory(&self.ecs, ctx);
+                match result {
+                    gui::GameOverResult::NoSelection => {}
+                    gui::GameOverResult::QuitToMenu => {
+                        self.game_over_cleanup();
+                        newrunstate = RunState::MainMenu {
+                            menu_selection: gui::MainMenuSelection::NewGame,
+                        };
+                    }
+                }
+            }
         }
 
         {
@@ -471,6 +972,115 @@ impl GameState for State {
     }
 }
 
+/// Registers every component type with the world
+fn register_components(ecs: &mut World) {
+    ecs.register::<Position>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Monster>();
+    ecs.register::<Name>();
+    ecs.register::<BlocksTile>();
+    ecs.register::<CombatStats>();
+    ecs.register::<WantsToMelee>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<Item>();
+    ecs.register::<ProvidesHealing>();
+    ecs.register::<InBackpack>();
+    ecs.register::<WantsToPickupItem>();
+    ecs.register::<WantsToUseItem>();
+    ecs.register::<WantsToDropItem>();
+    ecs.register::<Consumable>();
+    ecs.register::<InflictsDamage>();
+    ecs.register::<Ranged>();
+    ecs.register::<AreaOfEffect>();
+    ecs.register::<Confusion>();
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.register::<SerializationHelper>();
+    ecs.register::<Equippable>();
+    ecs.register::<Equipped>();
+    ecs.register::<DefenseBonus>();
+    ecs.register::<MeleePowerBonus>();
+    ecs.register::<WantsToRemoveItem>();
+    ecs.register::<ParticleLifetime>();
+    ecs.register::<HungerClock>();
+    ecs.register::<ProvidesFood>();
+    ecs.register::<MagicMapper>();
+    ecs.register::<Hidden>();
+    ecs.register::<EntryTrigger>();
+    ecs.register::<EntityMoved>();
+    ecs.register::<SingleActivation>();
+    ecs.register::<Npc>();
+    ecs.register::<OtherLevelPosition>();
+    ecs.register::<Gold>();
+    ecs.register::<Purse>();
+    ecs.register::<Value>();
+    ecs.register::<ProvidesIdentification>();
+    ecs.register::<Enchantment>();
+    ecs.register::<Cursed>();
+    ecs.register::<ProvidesEnchantment>();
+    ecs.register::<ProvidesRemoveCurse>();
+    ecs.register::<Affixes>();
+    ecs.register::<FireDamage>();
+    ecs.register::<HealthBonus>();
+    ecs.register::<Regeneration>();
+    ecs.register::<LootTable>();
+    ecs.register::<LeavesCorpse>();
+    ecs.register::<Rotting>();
+    ecs.register::<Charges>();
+    ecs.register::<ProvidesRecharge>();
+    ecs.register::<Weight>();
+    ecs.register::<Encumbered>();
+    ecs.register::<Stackable>();
+    ecs.register::<Container>();
+    ecs.register::<InContainer>();
+    ecs.register::<Locked>();
+    ecs.register::<Key>();
+    ecs.register::<Trapped>();
+    ecs.register::<Shatters>();
+    ecs.register::<WantsToThrowItem>();
+    ecs.register::<Alarm>();
+    ecs.register::<Investigating>();
+    ecs.register::<Rearming>();
+    ecs.register::<ReleasesGas>();
+    ecs.register::<TeleportsVictim>();
+    ecs.register::<TrapDoor>();
+    ecs.register::<Sprung>();
+    ecs.register::<Fuel>();
+    ecs.register::<LightSource>();
+    ecs.register::<ProvidesFuel>();
+    ecs.register::<Asleep>();
+    ecs.register::<Sneaking>();
+    ecs.register::<Stealth>();
+    ecs.register::<SetsFire>();
+    ecs.register::<SpillsLiquid>();
+    ecs.register::<Digger>();
+    ecs.register::<DestroysWalls>();
+    ecs.register::<WantsToDig>();
+    ecs.register::<Amulet>();
+}
+
+/// A world set up like a game in progress: every component registered, the resources the
+/// systems need and a player standing at (1, 1)
+#[cfg(test)]
+fn test_world(depth: i32) -> World {
+    let mut ecs = World::new();
+    register_components(&mut ecs);
+    ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    ecs.insert(Map::new(depth));
+    ecs.insert(Point::new(1, 1));
+    ecs.insert(RandomNumberGenerator::seeded(1));
+    ecs.insert(identification::ItemKnowledge::new(1));
+    ecs.insert(stats_system::RunStats::new(1));
+    let player_entity = spawner::player(&mut ecs, 1, 1);
+    ecs.insert(player_entity);
+    ecs.insert(RunState::PlayerTurn);
+    ecs.insert(gamelog::GameLog { entries: Vec::new() });
+    ecs.insert(particle_system::ParticleBuilder::new());
+    ecs.insert(noise_system::NoiseBuilder::new());
+    ecs
+}
+
 fn main() {
     let mut context = Rltk::init_simple8x8(80, 50, "Hello Rust World", "resources");
     context.with_post_scanlines(true);
@@ -480,51 +1090,23 @@ fn main() {
         mapgen_next_state: Some(RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame }),
         mapgen_index: 0,
         mapgen_history: Vec::new(),
-        mapgen_timer: 0.0, 
+        mapgen_timer: 0.0,
+        mapgen_delay: MAPGEN_DEFAULT_DELAY,
+        mapgen_paused: false,
+        show_mapgen_visualizer: true,
     };
 
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<Name>();
-    gs.ecs.register::<BlocksTile>();
-    gs.ecs.register::<CombatStats>();
-    gs.ecs.register::<WantsToMelee>();
-    gs.ecs.register::<SufferDamage>();
-    gs.ecs.register::<Item>();
-    gs.ecs.register::<ProvidesHealing>();
-    gs.ecs.register::<InBackpack>();
-    gs.ecs.register::<WantsToPickupItem>();
-    gs.ecs.register::<WantsToUseItem>();
-    gs.ecs.register::<WantsToDropItem>();
-    gs.ecs.register::<Consumable>();
-    gs.ecs.register::<InflictsDamage>();
-    gs.ecs.register::<Ranged>();
-    gs.ecs.register::<AreaOfEffect>();
-    gs.ecs.register::<Confusion>();
-    gs.ecs.register::<SimpleMarker<SerializeMe>>();
-    gs.ecs.register::<SerializationHelper>();
-    gs.ecs.register::<Equippable>();
-    gs.ecs.register::<Equipped>();
-    gs.ecs.register::<DefenseBonus>();
-    gs.ecs.register::<MeleePowerBonus>();
-    gs.ecs.register::<WantsToRemoveItem>();
-    gs.ecs.register::<ParticleLifetime>();
-    gs.ecs.register::<HungerClock>();
-    gs.ecs.register::<ProvidesFood>();
-    gs.ecs.register::<MagicMapper>();
-    gs.ecs.register::<Hidden>();
-    gs.ecs.register::<EntryTrigger>();
-    gs.ecs.register::<EntityMoved>();
-    gs.ecs.register::<SingleActivation>();
+    register_components(&mut gs.ecs);
 
     gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
 
     gs.ecs.insert(Map::new(1));
+    gs.ecs.insert(StoredMaps::default());
     gs.ecs.insert(Point::new(0, 0));
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
+    let seed = RandomNumberGenerator::new().next_u64();
+    gs.ecs.insert(RandomNumberGenerator::seeded(seed));
+    gs.ecs.insert(identification::ItemKnowledge::new(seed));
+    gs.ecs.insert(stats_system::RunStats::new(seed));
     let player_entity = spawner::player(&mut gs.ecs, 0, 0);
     gs.ecs.insert(player_entity);
     // gs.ecs.insert(RunState::MainMenu {
@@ -535,6 +1117,7 @@ fn main() {
         entries: vec!["Welcome to Rusty Roguelike".to_string()],
     });
     gs.ecs.insert(particle_system::ParticleBuilder::new());
+    gs.ecs.insert(noise_system::NoiseBuilder::new());
     gs.ecs.insert(rex_assets::RexAssets::new());
 
     gs.generate_world_map(1);
@@ -1,19 +1,69 @@
 use rltk::{Algorithm2D, BaseMap, Console, Point, Rltk, RGB};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 pub const MAPWIDTH: usize = 80;
 pub const MAPHEIGHT: usize = 43;
 pub const MAPCOUNT: usize = MAPHEIGHT * MAPWIDTH;
+/// Extra pathing cost for stepping onto a trap that has been revealed, so monsters go around
+const KNOWN_TRAP_COST: f32 = 10.0;
+/// How strongly a light's color tints the tiles it falls on
+const LIGHT_TINT: f32 = 0.3;
+/// How bright unlit tiles next to the player look on dark levels
+const DARK_BRIGHTNESS: f32 = 0.35;
+/// Smoke or gas at least this thick can't be seen through
+const OPAQUE_CLOUD: i32 = 3;
 
 #[derive(Clone, Copy, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum TileType {
     DownStairs,
     Floor,
+    Road,
+    UpStairs,
     Wall,
 }
 
+/// The kinds of liquid that can pool on the floor
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Liquid {
+    Oil,
+    Water,
+}
+
+/// Liquid lying on a tile; deep puddles flow out into shallower neighbours
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Puddle {
+    pub liquid: Liquid,
+    pub depth: i32,
+}
+
+/// Foreground colors used to draw each kind of tile
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+pub struct MapPalette {
+    pub floor: RGB,
+    pub road: RGB,
+    pub wall: RGB,
+    pub stairs: RGB,
+}
+
+impl Default for MapPalette {
+    fn default() -> MapPalette {
+        MapPalette {
+            floor: RGB::from_f32(0.0, 0.5, 0.5),
+            road: RGB::from_f32(0.5, 0.4, 0.25),
+            wall: RGB::from_f32(0.0, 1.0, 0.0),
+            stairs: RGB::from_f32(0.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Persistent levels the player has left, keyed by depth
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct StoredMaps {
+    pub maps: HashMap<i32, Map>,
+}
+
 #[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Map {
     pub tiles: Vec<TileType>,
@@ -24,10 +74,41 @@ pub struct Map {
     pub blocked: Vec<bool>,
     pub depth: i32,
     pub bloodstains: HashSet<usize>,
+    pub tints: HashMap<usize, (RGB, RGB)>,
+    #[serde(default)]
+    pub palette: MapPalette,
+    /// How thick the poison gas is on each tile that has any
+    #[serde(default)]
+    pub gas: HashMap<usize, i32>,
+    /// How thick the smoke is on each tile that has any
+    #[serde(default)]
+    pub smoke: HashMap<usize, i32>,
+    /// Turns left for each burning tile to burn
+    #[serde(default)]
+    pub fire: HashMap<usize, i32>,
+    #[serde(default)]
+    pub liquids: HashMap<usize, Puddle>,
+    /// Floor tiles covered in grass, which catches fire
+    #[serde(default)]
+    pub grass: HashSet<usize>,
+    /// On dark levels the player can only see lit tiles and those right next to them
+    #[serde(default)]
+    pub dark: bool,
+    /// Turns of digging already put into each wall that has been started on
+    #[serde(default)]
+    pub dug: HashMap<usize, i32>,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub tile_content: Vec<Vec<Entity>>,
+    /// Tiles holding a trap that is no longer hidden
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub known_traps: HashSet<usize>,
+    /// The color and brightness of the light falling on each tile, recalculated every turn
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub light: Vec<RGB>,
 }
 
 impl Map {
@@ -42,6 +123,17 @@ impl Map {
             tile_content: vec![Vec::new(); MAPCOUNT],
             depth: new_depth,
             bloodstains: HashSet::new(),
+            tints: HashMap::new(),
+            palette: MapPalette::default(),
+            gas: HashMap::new(),
+            smoke: HashMap::new(),
+            fire: HashMap::new(),
+            liquids: HashMap::new(),
+            grass: HashSet::new(),
+            known_traps: HashSet::new(),
+            dark: false,
+            dug: HashMap::new(),
+            light: vec![RGB::from_f32(0.0, 0.0, 0.0); MAPCOUNT],
         }
     }
 
@@ -70,6 +162,38 @@ impl Map {
         }
     }
 
+    /// Whether a tile is a wall that can be dug or blasted away. The walls around the edge of
+    /// the map hold everything in, so they can't.
+    pub fn is_diggable(&self, idx: usize) -> bool {
+        let x = idx as i32 % self.width;
+        let y = idx as i32 / self.width;
+        self.tiles[idx] == TileType::Wall
+            && x > 0
+            && x < self.width - 1
+            && y > 0
+            && y < self.height - 1
+    }
+
+    /// Whether a wall is thin enough for a blast to knock down: there is open floor on both
+    /// sides of it.
+    pub fn is_weak_wall(&self, idx: usize) -> bool {
+        if !self.is_diggable(idx) {
+            return false;
+        }
+        let width = self.width as usize;
+        let open = |idx: usize| self.tiles[idx] != TileType::Wall;
+        (open(idx - 1) && open(idx + 1)) || (open(idx - width) && open(idx + width))
+    }
+
+    /// Turns a wall into floor. The new floor is revealed, since whoever knocked the wall down
+    /// knows it's gone.
+    pub fn destroy_wall(&mut self, idx: usize) {
+        self.tiles[idx] = TileType::Floor;
+        self.blocked[idx] = false;
+        self.revealed_tiles[idx] = true;
+        self.dug.remove(&idx);
+    }
+
     pub fn clear_content_index(&mut self) {
         for content in self.tile_content.iter_mut() {
             content.clear();
@@ -86,6 +210,8 @@ impl Algorithm2D for Map {
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
         self.tiles[idx as usize] == TileType::Wall
+            || self.smoke.get(&idx).is_some_and(|thickness| *thickness >= OPAQUE_CLOUD)
+            || self.gas.get(&idx).is_some_and(|thickness| *thickness >= OPAQUE_CLOUD)
     }
     
     fn get_pathing_distance(&self, idx1:usize, idx2:usize) -> f32 {
@@ -113,6 +239,11 @@ impl BaseMap for Map {
         if self.is_exit_valid(x-1, y+1) { exits.push(((idx+w)-1, 1.45)); }
         if self.is_exit_valid(x+1, y+1) { exits.push(((idx+w)+1, 1.45)); }
 
+        // Avoid traps everyone knows are there
+        for exit in exits.iter_mut() {
+            if self.known_traps.contains(&exit.0) { exit.1 += KNOWN_TRAP_COST; }
+        }
+
         exits
     }
 }
@@ -123,18 +254,63 @@ pub fn draw_map(map: &Map, ctx: &mut Rltk) {
     for (idx, tile) in map.tiles.iter().enumerate() {
         // Render a tile depending upon the tile type
         if map.revealed_tiles[idx] {
-            let (glyph, mut fg) = match tile {
-                TileType::DownStairs => (rltk::to_cp437('>'), RGB::from_f32(0.0, 1.0, 1.0)),
-                TileType::Floor => (rltk::to_cp437('.'), RGB::from_f32(0.0, 0.5, 0.5)),
-                TileType::Wall => (wall_glyph(&*map, x, y), RGB::from_f32(0.0, 1.0, 0.)),
+            let (mut glyph, mut fg) = match tile {
+                TileType::DownStairs => (rltk::to_cp437('>'), map.palette.stairs),
+                TileType::Floor => (rltk::to_cp437('.'), map.palette.floor),
+                TileType::Road => (rltk::to_cp437('░'), map.palette.road),
+                TileType::UpStairs => (rltk::to_cp437('<'), map.palette.stairs),
+                TileType::Wall => (wall_glyph(&*map, x, y), map.palette.wall),
             };
+            let mut bg = RGB::from_f32(0.0, 0.0, 0.0);
+
+            // Apply any tint painted onto the tile by a prefab
+            if let Some((tint_fg, tint_bg)) = map.tints.get(&idx) {
+                fg = *tint_fg;
+                bg = *tint_bg;
+            }
 
             // Render bloodstain, if present
-            let bg = if map.bloodstains.contains(&idx) { 
-                RGB::from_f32(0.75, 0.0, 0.0) 
-            } else { 
-                RGB::from_f32(0.0, 0.0, 0.0) 
-            };
+            if map.bloodstains.contains(&idx) { 
+                bg = RGB::from_f32(0.75, 0.0, 0.0);
+            }
+
+            // Render grass and liquids lying on the floor
+            if map.grass.contains(&idx) {
+                glyph = rltk::to_cp437('"');
+                fg = RGB::from_f32(0.2, 0.7, 0.2);
+            }
+            if let Some(puddle) = map.liquids.get(&idx) {
+                glyph = rltk::to_cp437('≈');
+                (fg, bg) = match puddle.liquid {
+                    Liquid::Oil => (RGB::from_f32(0.6, 0.5, 0.2), RGB::from_f32(0.2, 0.15, 0.05)),
+                    Liquid::Water => (RGB::from_f32(0.3, 0.5, 1.0), RGB::from_f32(0.0, 0.1, 0.4)),
+                };
+            }
+
+            // Render fire, poison gas and smoke the player can see
+            if map.visible_tiles[idx] {
+                if map.fire.contains_key(&idx) {
+                    glyph = rltk::to_cp437('▲');
+                    fg = RGB::named(rltk::YELLOW);
+                    bg = RGB::from_f32(0.8, 0.25, 0.0);
+                } else if map.gas.contains_key(&idx) {
+                    bg = RGB::from_f32(0.25, 0.6, 0.1);
+                } else if map.smoke.contains_key(&idx) {
+                    bg = RGB::from_f32(0.35, 0.35, 0.35);
+                }
+            }
+
+            // Tint what the player can see by the light falling on it
+            if map.visible_tiles[idx] {
+                let light = map.light.get(idx).copied().unwrap_or_else(RGB::new);
+                let intensity = f32::max(light.r, f32::max(light.g, light.b));
+                if intensity > 0.0 {
+                    fg = fg.lerp(light, intensity * LIGHT_TINT);
+                }
+                if map.dark {
+                    fg = fg * f32::max(DARK_BRIGHTNESS, intensity);
+                }
+            }
 
             if !map.visible_tiles[idx] { fg = fg.to_greyscale() }
             ctx.set(x, y, fg, bg, glyph);
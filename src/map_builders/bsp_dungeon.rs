@@ -5,13 +5,12 @@ use super::super::components::Position;
 use super::super::map::{ Map, TileType };
 use super::super::rect::Rect;
 use super::super::spawner;
-use super::super::SHOW_MAPGEN_VISUALIZER;
 use super::common;
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 
 pub struct BspDungeonBuilder {
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     map: Map,
     rects: Vec<Rect>,
     rooms: Vec<Rect>,
@@ -60,8 +59,7 @@ impl BspDungeonBuilder {
         ));
     }
 
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
 
         self.rects.clear();
         // Start with a single map sized rectangle
@@ -75,14 +73,14 @@ impl BspDungeonBuilder {
         // add it to the rooms list.
         let mut n_rooms = 0;
         while n_rooms < 240 {
-            let rect = self.get_random_rect(&mut rng);
-            let candidate = self.get_random_subrect(rect, &mut rng);
+            let rect = self.get_random_rect(rng);
+            let candidate = self.get_random_subrect(rect, rng);
 
             if self.is_possible(candidate) {
                 common::apply_room_to_map(&mut self.map, &candidate);
                 self.rooms.push(candidate);
                 self.add_subrects(rect);
-                self.take_snapshot();
+                self.take_snapshot("Place room");
             }
 
             n_rooms += 1;
@@ -98,7 +96,7 @@ impl BspDungeonBuilder {
             let end_x = next_room.x1 + (rng.roll_dice(1, i32::abs(next_room.x1-next_room.x2))-1);
             let end_y = next_room.y1 + (rng.roll_dice(1, i32::abs(next_room.y1-next_room.y2))-1);
             self.draw_corridor(start_x, start_y, end_x, end_y);
-            self.take_snapshot();
+            self.take_snapshot("Connect rooms");
         }
 
         // Add stairs
@@ -115,7 +113,7 @@ impl BspDungeonBuilder {
 
         // Spawn entities
         for room in self.rooms.iter().skip(1) {
-            spawner::spawn_room(&self.map, &mut rng, room, self.depth, &mut self.spawn_list);
+            spawner::spawn_room(&self.map, rng, room, self.depth, &mut self.spawn_list);
         }
     }
 
@@ -198,15 +196,15 @@ impl BspDungeonBuilder {
 }
 
 impl MapBuilder for BspDungeonBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -222,13 +220,7 @@ impl MapBuilder for BspDungeonBuilder {
         
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
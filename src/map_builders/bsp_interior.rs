@@ -2,16 +2,15 @@ use rltk::RandomNumberGenerator;
 
 use super::super::components::Position;
 use super::super::map::{ Map, TileType }; 
-use super::MapBuilder; 
+use super::{MapBuilder, MapSnapshot}; 
 use super::super::rect::Rect;
-use super::super::SHOW_MAPGEN_VISUALIZER;
 use super::super::spawner;
 
 const MIN_ROOM_SIZE: i32 = 8;
 
 pub struct BspInteriorBuilder {
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     map : Map,
     rects: Vec<Rect>,
     rooms: Vec<Rect>,
@@ -68,14 +67,13 @@ impl BspInteriorBuilder {
         }
     }
 
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
 
         // Start with a single map-sized rectangle. 
         self.rects.clear();
         self.rects.push( Rect::new(1, 1, self.map.width-2, self.map.height-2) );
         let first_room = self.rects[0];
-        self.add_subrects(first_room, &mut rng); // Divide the first room
+        self.add_subrects(first_room, rng); // Divide the first room
 
         let rooms = self.rects.clone();
         for r in rooms.iter() {
@@ -90,7 +88,7 @@ impl BspInteriorBuilder {
                 }
             }
 
-            self.take_snapshot();
+            self.take_snapshot("Partition room");
         }
 
         // Add corridors
@@ -102,7 +100,7 @@ impl BspInteriorBuilder {
             let end_x = next_room.x1 + (rng.roll_dice(1, i32::abs(next_room.x1-next_room.x2))-1);
             let end_y = next_room.y1 + (rng.roll_dice(1, i32::abs(next_room.y1-next_room.y2))-1);
             self.draw_corridor(start_x, start_y, end_x, end_y);
-            self.take_snapshot();
+            self.take_snapshot("Connect rooms");
         }
 
         // Add stairs
@@ -116,7 +114,7 @@ impl BspInteriorBuilder {
 
         // Spawn entities
         for room in self.rooms.iter().skip(1) {
-            spawner::spawn_room(&self.map, &mut rng, room, self.depth, &mut self.spawn_list);
+            spawner::spawn_room(&self.map, rng, room, self.depth, &mut self.spawn_list);
         }
     }
 
@@ -142,15 +140,15 @@ impl BspInteriorBuilder {
 }
 
 impl MapBuilder for BspInteriorBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -162,13 +160,7 @@ impl MapBuilder for BspInteriorBuilder {
         self.starting_position.clone()
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
\ No newline at end of file
@@ -5,12 +5,11 @@ use super::common;
 use super::super::components::Position;
 use super::super::map::{Map, TileType};
 use super::super::spawner;
-use super::super::SHOW_MAPGEN_VISUALIZER;
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 
 pub struct CellularAutomataBuilder {
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     noise_areas: HashMap<i32, Vec<usize>>,
     map: Map,
     spawn_list: Vec<(usize, String)>,
@@ -29,8 +28,7 @@ impl CellularAutomataBuilder {
         }
     }
 
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
 
         // First randomize the map (set ~55% to be wall)
         for y in 1..self.map.height - 1 {
@@ -44,7 +42,7 @@ impl CellularAutomataBuilder {
                 };
             }
         }
-        self.take_snapshot();
+        self.take_snapshot("Random noise");
 
         // Now iteratively apply cellular automata rules
         for _i in 0..15 {
@@ -88,7 +86,7 @@ impl CellularAutomataBuilder {
             }
 
             self.map.tiles = newtiles.clone();
-            self.take_snapshot();
+            self.take_snapshot("Cellular automata iteration");
         }
 
         // Find a starting point; start at the middle and walk left until finding an open tile
@@ -126,32 +124,32 @@ impl CellularAutomataBuilder {
         // Find all tiles we can reach from the starting point
         let exit_tile = 
             common::remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
-        self.take_snapshot();
+        self.take_snapshot("Remove unreachable areas");
 
         // Place the stairs
         self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.take_snapshot();
+        self.take_snapshot("Place stairs");
 
         // Build a noise map for spawning entities
-        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, &mut rng);
+        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, rng);
 
         // Spawn entities
         for area in self.noise_areas.iter() {
-            spawner::spawn_region(&self.map, &mut rng, area.1, self.depth, &mut self.spawn_list);
+            spawner::spawn_region(&self.map, rng, area.1, self.depth, &mut self.spawn_list);
         }
     }
 }
 
 impl MapBuilder for CellularAutomataBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -163,13 +161,7 @@ impl MapBuilder for CellularAutomataBuilder {
         self.starting_position.clone()
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
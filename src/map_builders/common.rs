@@ -1,4 +1,4 @@
-use rltk::{CellularDistanceFunction, DijkstraMap, FastNoise, RandomNumberGenerator};
+use rltk::{BaseMap, CellularDistanceFunction, DijkstraMap, FastNoise, RandomNumberGenerator};
 use std::cmp::{max, min};
 use std::collections::HashMap;
 
@@ -139,9 +139,12 @@ pub fn paint(map: &mut Map, mode: Symmetry, brush_size: i32, x: i32, y: i32) {
 
 /// Searches a map, removes unreachable areas and returns the most distant tile
 pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx: usize) -> usize {
-    // Find all tiles we can reach from the starting point
+    // Find all tiles we can reach from the starting point; walls only block pathing once
+    // they are marked as blocked
+    map.populate_blocked();
     let map_starts: Vec<usize> = vec![start_idx];
-    let dijkstra_map = DijkstraMap::new(map.width, map.height, &map_starts, map, 200.0);
+    let dijkstra_map =
+        DijkstraMap::new(map.width, map.height, &map_starts, map, map.tiles.len() as f32);
     let mut exit_tile = (0, 0.0f32);
     for (i, tile) in map.tiles.iter_mut().enumerate() {
         if *tile == TileType::Floor {
@@ -161,3 +164,41 @@ pub fn remove_unreachable_areas_returning_most_distant(map: &mut Map, start_idx:
 
     exit_tile.0
 }
+
+/// Returns the floor tile nearest the middle of the map that lies in the largest connected
+/// area, so generators that leave isolated pockets don't start the player in one of them.
+pub fn start_in_largest_area(map: &mut Map) -> Option<usize> {
+    map.populate_blocked();
+    let mut visited = vec![false; map.tiles.len()];
+    let mut largest: Vec<usize> = Vec::new();
+    for idx in 0..map.tiles.len() {
+        if map.tiles[idx] != TileType::Floor || visited[idx] {
+            continue;
+        }
+
+        // Flood fill the area this tile belongs to
+        visited[idx] = true;
+        let mut area = vec![idx];
+        let mut i = 0;
+        while i < area.len() {
+            for (exit, _) in map.get_available_exits(area[i]) {
+                if map.tiles[exit] == TileType::Floor && !visited[exit] {
+                    visited[exit] = true;
+                    area.push(exit);
+                }
+            }
+            i += 1;
+        }
+
+        if area.len() > largest.len() {
+            largest = area;
+        }
+    }
+
+    let (center_x, center_y) = (map.width / 2, map.height / 2);
+    largest.into_iter().min_by_key(|idx| {
+        let dx = (*idx as i32 % map.width) - center_x;
+        let dy = (*idx as i32 / map.width) - center_y;
+        (dx * dx) + (dy * dy)
+    })
+}
@@ -1,13 +1,11 @@
 use rltk::{ line2d, LineAlg, Point, RandomNumberGenerator };
-use specs::prelude::*;
 use std::collections::HashMap;
 
 use super::super::components::Position;
 use super::super::map::{Map, TileType};
 use super::super::spawner;
-use super::super::SHOW_MAPGEN_VISUALIZER;
 use super::common::{ self, Symmetry };
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum DLAAlgorithm {
@@ -27,8 +25,9 @@ pub struct DLABuilder {
     map: Map,
     starting_position: Position,
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     noise_areas: HashMap<i32, Vec<usize>>,
+    spawn_list: Vec<(usize, String)>,
     settings: DLASettings,
 }
 
@@ -41,6 +40,7 @@ impl DLABuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings,
         }
     }
@@ -52,6 +52,7 @@ impl DLABuilder {
             depth : new_depth,
             history: Vec::new(),
             noise_areas : HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DLASettings {
                 algorithm: DLAAlgorithm::CentralAttractor,
                 brush_size: 2,
@@ -62,11 +63,9 @@ impl DLABuilder {
     }
 
     #[allow(clippy::map_entry)]
-    fn build(&mut self) {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
         const SNAPSHOT_INTERVAL: usize = 50;
 
-        let mut rng = RandomNumberGenerator::new();
-
         // Set a central starting point
         self.starting_position = Position {
             x: self.map.width / 2,
@@ -76,7 +75,7 @@ impl DLABuilder {
             .map
             .xy_idx(self.starting_position.x, self.starting_position.y)
             .unwrap();
-        self.take_snapshot();
+        self.take_snapshot("Seed starting area");
         self.map.tiles[start_idx] = TileType::Floor;
         self.map.tiles[start_idx-1] = TileType::Floor;
         self.map.tiles[start_idx+1] = TileType::Floor;
@@ -153,13 +152,13 @@ impl DLABuilder {
             }
 
             if i % SNAPSHOT_INTERVAL == 0 {
-                self.take_snapshot();
+                self.take_snapshot("Aggregate floor");
             }
             i += 1;
 
             floor_tile_count = self.map.tiles.iter().filter(|a| **a == TileType::Floor).count();
         }
-        self.take_snapshot();
+        self.take_snapshot("Aggregation complete");
 
         // Find all tiles we can reach from the starting point
         let exit_tile =
@@ -167,10 +166,15 @@ impl DLABuilder {
 
         // Place the stairs
         self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.take_snapshot();
+        self.take_snapshot("Place stairs");
 
         // Now we build a noise map for use in spawning entities later
-        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, &mut rng);
+        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, rng);
+
+        // Spawn entities
+        for area in self.noise_areas.iter() {
+            spawner::spawn_region(&self.map, rng, area.1, self.depth, &mut self.spawn_list);
+        }
     }
 
     pub fn insectoid(new_depth : i32) -> DLABuilder {
@@ -180,6 +184,7 @@ impl DLABuilder {
             depth : new_depth,
             history: Vec::new(),
             noise_areas : HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DLASettings {
                 algorithm: DLAAlgorithm::CentralAttractor,
                 brush_size: 2,
@@ -196,6 +201,7 @@ impl DLABuilder {
             depth : new_depth,
             history: Vec::new(),
             noise_areas : HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DLASettings {
                 algorithm: DLAAlgorithm::WalkInwards,
                 brush_size: 1,
@@ -212,6 +218,7 @@ impl DLABuilder {
             depth : new_depth,
             history: Vec::new(),
             noise_areas : HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DLASettings {
                 algorithm: DLAAlgorithm::WalkOutwards,
                 brush_size: 2,
@@ -223,15 +230,15 @@ impl DLABuilder {
 }
 
 impl MapBuilder for DLABuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -239,19 +246,11 @@ impl MapBuilder for DLABuilder {
         self.starting_position.clone()
     }
 
-    fn spawn_entities(&mut self, ecs: &mut World) {
-        for area in self.noise_areas.iter() {
-            spawner::spawn_region(ecs, area.1, self.depth);
-        }
+    fn get_spawn_list(&self) -> &Vec<(usize, String)> {
+        &self.spawn_list
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
@@ -1,14 +1,12 @@
 use rltk::{ console, RandomNumberGenerator };
-use specs::prelude::*;
 use std::collections::HashMap;
 
 use super::common::Symmetry;
 use super::super::components::Position;
 use super::super::map::{Map, TileType};
 use super::super::spawner;
-use super::super::SHOW_MAPGEN_VISUALIZER;
 use super::common;
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum DrunkSpawnMode {
@@ -28,8 +26,9 @@ pub struct DrunkardsWalkBuilder {
     map: Map,
     starting_position: Position,
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     noise_areas: HashMap<i32, Vec<usize>>,
+    spawn_list: Vec<(usize, String)>,
     settings: DrunkardSettings,
 }
 
@@ -42,13 +41,13 @@ impl DrunkardsWalkBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings
         }
     }
 
     #[allow(clippy::map_entry)]
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
 
         // Set a central starting point
         self.starting_position = Position {
@@ -76,7 +75,16 @@ impl DrunkardsWalkBuilder {
                     if digger_count == 0 {
                         (self.starting_position.x, self.starting_position.y)
                     } else {
-                        (rng.roll_dice(1, self.map.width-3)+1, rng.roll_dice(1, self.map.height-3)+1)
+                        // Start somewhere that has already been dug, so every digger's
+                        // tunnels stay connected to the starting point
+                        let dug: Vec<usize> = self.map.tiles
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, t)| **t == TileType::Floor)
+                            .map(|(i, _)| i)
+                            .collect();
+                        let idx = dug[(rng.roll_dice(1, dug.len() as i32) - 1) as usize] as i32;
+                        (idx % self.map.width, idx / self.map.width)
                     }
                 }
                 DrunkSpawnMode::StartingPoint => (self.starting_position.x, self.starting_position.y),
@@ -103,7 +111,7 @@ impl DrunkardsWalkBuilder {
             }
 
             if did_something {
-                self.take_snapshot();
+                self.take_snapshot("Drunken dig");
                 active_digger_count += 1;
             }
         
@@ -123,17 +131,22 @@ impl DrunkardsWalkBuilder {
             )
         );
 
-        self.take_snapshot();
+        self.take_snapshot("Digging complete");
 
         // Find all tiles we can reach from the starting point
         let exit_tile =
             common::remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
         // Place the stairs
         self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.take_snapshot();
+        self.take_snapshot("Place stairs");
 
         // Now we build a noise map for use in spawning entities later
-        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, &mut rng);
+        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, rng);
+
+        // Spawn entities
+        for area in self.noise_areas.iter() {
+            spawner::spawn_region(&self.map, rng, area.1, self.depth, &mut self.spawn_list);
+        }
     }
 
     pub fn fat_passage(new_depth: i32) -> DrunkardsWalkBuilder {
@@ -143,6 +156,7 @@ impl DrunkardsWalkBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DrunkardSettings {
                 spawn_mode: DrunkSpawnMode::Random,
                 drunken_lifetime: 100,
@@ -160,6 +174,7 @@ impl DrunkardsWalkBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DrunkardSettings {
                 spawn_mode: DrunkSpawnMode::Random,
                 drunken_lifetime: 100,
@@ -177,6 +192,7 @@ impl DrunkardsWalkBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DrunkardSettings {
                 spawn_mode: DrunkSpawnMode::StartingPoint,
                 drunken_lifetime: 400,
@@ -194,6 +210,7 @@ impl DrunkardsWalkBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DrunkardSettings {
                 spawn_mode: DrunkSpawnMode::Random,
                 drunken_lifetime: 400,
@@ -211,6 +228,7 @@ impl DrunkardsWalkBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: DrunkardSettings {
                 spawn_mode: DrunkSpawnMode::Random,
                 drunken_lifetime: 100,
@@ -223,15 +241,15 @@ impl DrunkardsWalkBuilder {
 }
 
 impl MapBuilder for DrunkardsWalkBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -239,19 +257,11 @@ impl MapBuilder for DrunkardsWalkBuilder {
         self.starting_position.clone()
     }
 
-    fn spawn_entities(&mut self, ecs: &mut World) {
-        for area in self.noise_areas.iter() {
-            spawner::spawn_region(ecs, area.1, self.depth);
-        }
+    fn get_spawn_list(&self) -> &Vec<(usize, String)> {
+        &self.spawn_list
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
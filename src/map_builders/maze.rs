@@ -1,13 +1,11 @@
 use rltk::RandomNumberGenerator;
-use specs::prelude::*;
 use std::collections::HashMap;
 
 use super::common;
 use super::super::components::Position;
 use super::super::map::{Map, TileType};
 use super::super::spawner;
-use super::super::SHOW_MAPGEN_VISUALIZER;
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 
 const TOP : usize = 0;
 const RIGHT : usize = 1;
@@ -155,7 +153,7 @@ impl<'a> Grid<'a> {
     
             if i % SNAPSHOT_INTERVAL == 0 {
                 self.copy_to_map(&mut generator.map);
-                generator.take_snapshot();
+                generator.take_snapshot("Carve maze");
             }
             i += 1;    
         }
@@ -188,8 +186,9 @@ pub struct MazeBuilder {
     map: Map,
     starting_position: Position,
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     noise_areas: HashMap<i32, Vec<usize>>,
+    spawn_list: Vec<(usize, String)>,
 }
 
 impl MazeBuilder {
@@ -200,15 +199,15 @@ impl MazeBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
         }
     }
 
     #[allow(clippy::map_entry)]
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
 
         // Generate maze
-        let mut maze = Grid::new(self.map.width/2 - 2, self.map.height/2 - 2, &mut rng);
+        let mut maze = Grid::new(self.map.width/2 - 2, self.map.height/2 - 2, rng);
         maze.generate_maze(self);
 
         // Find a starting point; start at the middle and walk left until finding an open tile
@@ -224,17 +223,22 @@ impl MazeBuilder {
                 .xy_idx(self.starting_position.x, self.starting_position.y)
                 .unwrap();
         }
-        self.take_snapshot();
+        self.take_snapshot("Find starting point");
 
         // Find all tiles we can reach from the starting point
         let exit_tile =
             common::remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
         // Place the stairs
         self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.take_snapshot();
+        self.take_snapshot("Place stairs");
 
         // Now we build a noise map for use in spawning entities later
-        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, &mut rng);
+        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, rng);
+
+        // Spawn entities
+        for area in self.noise_areas.iter() {
+            spawner::spawn_region(&self.map, rng, area.1, self.depth, &mut self.spawn_list);
+        }
     }
 }
 
@@ -247,27 +251,19 @@ impl MapBuilder for MazeBuilder {
         self.starting_position.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
-    fn spawn_entities(&mut self, ecs: &mut World) {
-        for area in self.noise_areas.iter() {
-            spawner::spawn_region(ecs, area.1, self.depth);
-        }
+    fn get_spawn_list(&self) -> &Vec<(usize, String)> {
+        &self.spawn_list
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
@@ -1,7 +1,9 @@
-use rltk::RandomNumberGenerator;
+use rltk::{console, RandomNumberGenerator};
 use specs::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::components::Position;
+use super::dungeon_plan::{self, BuilderStep};
 use super::map::Map;
 use super::spawner;
 
@@ -19,18 +21,54 @@ use drunkard::DrunkardsWalkBuilder;
 mod maze;
 use maze::MazeBuilder;
 mod prefab_builder;
-use prefab_builder::PrefabBuilder;
+use prefab_builder::{PrefabBuilder, PrefabLevel, PrefabSection};
 mod simple_map;
 use simple_map::SimpleMapBuilder;
+mod town;
+use town::TownBuilder;
+pub mod validation;
 mod voronoi;
 use voronoi::VoronoiCellBuilder;
 mod waveform_collapse;
 use waveform_collapse::WaveformCollapseBuilder;
 
+/// A copy of a map under construction, labelled with the builder phase that produced it
+#[derive(Clone)]
+pub struct MapSnapshot {
+    pub phase: String,
+    pub map: Map,
+}
+
+impl MapSnapshot {
+    /// Copies `map` with every tile revealed so the visualizer can draw all of it
+    pub fn new(map: &Map, phase: &str) -> MapSnapshot {
+        let mut map = map.clone();
+        for v in map.revealed_tiles.iter_mut() {
+            *v = true;
+        }
+        MapSnapshot { phase: phase.to_string(), map }
+    }
+
+    /// Adds a snapshot of `map` to a builder's history, unless snapshots are switched off
+    pub fn take(history: &mut Vec<MapSnapshot>, map: &Map, phase: &str) {
+        if RECORD_SNAPSHOTS.load(Ordering::Relaxed) {
+            history.push(MapSnapshot::new(map, phase));
+        }
+    }
+}
+
+/// Whether builders keep snapshots for the visualizer. Copying the map after every step is
+/// wasted work when the visualizer is switched off.
+static RECORD_SNAPSHOTS: AtomicBool = AtomicBool::new(true);
+
+pub fn record_snapshots(enabled: bool) {
+    RECORD_SNAPSHOTS.store(enabled, Ordering::Relaxed);
+}
+
 pub trait MapBuilder {
-    fn build_map(&mut self);
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator);
     fn get_map(&mut self) -> Map;
-    fn get_snapshot_history(&self) -> Vec<Map>;
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot>;
     fn get_spawn_list(&self) -> &Vec<(usize, String)>;
     fn get_starting_position(&mut self) -> Position;
 
@@ -40,37 +78,112 @@ pub trait MapBuilder {
         }
     }
 
-    fn take_snapshot(&mut self);
+    fn take_snapshot(&mut self, phase: &str);
 }
 
-pub fn random_builder(new_depth: i32) -> Box<dyn MapBuilder> {
-    // let mut rng = RandomNumberGenerator::new();
-    // let mut result: Box<dyn MapBuilder> = match rng.roll_dice(1, 16) {
-    //     1 => Box::new(BspDungeonBuilder::new(new_depth)),
-    //     2 => Box::new(BspInteriorBuilder::new(new_depth)),
-    //     3 => Box::new(CellularAutomataBuilder::new(new_depth)),
-    //     4 => Box::new(DrunkardsWalkBuilder::open_area(new_depth)),
-    //     5 => Box::new(DrunkardsWalkBuilder::open_halls(new_depth)),
-    //     6 => Box::new(DrunkardsWalkBuilder::winding_passages(new_depth)),
-    //     7 => Box::new(DrunkardsWalkBuilder::fat_passage(new_depth)),
-    //     8 => Box::new(DrunkardsWalkBuilder::fearful_symmetry(new_depth)),
-    //     9 => Box::new(MazeBuilder::new(new_depth)),
-    //     10 => Box::new(DLABuilder::central_attractor(new_depth)),
-    //     11 => Box::new(DLABuilder::insectoid(new_depth)),
-    //     12 => Box::new(DLABuilder::walk_inwards(new_depth)),
-    //     13 => Box::new(DLABuilder::walk_outwards(new_depth)),
-    //     14 => Box::new(VoronoiCellBuilder::manhattan(new_depth)),
-    //     15 => Box::new(VoronoiCellBuilder::pythagoras(new_depth)),
-    //     _ => Box::new(SimpleMapBuilder::new(new_depth)),
-    // };
-
-    // if rng.roll_dice(1, 3) == 1 {
-    //     result = Box::new(WaveformCollapseBuilder::derived_map(new_depth, result));
-    // }
-
-    // result
-    Box::new(PrefabBuilder::new(
-        new_depth,
-        Some(Box::new(CellularAutomataBuilder::new(new_depth))),
-    ))
+/// Picks the builder for a level from the dungeon plan's theme for its depth. Depths the
+/// plan doesn't cover get caves with the underground fort.
+pub fn random_builder(new_depth: i32, rng: &mut RandomNumberGenerator) -> Box<dyn MapBuilder> {
+    match dungeon_plan::theme_for_depth(new_depth) {
+        Some(theme) => themed_builder(new_depth, &theme.pipeline, rng),
+        None => section_builder(
+            new_depth,
+            "underground_fort.prefab",
+            Box::new(CellularAutomataBuilder::new(new_depth)),
+        ),
+    }
+}
+
+/// Builders that a dungeon plan can refer to by name
+fn named_builder(name: &str, new_depth: i32) -> Option<Box<dyn MapBuilder>> {
+    let builder: Box<dyn MapBuilder> = match name {
+        "simple_map" => Box::new(SimpleMapBuilder::new(new_depth)),
+        "bsp_dungeon" => Box::new(BspDungeonBuilder::new(new_depth)),
+        "bsp_interior" => Box::new(BspInteriorBuilder::new(new_depth)),
+        "cellular_automata" => Box::new(CellularAutomataBuilder::new(new_depth)),
+        "drunkard_open_area" => Box::new(DrunkardsWalkBuilder::open_area(new_depth)),
+        "drunkard_open_halls" => Box::new(DrunkardsWalkBuilder::open_halls(new_depth)),
+        "drunkard_winding_passages" => Box::new(DrunkardsWalkBuilder::winding_passages(new_depth)),
+        "drunkard_fat_passage" => Box::new(DrunkardsWalkBuilder::fat_passage(new_depth)),
+        "drunkard_fearful_symmetry" => Box::new(DrunkardsWalkBuilder::fearful_symmetry(new_depth)),
+        "maze" => Box::new(MazeBuilder::new(new_depth)),
+        "dla_central_attractor" => Box::new(DLABuilder::central_attractor(new_depth)),
+        "dla_insectoid" => Box::new(DLABuilder::insectoid(new_depth)),
+        "dla_walk_inwards" => Box::new(DLABuilder::walk_inwards(new_depth)),
+        "dla_walk_outwards" => Box::new(DLABuilder::walk_outwards(new_depth)),
+        "voronoi_manhattan" => Box::new(VoronoiCellBuilder::manhattan(new_depth)),
+        "voronoi_pythagoras" => Box::new(VoronoiCellBuilder::pythagoras(new_depth)),
+        "voronoi_chebyshev" => Box::new(VoronoiCellBuilder::chebyshev(new_depth)),
+        "town" => Box::new(TownBuilder::new(new_depth)),
+        _ => return None,
+    };
+    Some(builder)
+}
+
+fn section_builder(
+    new_depth: i32,
+    file_name: &str,
+    previous_builder: Box<dyn MapBuilder>,
+) -> Box<dyn MapBuilder> {
+    match PrefabSection::load(file_name) {
+        Ok(section) => Box::new(PrefabBuilder::sectional(new_depth, section, previous_builder)),
+        Err(e) => {
+            console::log(format!("{}", e));
+            previous_builder
+        }
+    }
+}
+
+/// Runs each step of a theme's pipeline in turn. Anything the plan gets wrong is logged
+/// and replaced with a cellular automata map, so a bad plan never stops the game.
+fn themed_builder(
+    new_depth: i32,
+    pipeline: &[BuilderStep],
+    rng: &mut RandomNumberGenerator,
+) -> Box<dyn MapBuilder> {
+    let fallback = || -> Box<dyn MapBuilder> { Box::new(CellularAutomataBuilder::new(new_depth)) };
+
+    let mut builder: Option<Box<dyn MapBuilder>> = None;
+    for step in pipeline.iter() {
+        builder = Some(match step {
+            BuilderStep::Random(names) if !names.is_empty() => {
+                let name = &names[(rng.roll_dice(1, names.len() as i32) - 1) as usize];
+                named_builder(name, new_depth).unwrap_or_else(|| {
+                    console::log(format!("Unknown map builder '{}'", name));
+                    fallback()
+                })
+            }
+            BuilderStep::Random(_) => fallback(),
+            BuilderStep::Level(file_name) => match PrefabLevel::load(file_name) {
+                Ok(level) => Box::new(PrefabBuilder::level(new_depth, level)),
+                Err(e) => {
+                    console::log(format!("{}", e));
+                    fallback()
+                }
+            },
+            BuilderStep::Section(file_name) => {
+                let previous_builder = builder.take().unwrap_or_else(fallback);
+                section_builder(new_depth, file_name, previous_builder)
+            }
+            BuilderStep::WaveformSample(file_name) => {
+                match WaveformCollapseBuilder::rex_sample(new_depth, file_name) {
+                    Ok(builder) => Box::new(builder),
+                    Err(e) => {
+                        console::log(format!("{}", e));
+                        fallback()
+                    }
+                }
+            }
+            BuilderStep::WaveformCollapse(one_in) => {
+                let previous_builder = builder.take().unwrap_or_else(fallback);
+                if rng.roll_dice(1, i32::max(*one_in, 1)) == 1 {
+                    Box::new(WaveformCollapseBuilder::derived_map(new_depth, previous_builder))
+                } else {
+                    previous_builder
+                }
+            }
+        });
+    }
+
+    builder.unwrap_or_else(fallback)
 }
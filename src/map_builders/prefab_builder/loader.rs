@@ -0,0 +1,464 @@
+use rltk::rex::{XpFile, XpLayer};
+use rltk::RGB;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::super::super::map::TileType;
+use super::prefab_section::{HorizontalPlacement, VerticalPlacement};
+
+/// Directory that prefab files (and the REX Paint files they reference) are loaded from.
+pub const PREFAB_DIR: &str = "resources/prefabs";
+
+#[derive(Debug)]
+pub enum PrefabError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+    UnknownGlyph { path: PathBuf, glyph: char, x: usize, y: usize },
+    MisplacedGlyph { path: PathBuf, glyph: char, layer: usize, x: usize, y: usize },
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrefabError::Io { path, error } => {
+                write!(f, "Unable to read prefab {}: {}", path.display(), error)
+            }
+            PrefabError::Parse { path, line, message } => {
+                write!(f, "Error in prefab {} line {}: {}", path.display(), line, message)
+            }
+            PrefabError::UnknownGlyph { path, glyph, x, y } => write!(
+                f,
+                "Unknown glyph '{}' at ({}, {}) in prefab {}",
+                glyph,
+                x,
+                y,
+                path.display()
+            ),
+            PrefabError::MisplacedGlyph { path, glyph, layer, x, y } => write!(
+                f,
+                "Glyph '{}' at ({}, {}) does not belong on layer {} of prefab {}",
+                glyph,
+                x,
+                y,
+                layer,
+                path.display()
+            ),
+        }
+    }
+}
+
+/// What a single legend character turns into when it is stamped onto the map.
+/// A `tile` of `None` leaves the terrain underneath untouched.
+#[derive(Clone, PartialEq)]
+pub struct LegendEntry {
+    pub tile: Option<TileType>,
+    pub spawn: Option<String>,
+    pub start: bool,
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct PrefabLegend {
+    entries: HashMap<char, LegendEntry>,
+}
+
+impl PrefabLegend {
+    pub fn get(&self, ch: char) -> Option<&LegendEntry> {
+        self.entries.get(&ch)
+    }
+}
+
+/// REX Paint layers, in the order they are stored in the file.
+const TERRAIN_LAYER: usize = 0;
+const ENTITY_LAYER: usize = 1;
+const METADATA_LAYER: usize = 2;
+
+/// The glyphs of a prefab, one entry per cell. ASCII prefabs only have terrain; REX Paint
+/// prefabs may also carry an entity layer, a metadata layer and per-cell colors.
+#[derive(Clone, Default, PartialEq)]
+pub struct PrefabTemplate {
+    pub terrain: Vec<char>,
+    pub entities: Vec<Option<char>>,
+    pub metadata: Vec<Option<char>>,
+    pub tints: Vec<Option<(RGB, RGB)>>,
+}
+
+/// The parsed contents of a prefab file, before it is turned into a level or section.
+pub struct PrefabFile {
+    pub path: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    pub placement: Option<(HorizontalPlacement, VerticalPlacement)>,
+    pub legend: PrefabLegend,
+    pub template: PrefabTemplate,
+}
+
+/// Reads a prefab from `PREFAB_DIR`.
+///
+/// A prefab file is a header of `key = value` lines and `legend` lines, followed by a line
+/// containing only `---` and the ASCII template. Instead of an ASCII template, the header may
+/// name a REX Paint file with `rex = file.xp`. Blank lines and lines starting with `#` are
+/// ignored in the header.
+///
+/// REX Paint prefabs use layer 0 for terrain, layer 1 for spawns and layer 2 for metadata
+/// (start position, exits and triggers); empty cells on layers 1 and 2 are skipped. With
+/// `tint = true`, the colors painted on layer 0 are kept as tile tints.
+pub fn load_prefab(file_name: &str) -> Result<PrefabFile, PrefabError> {
+    let path = Path::new(PREFAB_DIR).join(file_name);
+    let contents = std::fs::read_to_string(&path).map_err(|error| PrefabError::Io {
+        path: path.clone(),
+        error,
+    })?;
+    parse_prefab(&path, &contents)
+}
+
+fn parse_prefab(path: &Path, contents: &str) -> Result<PrefabFile, PrefabError> {
+    let parse_error = |line: usize, message: String| PrefabError::Parse {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut width: Option<usize> = None;
+    let mut height: Option<usize> = None;
+    let mut placement = None;
+    let mut rex: Option<String> = None;
+    let mut tint = false;
+    let mut legend = PrefabLegend::default();
+    let mut template_rows: Option<Vec<&str>> = None;
+
+    let mut lines = contents.lines().enumerate();
+    for (i, raw_line) in &mut lines {
+        let line_number = i + 1;
+        let line = raw_line.trim_end_matches('\r');
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed == "---" {
+            template_rows = Some(Vec::new());
+            break;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("legend") {
+            let (glyph, entry) =
+                parse_legend(rest.trim_start()).map_err(|m| parse_error(line_number, m))?;
+            legend.entries.insert(glyph, entry);
+            continue;
+        }
+
+        let mut parts = trimmed.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = match parts.next() {
+            Some(value) => value.trim(),
+            None => return Err(parse_error(line_number, format!("Expected `key = value`, found `{}`", trimmed))),
+        };
+        match key {
+            "width" => width = Some(parse_size(value).map_err(|m| parse_error(line_number, m))?),
+            "height" => height = Some(parse_size(value).map_err(|m| parse_error(line_number, m))?),
+            "placement" => {
+                placement = Some(parse_placement(value).map_err(|m| parse_error(line_number, m))?)
+            }
+            "rex" => rex = Some(value.to_string()),
+            "tint" => {
+                tint = match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(parse_error(line_number, format!("`{}` is not true or false", value))),
+                }
+            }
+            _ => return Err(parse_error(line_number, format!("Unknown header key `{}`", key))),
+        }
+    }
+
+    let width = width.ok_or_else(|| parse_error(0, "Missing `width` header".to_string()))?;
+    let height = height.ok_or_else(|| parse_error(0, "Missing `height` header".to_string()))?;
+
+    let mut template = PrefabTemplate::default();
+    let rows: Vec<Vec<char>> = match rex {
+        Some(rex_file) => {
+            let rex_path = path.with_file_name(rex_file);
+            let xp_file = read_rex_file(&rex_path)?;
+            let layer = |n: usize| xp_file.layers.get(n);
+
+            if let Some(entities) = layer(ENTITY_LAYER) {
+                template.entities = read_rex_overlay(entities, width, height);
+            }
+            if let Some(metadata) = layer(METADATA_LAYER) {
+                template.metadata = read_rex_overlay(metadata, width, height);
+            }
+            match layer(TERRAIN_LAYER) {
+                Some(terrain) => {
+                    if tint {
+                        template.tints = read_rex_tints(terrain, width, height);
+                    }
+                    read_rex_rows(terrain, width, height)
+                }
+                None => Vec::new(),
+            }
+        }
+        None => {
+            let mut rows: Vec<&str> = template_rows
+                .ok_or_else(|| parse_error(0, "Missing `---` before the template".to_string()))?;
+            for (_, raw_line) in lines {
+                rows.push(raw_line.trim_end_matches('\r'));
+            }
+            // Allow trailing blank lines at the end of the file
+            while rows.len() > height && rows.last().is_some_and(|r| r.is_empty()) {
+                rows.pop();
+            }
+            rows.iter()
+                .map(|row| row.chars().map(|c| if c == '\u{a0}' { ' ' } else { c }).collect())
+                .collect()
+        }
+    };
+
+    if rows.len() != height {
+        return Err(parse_error(
+            0,
+            format!("Template has {} rows, expected {}", rows.len(), height),
+        ));
+    }
+
+    for (y, mut row) in rows.into_iter().enumerate() {
+        if row.len() > width {
+            return Err(parse_error(
+                0,
+                format!("Template row {} is {} wide, expected {}", y, row.len(), width),
+            ));
+        }
+        // Editors like to strip trailing whitespace, so pad short rows with spaces
+        row.resize(width, ' ');
+        for (x, ch) in row.iter().enumerate() {
+            if legend.get(*ch).is_none() {
+                return Err(PrefabError::UnknownGlyph {
+                    path: path.to_path_buf(),
+                    glyph: *ch,
+                    x,
+                    y,
+                });
+            }
+        }
+        template.terrain.extend(row);
+    }
+
+    // Entity layers may only hold spawns, and metadata layers only markers and triggers
+    let overlays = [
+        (ENTITY_LAYER, &template.entities),
+        (METADATA_LAYER, &template.metadata),
+    ];
+    for (layer, glyphs) in overlays.iter() {
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if let Some(glyph) = glyph {
+                let (x, y) = (i % width, i / width);
+                let entry = legend.get(*glyph).ok_or_else(|| PrefabError::UnknownGlyph {
+                    path: path.to_path_buf(),
+                    glyph: *glyph,
+                    x,
+                    y,
+                })?;
+                let allowed = match *layer {
+                    ENTITY_LAYER => entry.spawn.is_some() && !entry.start,
+                    _ => entry.start || entry.tile.is_none() || entry.tile == Some(TileType::DownStairs),
+                };
+                if !allowed {
+                    return Err(PrefabError::MisplacedGlyph {
+                        path: path.to_path_buf(),
+                        glyph: *glyph,
+                        layer: *layer,
+                        x,
+                        y,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(PrefabFile {
+        path: path.to_path_buf(),
+        width,
+        height,
+        placement,
+        legend,
+        template,
+    })
+}
+
+fn read_rex_file(path: &Path) -> Result<XpFile, PrefabError> {
+    let io_error = |error| PrefabError::Io {
+        path: path.to_path_buf(),
+        error,
+    };
+    let mut file = File::open(path).map_err(io_error)?;
+    XpFile::read(&mut file).map_err(io_error)
+}
+
+/// Reads a REX Paint layer as rows of template characters.
+fn read_rex_rows(layer: &XpLayer, width: usize, height: usize) -> Vec<Vec<char>> {
+    let mut rows = Vec::with_capacity(height);
+    for y in 0..usize::min(height, layer.height) {
+        let mut row = Vec::with_capacity(width);
+        for x in 0..usize::min(width, layer.width) {
+            let cell = layer.get(x, y).unwrap();
+            row.push(rltk::to_char(cell.ch as u8));
+        }
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Reads an overlay layer, with `None` for empty cells and cells outside the layer.
+fn read_rex_overlay(layer: &XpLayer, width: usize, height: usize) -> Vec<Option<char>> {
+    let mut glyphs = vec![None; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(cell) = layer.get(x, y) {
+                if cell.ch != 0 && cell.ch != 32 {
+                    glyphs[(y * width) + x] = Some(rltk::to_char(cell.ch as u8));
+                }
+            }
+        }
+    }
+
+    glyphs
+}
+
+/// Reads the foreground and background color of every cell in a layer.
+fn read_rex_tints(layer: &XpLayer, width: usize, height: usize) -> Vec<Option<(RGB, RGB)>> {
+    let mut tints = vec![None; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(cell) = layer.get(x, y) {
+                if !cell.bg.is_transparent() {
+                    tints[(y * width) + x] = Some((RGB::from_xp(cell.fg), RGB::from_xp(cell.bg)));
+                }
+            }
+        }
+    }
+
+    tints
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .map_err(|_| format!("`{}` is not a valid size", value))
+}
+
+/// Parses the part of a legend line after `legend`, e.g. `'g' = spawn Goblin`.
+fn parse_legend(rest: &str) -> Result<(char, LegendEntry), String> {
+    let mut chars = rest.chars();
+    let glyph = match chars.next() {
+        Some('\'') => {
+            let glyph = chars.next().ok_or("Missing legend glyph")?;
+            if chars.next() != Some('\'') {
+                return Err("Unterminated quoted legend glyph".to_string());
+            }
+            glyph
+        }
+        Some(glyph) => glyph,
+        None => return Err("Missing legend glyph".to_string()),
+    };
+
+    let definition = chars.as_str().trim_start();
+    let definition = match definition.strip_prefix('=') {
+        Some(definition) => definition.trim(),
+        None => return Err(format!("Expected `=` after legend glyph '{}'", glyph)),
+    };
+
+    let mut words = definition.splitn(2, ' ');
+    let kind = words.next().unwrap_or("");
+    let argument = words.next().map(|a| a.trim());
+    let entry = match (kind, argument) {
+        ("floor", None) => LegendEntry { tile: Some(TileType::Floor), spawn: None, start: false },
+        ("wall", None) => LegendEntry { tile: Some(TileType::Wall), spawn: None, start: false },
+        ("stairs", None) => LegendEntry { tile: Some(TileType::DownStairs), spawn: None, start: false },
+        ("start", None) => LegendEntry { tile: Some(TileType::Floor), spawn: None, start: true },
+        ("spawn", Some(name)) if !name.is_empty() => LegendEntry {
+            tile: Some(TileType::Floor),
+            spawn: Some(name.to_string()),
+            start: false,
+        },
+        ("trigger", Some(name)) if !name.is_empty() => LegendEntry {
+            tile: None,
+            spawn: Some(name.to_string()),
+            start: false,
+        },
+        _ => return Err(format!("Unknown legend definition `{}`", definition)),
+    };
+
+    Ok((glyph, entry))
+}
+
+fn parse_placement(value: &str) -> Result<(HorizontalPlacement, VerticalPlacement), String> {
+    let mut parts = value.split(',').map(|p| p.trim());
+    let horizontal = match parts.next() {
+        Some("left") => HorizontalPlacement::Left,
+        Some("center") => HorizontalPlacement::Center,
+        Some("right") => HorizontalPlacement::Right,
+        _ => return Err(format!("Invalid horizontal placement in `{}`", value)),
+    };
+    let vertical = match parts.next() {
+        Some("top") => VerticalPlacement::Top,
+        Some("center") => VerticalPlacement::Center,
+        Some("bottom") => VerticalPlacement::Bottom,
+        _ => return Err(format!("Invalid vertical placement in `{}`", value)),
+    };
+
+    Ok((horizontal, vertical))
+}
+
+#[cfg(test)]
+mod tests {
+    use rltk::RandomNumberGenerator;
+
+    use super::super::super::MapBuilder;
+    use super::super::{PrefabBuilder, PrefabLevel};
+    use super::*;
+
+    #[test]
+    fn rex_prefab_matches_ascii_prefab() {
+        let build = |file_name: &str| {
+            let level = PrefabLevel::load(file_name).unwrap();
+            let mut builder = PrefabBuilder::level(1, level);
+            builder.build_map(&mut RandomNumberGenerator::seeded(1));
+            builder
+        };
+        let mut ascii = build("wfc_populated.prefab");
+        let mut rex = build("wfc_populated_rex.prefab");
+
+        // Spawns come from the entity layer, and the start and exit from the metadata layer
+        let level = PrefabLevel::load("wfc_populated_rex.prefab").unwrap();
+        assert!(level.template.terrain.iter().all(|glyph| *glyph == '#' || *glyph == ' '));
+        let spawns = level.template.entities.iter().filter(|glyph| glyph.is_some()).count();
+        assert_eq!(spawns, ascii.get_spawn_list().len());
+        assert_eq!(rex.get_spawn_list(), ascii.get_spawn_list());
+        let start = rex.get_starting_position();
+        assert_eq!((start.x, start.y), (5, 2));
+        assert!(rex.get_map().tiles == ascii.get_map().tiles);
+
+        // Only the starting room is tinted
+        let map = rex.get_map();
+        assert!(!map.tints.is_empty());
+        for idx in map.tints.keys() {
+            let (x, y) = (*idx as i32 % map.width, *idx as i32 / map.width);
+            assert!((1..=10).contains(&x) && (1..=4).contains(&y), "({}, {}) is tinted", x, y);
+            assert!(map.tiles[*idx] == TileType::Floor);
+        }
+        assert!(ascii.get_map().tints.is_empty());
+    }
+
+    #[test]
+    fn misplaced_rex_glyph_is_an_error() {
+        match PrefabLevel::load("tests/misplaced_glyph.prefab") {
+            Err(PrefabError::MisplacedGlyph { glyph, layer, x, y, .. }) => {
+                assert_eq!((glyph, layer, x, y), ('@', 1, 2, 1));
+            }
+            Err(e) => panic!("Expected a misplaced glyph, got: {}", e),
+            Ok(_) => panic!("Expected a misplaced glyph"),
+        }
+    }
+}
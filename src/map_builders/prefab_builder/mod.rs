@@ -1,58 +1,72 @@
-use rltk::{console, RandomNumberGenerator};
-use specs::prelude::*;
+use rltk::RandomNumberGenerator;
 
-use super::super::{
-    components::Position, map::Map, map::TileType, spawner, SHOW_MAPGEN_VISUALIZER,
-};
-use super::{common, MapBuilder};
+use super::super::{components::Position, map::Map, map::TileType};
+use super::{common, MapBuilder, MapSnapshot};
 
+mod loader;
+use loader::{LegendEntry, PrefabLegend, PrefabTemplate};
 mod prefab_level;
+pub use prefab_level::PrefabLevel;
 mod prefab_section;
+pub use prefab_section::PrefabSection;
 
 #[derive(Clone, PartialEq)]
 pub enum PrefabMode {
-    Constant { level: prefab_level::PrefabLevel },
-    RexLevel { template: &'static str },
-    Sectional { section: prefab_section::PrefabSection }
+    Constant { level: PrefabLevel },
+    Sectional { section: PrefabSection }
 }
 
 pub struct PrefabBuilder {
     map: Map,
     starting_position: Position,
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     mode: PrefabMode,
     spawns: Vec<(usize, String)>,
     previous_builder: Option<Box<dyn MapBuilder>>,
 }
 
 impl PrefabBuilder {
-    pub fn new(new_depth: i32, previous_builder: Option<Box<dyn MapBuilder>>) -> PrefabBuilder {
+    pub fn level(new_depth: i32, level: PrefabLevel) -> PrefabBuilder {
         PrefabBuilder {
             map: Map::new(new_depth),
             starting_position: Position { x: 0, y: 0 },
             depth: new_depth,
             history: Vec::new(),
-            mode: PrefabMode::Sectional {
-                section: prefab_section::UNDERGROUND_FORT,
-            },
+            mode: PrefabMode::Constant { level },
             spawns: Vec::new(),
-            previous_builder
+            previous_builder: None,
         }
     }
 
-    pub fn apply_sectional(&mut self, section: &prefab_section::PrefabSection) {
+    pub fn sectional(
+        new_depth: i32,
+        section: PrefabSection,
+        previous_builder: Box<dyn MapBuilder>,
+    ) -> PrefabBuilder {
+        PrefabBuilder {
+            map: Map::new(new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            depth: new_depth,
+            history: Vec::new(),
+            mode: PrefabMode::Sectional { section },
+            spawns: Vec::new(),
+            previous_builder: Some(previous_builder),
+        }
+    }
+
+    pub fn apply_sectional(&mut self, section: &PrefabSection, rng: &mut RandomNumberGenerator) {
         // Build the map
         let prev_builder = self.previous_builder.as_mut().unwrap();
-        prev_builder.build_map();
+        prev_builder.build_map(rng);
+        self.history.extend(prev_builder.get_snapshot_history());
         self.starting_position = prev_builder.get_starting_position();
-        self.map = prev_builder.get_map().clone();
-        self.take_snapshot();
+        self.map = prev_builder.get_map();
+        let prev_spawns = prev_builder.get_spawn_list().clone();
+        self.take_snapshot("Build base map");
 
         use prefab_section::*;
 
-        let string_vec = PrefabBuilder::read_ascii_to_vec(section.template);
-
         // Place the new section
         let chunk_x = match section.placement.0 {
             HorizontalPlacement::Left => 0,
@@ -64,27 +78,54 @@ impl PrefabBuilder {
             VerticalPlacement::Center => (self.map.height / 2) - (section.height as i32 / 2),
             VerticalPlacement::Bottom => (self.map.height-1) - section.height as i32
         };
-        println!("{},{}", chunk_x, chunk_y);
 
-        let mut i = 0;
-        for ty in 0..section.height {
-            for tx in 0..section.width {
-                if let Some(idx) = self.map.xy_idx(tx as i32 + chunk_x, ty as i32 + chunk_y) {
-                    self.char_to_map(string_vec[i], idx);
+        // Keep whatever the previous builder spawned, unless the section covers it
+        let map_width = self.map.width;
+        let section_covers = |idx: usize| {
+            let x = idx as i32 % map_width;
+            let y = idx as i32 / map_width;
+            x >= chunk_x
+                && x < chunk_x + section.width as i32
+                && y >= chunk_y
+                && y < chunk_y + section.height as i32
+        };
+        self.spawns = prev_spawns
+            .into_iter()
+            .filter(|spawn| !section_covers(spawn.0))
+            .collect();
+
+        self.apply_template(
+            &section.template,
+            &section.legend,
+            section.width,
+            section.height,
+            chunk_x,
+            chunk_y,
+        );
+        self.take_snapshot("Place prefab section");
+
+        // The section may have walled off part of the map or built over the stairs
+        if let Some(start_idx) = self.map.xy_idx(self.starting_position.x, self.starting_position.y) {
+            for tile in self.map.tiles.iter_mut() {
+                if *tile == TileType::DownStairs {
+                    *tile = TileType::Floor;
                 }
-                i += 1;
             }
+            let exit_tile =
+                common::remove_unreachable_areas_returning_most_distant(&mut self.map, start_idx);
+            self.map.tiles[exit_tile] = TileType::DownStairs;
+            let map = &self.map;
+            self.spawns.retain(|spawn| map.tiles[spawn.0] != TileType::Wall);
+            self.take_snapshot("Reconnect map");
         }
-        self.take_snapshot();
     }
 
-    fn build(&mut self) {
-        match self.mode {
-            PrefabMode::Constant { level } => self.load_ascii_map(&level),
-            PrefabMode::RexLevel { template } => self.load_rex_map(&template),
-            PrefabMode::Sectional { section } => self.apply_sectional(&section),
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
+        match self.mode.clone() {
+            PrefabMode::Constant { level } => self.load_level(&level),
+            PrefabMode::Sectional { section } => self.apply_sectional(&section, rng),
         }
-        self.take_snapshot();
+        self.take_snapshot("Apply prefab");
 
         // Find a starting point; start at the middle and walk left until finding an open tile
         if self.starting_position.x == 0 {
@@ -101,106 +142,79 @@ impl PrefabBuilder {
                     .unwrap();
             }
         }
-        self.take_snapshot();
-    }
-
-    fn char_to_map(&mut self, ch: char, idx: usize) {
-        match ch {
-            ' ' => self.map.tiles[idx] = TileType::Floor,
-            '#' => self.map.tiles[idx] = TileType::Wall,
-            '@' => {
-                let x = idx as i32 % self.map.width;
-                let y = idx as i32 / self.map.width;
-                self.map.tiles[idx] = TileType::Floor;
-                self.starting_position = Position {
-                    x: x as i32,
-                    y: y as i32,
-                };
-            }
-            '>' => self.map.tiles[idx] = TileType::DownStairs,
-            'g' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Goblin".to_string()));
-            }
-            'o' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Orc".to_string()));
-            }
-            '^' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Bear Trap".to_string()));
-            }
-            '%' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Rations".to_string()));
-            }
-            '!' => {
-                self.map.tiles[idx] = TileType::Floor;
-                self.spawns.push((idx, "Health Potion".to_string()));
-            }
-            _ => console::log(format!("Unknown glyph loading map: {}", ch)),
-        }
+        self.take_snapshot("Find starting point");
     }
 
-    #[allow(dead_code)]
-    fn load_ascii_map(&mut self, level: &prefab_level::PrefabLevel) {
-        let string_vec = PrefabBuilder::read_ascii_to_vec(level.template);
-
+    /// Stamps a template onto the map with its top-left corner at the given offset.
+    /// Templates are validated against their legend when they are loaded.
+    fn apply_template(
+        &mut self,
+        template: &PrefabTemplate,
+        legend: &PrefabLegend,
+        width: usize,
+        height: usize,
+        offset_x: i32,
+        offset_y: i32,
+    ) {
         let mut i = 0;
-        for ty in 0..level.height {
-            for tx in 0..level.width {
-                if let Some(idx) = self.map.xy_idx(tx as i32, ty as i32) {
-                    self.char_to_map(string_vec[i], idx);
-                }
-                i += 1;
-            }
-        }
-    }
+        for ty in 0..height {
+            for tx in 0..width {
+                let x = tx as i32 + offset_x;
+                let y = ty as i32 + offset_y;
+                if let Some(idx) = self.map.xy_idx(x, y) {
+                    let terrain = legend.get(template.terrain[i]).unwrap();
+                    self.apply_legend_entry(terrain, idx, x, y, false);
+
+                    let overlays = [template.entities.get(i), template.metadata.get(i)];
+                    for glyph in overlays.iter().filter_map(|g| g.and_then(|g| g.as_ref())) {
+                        self.apply_legend_entry(legend.get(*glyph).unwrap(), idx, x, y, true);
+                    }
 
-    #[allow(dead_code)]
-    fn load_rex_map(&mut self, path: &str) {
-        let xp_file = rltk::rex::XpFile::from_resource(path).unwrap();
-
-        for layer in &xp_file.layers {
-            for y in 0..layer.height {
-                for x in 0..layer.width {
-                    let cell = layer.get(x, y).unwrap();
-                    if x < self.map.width as usize && y < self.map.height as usize {
-                        let idx = self.map.xy_idx(x as i32, y as i32).unwrap();
-                        self.char_to_map(cell.ch as u8 as char, idx);
+                    match template.tints.get(i) {
+                        Some(Some(tint)) => {
+                            self.map.tints.insert(idx, *tint);
+                        }
+                        _ => {
+                            self.map.tints.remove(&idx);
+                        }
                     }
                 }
+                i += 1;
             }
         }
     }
 
-    fn read_ascii_to_vec(template: &str ) -> Vec<char> {
-        // Start by converting to a vector, with newlines removed
-        let mut string_vec: Vec<char> = template
-            .chars()
-            .filter(|a| *a != '\r' && *a != '\n')
-            .collect();
-
-        for c in string_vec.iter_mut() {
-            if *c as u8 == 160u8 {
-                *c = ' ';
+    /// Applies a single legend entry. Overlay layers keep the terrain they are drawn over,
+    /// except for exits which turn it into stairs.
+    fn apply_legend_entry(&mut self, entry: &LegendEntry, idx: usize, x: i32, y: i32, overlay: bool) {
+        if let Some(tile) = entry.tile {
+            if !overlay || tile == TileType::DownStairs {
+                self.map.tiles[idx] = tile;
             }
         }
+        if let Some(spawn) = &entry.spawn {
+            self.spawns.push((idx, spawn.to_string()));
+        }
+        if entry.start {
+            self.starting_position = Position { x, y };
+        }
+    }
 
-        string_vec
+    fn load_level(&mut self, level: &PrefabLevel) {
+        self.apply_template(&level.template, &level.legend, level.width, level.height, 0, 0);
     }
 }
 
 impl MapBuilder for PrefabBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -208,19 +222,11 @@ impl MapBuilder for PrefabBuilder {
         self.starting_position.clone()
     }
 
-    fn spawn_entities(&mut self, ecs: &mut World) {
-        for entity in self.spawns.iter() {
-            spawner::spawn_entity(ecs, &(&entity.0, &entity.1));
-        }
+    fn get_spawn_list(&self) -> &Vec<(usize, String)> {
+        &self.spawns
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
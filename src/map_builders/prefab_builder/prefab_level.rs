@@ -1,92 +1,22 @@
-#[derive(PartialEq, Copy, Clone)]
+use super::loader::{self, PrefabError, PrefabLegend, PrefabTemplate};
+
+#[derive(PartialEq, Clone)]
 pub struct PrefabLevel {
-    pub template : &'static str,
+    pub template : PrefabTemplate,
+    pub legend : PrefabLegend,
     pub width : usize,
     pub height: usize
 }
 
-pub const WFC_POPULATED : PrefabLevel = PrefabLevel {
-    template : LEVEL_MAP,
-    width: 80,
-    height: 43
-};
-
-const LEVEL_MAP : &str =
-"
-################################################################################
-#          ########################################################    #########
-#    @     ######    #########       ####     ###################        #######
-#          ####   g  #                          ###############            #####
-#          #### #    # #######       ####       #############                ###
-##### ######### #    # #######       #########  ####    #####                ###
-##### ######### ###### #######   o   #########  #### ## #####                ###
-##                        ####       #########   ### ##         o            ###
-##### ######### ###       ####       #######         ## #####                ###
-##### ######### ###       ####       ####### #   ### ## #####                ###
-##### ######### ###       ####       ####### #######    #####     o          ###
-###          ## ###       ####       ####### ################                ###
-###          ## ###   o   ###### ########### #   ############                ###
-###          ## ###       ###### ###########     ###                         ###
-###    %                  ###### ########### #   ###   !   ##                ###
-###          ## ###              ######   ## #######       ##                ###
-###          ## ###       ## ### #####     # ########################      #####
-###          ## ###       ## ### #####     # #   ######################    #####
-#### ## ####### ###### ##### ### ####          o ###########     ######    #####
-#### ## ####### ###### ####   ## ####        #   #########         ###### ######
-#    ## ####### ###### ####   ## ####        ############           ##### ######
-# g  ## ####### ###### ####   ##        %    ###########   o      o  #### #    #
-#    ## ###            ####   ## ####        #   #######   ##    ##  ####   g  #
-#######                  ####### ####            ######     !    !    ### #    #
-######                     ##### ####        #   ######               ### ######
-#####                            #####     # ##########               ### ######
-#####           !           ### ######     # ##########      o##o     ### #   ##
-#####                       ### #######   ## #   ######               ###   g ##
-#   ##                     #### ######## ###   o #######  ^########^ #### #   ##
-# g    #                 ###### ######## #####   #######  ^        ^ #### ######
-#   ##g####           ######    ######## ################           ##### ######
-#   ## ########## ##########    ######## #################         ######      #
-#####   ######### ########## %  ######## ###################     ######## ##   #
-#### ### ######## ##########    ######## #################### ##########   #   #
-### ##### ######   #########    ########          ########### #######   # g#   #
-### #####           ###############      ###      ########### #######   ####   #
-### ##### ####       ############## ######## g  g ########### ####         # ^ #
-#### ###^####         ############# ########      #####       ####      # g#   #
-#####   ######       ###            ########      ##### g     ####   !  ####^^ #
-#!%^## ###  ##           ########## ########  gg                 g         # > #
-#!%^   ###  ###     ############### ########      ##### g     ####      # g#   #
-# %^##  ^   ###     ############### ########      #####       ##################
-################################################################################
-";
-
-// const LEVEL_MAP : &str = 
-// "
-// ###############################################################################
-// #### ######### #    # #######       #########  ####    #####                ###
-// #### ######### ###### #######   o   #########  #### ## #####                ###
-// #                        ####       #########   ### ##         o            ###
-// #### ######### ###       ####       #######         ## #####                ###
-// #### ######### ###       ####       ####### #   ### ## #####                ###
-// #### ######### ###       ####       ####### #######    #####     o          ###
-// ##          ## ###       ####       ####### ################                ###
-// ##          ## ###   o   ###### ########### #   ############                ###
-// ##          ## ###       ###### ###########     ###                         ###
-// ##    %                  ###### ########### #   ###   !   ##                ###
-// ##          ## ###              ######   ## #######       ##                ###
-// ##          ## ###       ## ### #####     # ########################      #####
-// ##          ## ###       ## ### #####     # #   ######################    #####
-// ### ## ####### ###### ##### ### ####          o ###########     ######    #####
-// ### ## ####### ###### ####   ## ####        #   #########         ###### ######
-// ######                  ####### ####            ######     !    !    ### #    #
-// #####                     ##### ####        #   ######               ### ######
-// ####                            #####     # ##########               ### ######
-// ####           !           ### ######     # ##########      o##o     ### #   ##
-// ####                       ### #######   ## #   ######               ###   g ##
-// ####   ######### ########## %  ######## ###################     ######## ##   #
-// ### ### ######## ##########    ######## #################### ##########   #   #
-// ## ##### ######   #########    ########          ########### #######   # g#   #
-// ## #####           ###############      ###      ########### #######   ####   #
-// ## ##### ####       ############## ######## g  g ########### ####         # ^ #
-// ### ###^####         ############# ########      #####       ####      # g#   #
-// ####   ######       ###            ########      ##### g     ####   !  ####^^ #
-// ###############################################################################
-// ";
+impl PrefabLevel {
+    /// Loads a whole-map prefab from the prefab directory.
+    pub fn load(file_name: &str) -> Result<PrefabLevel, PrefabError> {
+        let file = loader::load_prefab(file_name)?;
+        Ok(PrefabLevel {
+            template: file.template,
+            legend: file.legend,
+            width: file.width,
+            height: file.height,
+        })
+    }
+}
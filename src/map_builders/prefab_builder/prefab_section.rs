@@ -1,3 +1,5 @@
+use super::loader::{self, PrefabError, PrefabLegend, PrefabTemplate};
+
 #[allow(dead_code)]
 #[derive(PartialEq, Copy, Clone)]
 pub enum HorizontalPlacement { Left, Center, Right }
@@ -6,66 +8,30 @@ pub enum HorizontalPlacement { Left, Center, Right }
 #[derive(PartialEq, Copy, Clone)]
 pub enum VerticalPlacement { Top, Center, Bottom }
 
-#[allow(dead_code)]
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Clone)]
 pub struct PrefabSection {
-    pub template : &'static str,
+    pub template : PrefabTemplate,
+    pub legend : PrefabLegend,
     pub width : usize,
     pub height: usize,
     pub placement : (HorizontalPlacement, VerticalPlacement)
 }
 
-#[allow(dead_code)]
-pub const UNDERGROUND_FORT : PrefabSection = PrefabSection{
-    template : RIGHT_FORT,
-    width: 15,
-    height: 43,
-    placement: ( HorizontalPlacement::Right, VerticalPlacement::Top )
-};
-
-#[allow(dead_code)]
-const RIGHT_FORT: &str = "
-     #         
-  #######      
-  #     #      
-  #     #######
-  #  g        #
-  #     #######
-  #     #      
-  ### ###      
-    # #        
-    # #        
-    # ##       
-    ^          
-    ^          
-    # ##       
-    # #        
-    # #        
-    # #        
-    # #        
-  ### ###      
-  #     #      
-  #     #      
-  #  g  #      
-  #     #      
-  #     #      
-  ### ###      
-    # #        
-    # #        
-    # #        
-    # ##       
-    ^          
-    ^          
-    # ##       
-    # #        
-    # #        
-    # #        
-  ### ###      
-  #     #      
-  #     #######
-  #  g        #
-  #     #######
-  #     #      
-  #######      
-     #         
-";
\ No newline at end of file
+impl PrefabSection {
+    /// Loads a section prefab from the prefab directory. Sections must specify a placement.
+    pub fn load(file_name: &str) -> Result<PrefabSection, PrefabError> {
+        let file = loader::load_prefab(file_name)?;
+        let placement = file.placement.ok_or_else(|| PrefabError::Parse {
+            path: file.path.clone(),
+            line: 0,
+            message: "Sections require a `placement` header".to_string(),
+        })?;
+        Ok(PrefabSection {
+            template: file.template,
+            legend: file.legend,
+            width: file.width,
+            height: file.height,
+            placement,
+        })
+    }
+}
@@ -1,17 +1,16 @@
 use rltk::RandomNumberGenerator;
 
 use super::common::*;
-use super::super::SHOW_MAPGEN_VISUALIZER;
 use super::super::components::Position;
 use super::super::map::{ Map, TileType };
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 use super::super::rect::Rect;
 use super::super::spawner;
 
 pub struct SimpleMapBuilder {
     depth: i32,
     map: Map,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     rooms: Vec<Rect>,
     spawn_list: Vec<(usize, String)>,
     starting_position: Position,
@@ -29,13 +28,11 @@ impl SimpleMapBuilder {
         }
     }
 
-    fn rooms_and_corridors(&mut self) {
+    fn rooms_and_corridors(&mut self, rng: &mut RandomNumberGenerator) {
         const MAX_ROOMS: i32 = 30;
         const MIN_SIZE: i32 = 6;
         const MAX_SIZE: i32 = 10;
 
-        let mut rng = RandomNumberGenerator::new();
-
         for _i in 0..MAX_ROOMS {
             let w = rng.range(MIN_SIZE, MAX_SIZE);
             let h = rng.range(MIN_SIZE, MAX_SIZE);
@@ -62,7 +59,7 @@ impl SimpleMapBuilder {
                 }
 
                 self.rooms.push(new_room);
-                self.take_snapshot();
+                self.take_snapshot("Place room");
             }
         }
 
@@ -75,21 +72,21 @@ impl SimpleMapBuilder {
         self.starting_position = Position { x: start_pos.0, y: start_pos.1 };
 
         for room in self.rooms.iter().skip(1) {
-            spawner::spawn_room(&self.map, &mut rng, room, self.depth, &mut self.spawn_list);
+            spawner::spawn_room(&self.map, rng, room, self.depth, &mut self.spawn_list);
         }
     }
 }
 
 impl MapBuilder for SimpleMapBuilder {
-    fn build_map(&mut self) {
-        self.rooms_and_corridors();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.rooms_and_corridors(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -101,13 +98,7 @@ impl MapBuilder for SimpleMapBuilder {
         self.starting_position.clone()
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
\ No newline at end of file
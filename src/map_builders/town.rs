@@ -0,0 +1,186 @@
+use rltk::RandomNumberGenerator;
+
+use super::super::components::Position;
+use super::super::map::{Map, TileType};
+use super::super::rect::Rect;
+use super::{MapBuilder, MapSnapshot};
+
+/// The town's residents, in the order they move into buildings
+const RESIDENTS: [&str; 3] = ["Vendor", "Healer", "Quest Giver"];
+const MAX_BUILDINGS: usize = 8;
+const BUILDING_ATTEMPTS: usize = 200;
+
+/// A surface town: open ground crossed by roads, with buildings for the townsfolk and the
+/// dungeon entrance at the east end of the main road.
+pub struct TownBuilder {
+    map: Map,
+    starting_position: Position,
+    history: Vec<MapSnapshot>,
+    spawn_list: Vec<(usize, String)>,
+    buildings: Vec<Rect>,
+}
+
+impl TownBuilder {
+    pub fn new(new_depth: i32) -> TownBuilder {
+        TownBuilder {
+            map: Map::new(new_depth),
+            starting_position: Position { x: 0, y: 0 },
+            history: Vec::new(),
+            spawn_list: Vec::new(),
+            buildings: Vec::new(),
+        }
+    }
+
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
+        // Clear the ground inside the town wall
+        for y in 1..self.map.height - 1 {
+            for x in 1..self.map.width - 1 {
+                let idx = self.map.xy_idx(x, y).unwrap();
+                self.map.tiles[idx] = TileType::Floor;
+            }
+        }
+        self.take_snapshot("Clear ground");
+
+        let road_y = self.map.height / 2;
+        self.lay_roads(road_y, rng);
+        self.take_snapshot("Lay roads");
+
+        for _attempt in 0..BUILDING_ATTEMPTS {
+            if self.buildings.len() >= MAX_BUILDINGS {
+                break;
+            }
+            let width = rng.range(7, 14);
+            let height = rng.range(5, 9);
+            let x = rng.roll_dice(1, self.map.width - width - 3) + 1;
+            let y = rng.roll_dice(1, self.map.height - height - 3) + 1;
+            let building = Rect::new(x, y, width, height);
+            if self.is_open_ground(&building) {
+                self.add_building(building, road_y);
+                self.take_snapshot("Build house");
+            }
+        }
+
+        // Everyone lives in the middle of their own building; anyone left over waits on
+        // the main road
+        for (i, resident) in RESIDENTS.iter().enumerate() {
+            let (x, y) = match self.buildings.get(i) {
+                Some(building) => building.center(),
+                None => (self.map.width / 2 + (i as i32 * 2), road_y),
+            };
+            let idx = self.map.xy_idx(x, y).unwrap();
+            self.spawn_list.push((idx, resident.to_string()));
+        }
+
+        // The dungeon entrance is at the east end of the main road; travellers arrive from
+        // the west
+        let stairs_idx = self.map.xy_idx(self.map.width - 3, road_y).unwrap();
+        self.map.tiles[stairs_idx] = TileType::DownStairs;
+        self.starting_position = Position { x: 2, y: road_y };
+        self.take_snapshot("Place dungeon entrance");
+    }
+
+    /// A three-tile main road from west to east, crossed by a few north-south streets
+    fn lay_roads(&mut self, road_y: i32, rng: &mut RandomNumberGenerator) {
+        for x in 1..self.map.width - 1 {
+            for y in road_y - 1..=road_y + 1 {
+                let idx = self.map.xy_idx(x, y).unwrap();
+                self.map.tiles[idx] = TileType::Road;
+            }
+        }
+
+        let streets = rng.range(2, 4);
+        let spacing = self.map.width / (streets + 1);
+        for street in 1..=streets {
+            let street_x = (street * spacing) + rng.range(-3, 4);
+            for y in 1..self.map.height - 1 {
+                for x in street_x..=street_x + 1 {
+                    let idx = self.map.xy_idx(x, y).unwrap();
+                    self.map.tiles[idx] = TileType::Road;
+                }
+            }
+        }
+    }
+
+    /// True if the building and a one-tile margin around it are all untouched ground
+    fn is_open_ground(&self, building: &Rect) -> bool {
+        for y in building.y1 - 1..=building.y2 + 1 {
+            for x in building.x1 - 1..=building.x2 + 1 {
+                match self.map.xy_idx(x, y) {
+                    Some(idx) if self.map.tiles[idx] == TileType::Floor => {}
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    /// Walls the building in, then opens a door on the side facing the main road and paves
+    /// a path from the door to the road.
+    fn add_building(&mut self, building: Rect, road_y: i32) {
+        for y in building.y1..=building.y2 {
+            for x in building.x1..=building.x2 {
+                let idx = self.map.xy_idx(x, y).unwrap();
+                let on_edge =
+                    x == building.x1 || x == building.x2 || y == building.y1 || y == building.y2;
+                self.map.tiles[idx] = if on_edge { TileType::Wall } else { TileType::Floor };
+            }
+        }
+
+        let (door_x, _) = building.center();
+        let (door_y, step) = if building.y2 < road_y {
+            (building.y2, 1)
+        } else {
+            (building.y1, -1)
+        };
+        let door_idx = self.map.xy_idx(door_x, door_y).unwrap();
+        self.map.tiles[door_idx] = TileType::Floor;
+
+        let mut path_y = door_y + step;
+        while let Some(idx) = self.map.xy_idx(door_x, path_y) {
+            if self.map.tiles[idx] != TileType::Floor {
+                break;
+            }
+            self.map.tiles[idx] = TileType::Road;
+            path_y += step;
+        }
+
+        self.buildings.push(building);
+    }
+}
+
+impl MapBuilder for TownBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
+    }
+
+    fn get_map(&mut self) -> Map {
+        self.map.clone()
+    }
+
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
+        self.history.clone()
+    }
+
+    fn get_spawn_list(&self) -> &Vec<(usize, String)> {
+        &self.spawn_list
+    }
+
+    fn get_starting_position(&mut self) -> Position {
+        self.starting_position.clone()
+    }
+
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::validation::check_builder;
+    use super::*;
+
+    #[test]
+    fn town_is_valid() {
+        check_builder("town", |depth| Box::new(TownBuilder::new(depth)));
+    }
+}
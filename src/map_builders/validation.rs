@@ -0,0 +1,348 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::super::components::Position;
+use super::super::map::{Map, TileType};
+
+/// Playable maps should be neither mostly rock nor one big open field.
+pub const MIN_FLOOR_RATIO: f32 = 0.1;
+pub const MAX_FLOOR_RATIO: f32 = 0.9;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    StartOutOfBounds { x: i32, y: i32 },
+    StartNotFloor { x: i32, y: i32 },
+    NoStairs,
+    StairsUnreachable { x: i32, y: i32 },
+    FloorRatio(f32),
+    FloorOnBorder { x: i32, y: i32 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::StartOutOfBounds { x, y } => {
+                write!(f, "Starting position ({}, {}) is outside the map", x, y)
+            }
+            ValidationError::StartNotFloor { x, y } => {
+                write!(f, "Starting position ({}, {}) is not a floor tile", x, y)
+            }
+            ValidationError::NoStairs => write!(f, "The map has no down stairs"),
+            ValidationError::StairsUnreachable { x, y } => {
+                write!(f, "Stairs at ({}, {}) cannot be reached from the start", x, y)
+            }
+            ValidationError::FloorRatio(ratio) => write!(
+                f,
+                "Floor covers {:.2} of the map, outside {:.2}..{:.2}",
+                ratio, MIN_FLOOR_RATIO, MAX_FLOOR_RATIO
+            ),
+            ValidationError::FloorOnBorder { x, y } => {
+                write!(f, "Floor tile ({}, {}) lies on the map border", x, y)
+            }
+        }
+    }
+}
+
+/// Numbers describing the shape of a built map, for comparing builders with each other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapMetrics {
+    pub floor_tiles: usize,
+    pub floor_ratio: f32,
+    pub reachable_tiles: usize,
+    /// Floor tiles with exactly one walkable cardinal neighbor
+    pub dead_ends: usize,
+    /// The mean, over all floor tiles, of the narrower of the horizontal and vertical
+    /// runs of floor passing through the tile. Corridors score 1; open caves score high.
+    pub average_corridor_width: f32,
+}
+
+impl fmt::Display for MapMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} floor tiles ({:.2} of the map, {} reachable), {} dead ends, average corridor width {:.2}",
+            self.floor_tiles,
+            self.floor_ratio,
+            self.reachable_tiles,
+            self.dead_ends,
+            self.average_corridor_width
+        )
+    }
+}
+
+fn is_walkable(map: &Map, x: i32, y: i32) -> bool {
+    match map.xy_idx(x, y) {
+        Some(idx) => map.tiles[idx] != TileType::Wall,
+        None => false,
+    }
+}
+
+/// Marks every tile reachable from `start` by the moves the player can make, which
+/// include diagonals. Walls block movement; `blocked` is ignored because builders never
+/// fill it in.
+fn reachable_from(map: &Map, start: usize) -> Vec<bool> {
+    let mut reached = vec![false; map.tiles.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    reached[start] = true;
+    queue.push_back(start);
+
+    while let Some(idx) = queue.pop_front() {
+        let x = idx as i32 % map.width;
+        let y = idx as i32 / map.width;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx != 0 || dy != 0) && is_walkable(map, x + dx, y + dy) {
+                    let next = map.xy_idx(x + dx, y + dy).unwrap();
+                    if !reached[next] {
+                        reached[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+    }
+
+    reached
+}
+
+/// Checks that a map is playable from `start`: the start is on open floor, every down
+/// stair can be reached from it, the amount of floor is sensible and the border is solid.
+pub fn validate_map(map: &Map, start: &Position) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    let start_idx = match map.xy_idx(start.x, start.y) {
+        Some(idx) => idx,
+        None => {
+            errors.push(ValidationError::StartOutOfBounds { x: start.x, y: start.y });
+            return errors;
+        }
+    };
+    if map.tiles[start_idx] != TileType::Floor && map.tiles[start_idx] != TileType::Road {
+        errors.push(ValidationError::StartNotFloor { x: start.x, y: start.y });
+    }
+
+    let reached = reachable_from(map, start_idx);
+    let mut found_stairs = false;
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        if *tile == TileType::DownStairs {
+            found_stairs = true;
+            if !reached[idx] {
+                errors.push(ValidationError::StairsUnreachable {
+                    x: idx as i32 % map.width,
+                    y: idx as i32 / map.width,
+                });
+            }
+        }
+    }
+    if !found_stairs {
+        errors.push(ValidationError::NoStairs);
+    }
+
+    let metrics = map_metrics(map, start);
+    if metrics.floor_ratio < MIN_FLOOR_RATIO || metrics.floor_ratio > MAX_FLOOR_RATIO {
+        errors.push(ValidationError::FloorRatio(metrics.floor_ratio));
+    }
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let on_border = x == 0 || y == 0 || x == map.width - 1 || y == map.height - 1;
+            if on_border && is_walkable(map, x, y) {
+                errors.push(ValidationError::FloorOnBorder { x, y });
+            }
+        }
+    }
+
+    errors
+}
+
+/// Measures a map. `start` is used to count how much of the floor is reachable.
+pub fn map_metrics(map: &Map, start: &Position) -> MapMetrics {
+    let floor_tiles = map.tiles.iter().filter(|t| **t != TileType::Wall).count();
+    let reachable_tiles = match map.xy_idx(start.x, start.y) {
+        Some(idx) => reachable_from(map, idx)
+            .iter()
+            .zip(map.tiles.iter())
+            .filter(|(reached, tile)| **reached && **tile != TileType::Wall)
+            .count(),
+        None => 0,
+    };
+
+    let mut dead_ends = 0;
+    let mut total_width = 0;
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if !is_walkable(map, x, y) {
+                continue;
+            }
+
+            let neighbors = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .filter(|(dx, dy)| is_walkable(map, x + dx, y + dy))
+                .count();
+            if neighbors == 1 {
+                dead_ends += 1;
+            }
+
+            let run = |dx: i32, dy: i32| {
+                let mut length = 0;
+                let (mut cx, mut cy) = (x + dx, y + dy);
+                while is_walkable(map, cx, cy) {
+                    length += 1;
+                    cx += dx;
+                    cy += dy;
+                }
+                length
+            };
+            let horizontal = 1 + run(-1, 0) + run(1, 0);
+            let vertical = 1 + run(0, -1) + run(0, 1);
+            total_width += i32::min(horizontal, vertical);
+        }
+    }
+
+    MapMetrics {
+        floor_tiles,
+        floor_ratio: floor_tiles as f32 / map.tiles.len() as f32,
+        reachable_tiles,
+        dead_ends,
+        average_corridor_width: if floor_tiles > 0 {
+            total_width as f32 / floor_tiles as f32
+        } else {
+            0.0
+        },
+    }
+}
+
+/// How many seeds `check_builder` tries
+#[cfg(test)]
+const SEEDS: u64 = 25;
+
+/// Builds a fresh map for every seed and fails with every broken invariant it finds.
+#[cfg(test)]
+pub fn check_builder<F>(name: &str, make_builder: F)
+where
+    F: Fn(i32) -> Box<dyn super::MapBuilder>,
+{
+    let mut failures = Vec::new();
+    for seed in 0..SEEDS {
+        let mut rng = rltk::RandomNumberGenerator::seeded(seed);
+        let mut builder = make_builder(1);
+        builder.build_map(&mut rng);
+        let map = builder.get_map();
+        let start = builder.get_starting_position();
+        for error in validate_map(&map, &start) {
+            failures.push(format!("{} seed {}: {}", name, seed, error));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::bsp_dungeon::BspDungeonBuilder;
+    use super::super::bsp_interior::BspInteriorBuilder;
+    use super::super::cellular_automata::CellularAutomataBuilder;
+    use super::super::dla::DLABuilder;
+    use super::super::drunkard::DrunkardsWalkBuilder;
+    use super::super::maze::MazeBuilder;
+    use super::super::prefab_builder::{PrefabBuilder, PrefabLevel, PrefabSection};
+    use super::super::simple_map::SimpleMapBuilder;
+    use super::super::voronoi::VoronoiCellBuilder;
+    use super::super::waveform_collapse::WaveformCollapseBuilder;
+    use super::*;
+
+    #[test]
+    fn simple_map_is_valid() {
+        check_builder("simple map", |depth| Box::new(SimpleMapBuilder::new(depth)));
+    }
+
+    #[test]
+    fn bsp_dungeon_is_valid() {
+        check_builder("bsp dungeon", |depth| Box::new(BspDungeonBuilder::new(depth)));
+    }
+
+    #[test]
+    fn bsp_interior_is_valid() {
+        check_builder("bsp interior", |depth| Box::new(BspInteriorBuilder::new(depth)));
+    }
+
+    #[test]
+    fn cellular_automata_is_valid() {
+        check_builder("cellular automata", |depth| Box::new(CellularAutomataBuilder::new(depth)));
+    }
+
+    #[test]
+    fn drunkards_walk_is_valid() {
+        check_builder("open area", |depth| Box::new(DrunkardsWalkBuilder::open_area(depth)));
+        check_builder("open halls", |depth| Box::new(DrunkardsWalkBuilder::open_halls(depth)));
+        check_builder("winding passages", |depth| {
+            Box::new(DrunkardsWalkBuilder::winding_passages(depth))
+        });
+        check_builder("fat passage", |depth| Box::new(DrunkardsWalkBuilder::fat_passage(depth)));
+        check_builder("fearful symmetry", |depth| {
+            Box::new(DrunkardsWalkBuilder::fearful_symmetry(depth))
+        });
+    }
+
+    #[test]
+    fn maze_is_valid() {
+        check_builder("maze", |depth| Box::new(MazeBuilder::new(depth)));
+    }
+
+    #[test]
+    fn dla_is_valid() {
+        check_builder("central attractor", |depth| Box::new(DLABuilder::central_attractor(depth)));
+        check_builder("insectoid", |depth| Box::new(DLABuilder::insectoid(depth)));
+        check_builder("walk inwards", |depth| Box::new(DLABuilder::walk_inwards(depth)));
+        check_builder("walk outwards", |depth| Box::new(DLABuilder::walk_outwards(depth)));
+    }
+
+    #[test]
+    fn voronoi_is_valid() {
+        check_builder("manhattan", |depth| Box::new(VoronoiCellBuilder::manhattan(depth)));
+        check_builder("pythagoras", |depth| Box::new(VoronoiCellBuilder::pythagoras(depth)));
+        check_builder("chebyshev", |depth| Box::new(VoronoiCellBuilder::chebyshev(depth)));
+    }
+
+    #[test]
+    fn waveform_collapse_is_valid() {
+        check_builder("derived wfc", |depth| {
+            Box::new(WaveformCollapseBuilder::derived_map(
+                depth,
+                Box::new(CellularAutomataBuilder::new(depth)),
+            ))
+        });
+        check_builder("rex sample wfc", |depth| {
+            Box::new(WaveformCollapseBuilder::rex_sample(depth, "wfc-demo1.xp").unwrap())
+        });
+    }
+
+    #[test]
+    fn prefabs_are_valid() {
+        check_builder("prefab level", |depth| {
+            let level = PrefabLevel::load("wfc_populated.prefab").unwrap();
+            Box::new(PrefabBuilder::level(depth, level))
+        });
+        check_builder("prefab section", |depth| {
+            let section = PrefabSection::load("underground_fort.prefab").unwrap();
+            Box::new(PrefabBuilder::sectional(
+                depth,
+                section,
+                Box::new(CellularAutomataBuilder::new(depth)),
+            ))
+        });
+    }
+
+    #[test]
+    fn metrics_describe_a_corridor() {
+        let mut map = Map::new(1);
+        for x in 1..11 {
+            let idx = map.xy_idx(x, 5).unwrap();
+            map.tiles[idx] = TileType::Floor;
+        }
+        let metrics = map_metrics(&map, &Position { x: 1, y: 5 });
+        assert_eq!(metrics.floor_tiles, 10);
+        assert_eq!(metrics.reachable_tiles, 10);
+        assert_eq!(metrics.dead_ends, 2);
+        assert_eq!(metrics.average_corridor_width, 1.0);
+    }
+}
@@ -1,13 +1,11 @@
 use rltk::{ DistanceAlg, Point, RandomNumberGenerator };
-use specs::prelude::*;
 use std::collections::HashMap;
 
 use super::common;
 use super::super::components::Position;
 use super::super::map::{Map, TileType};
-use super::MapBuilder;
+use super::{MapBuilder, MapSnapshot};
 use super::super::spawner;
-use super::super::SHOW_MAPGEN_VISUALIZER;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum DistanceAlgorithm {
@@ -23,7 +21,7 @@ pub struct VoronoiCellSettings {
 
 pub struct VoronoiCellBuilder {
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     map: Map,
     noise_areas: HashMap<i32, Vec<usize>>,
     settings: VoronoiCellSettings,
@@ -46,8 +44,7 @@ impl VoronoiCellBuilder {
     }
 
     #[allow(clippy::map_entry)]
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
 
         // Generate Voronoi Diagram
         // First generate `n_seeds` randomly distributed about the map
@@ -108,19 +105,26 @@ impl VoronoiCellBuilder {
                 }
             }
 
-            self.take_snapshot();
+            self.take_snapshot("Voronoi cells");
         }
 
-        // Set a central starting point
+        // Find a starting point; start at the middle and walk left until finding an open tile
         self.starting_position = Position {
             x: self.map.width / 2,
             y: self.map.height / 2,
         };
-        let start_idx = self
+        let mut start_idx = self
             .map
             .xy_idx(self.starting_position.x, self.starting_position.y)
             .unwrap();
-        self.take_snapshot();
+        while self.map.tiles[start_idx] != TileType::Floor {
+            self.starting_position.x -= 1;
+            start_idx = self
+                .map
+                .xy_idx(self.starting_position.x, self.starting_position.y)
+                .unwrap();
+        }
+        self.take_snapshot("Find starting point");
 
         // Find all tiles we can reach from the starting point
         let exit_tile =
@@ -128,18 +132,17 @@ impl VoronoiCellBuilder {
 
         // Place the stairs
         self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.take_snapshot();
+        self.take_snapshot("Place stairs");
 
         // Now we build a noise map for use in spawning entities later
-        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, &mut rng);
+        self.noise_areas = common::generate_voronoi_spawn_regions(&self.map, rng);
 
         // Spawn entities
         for area in self.noise_areas.iter() {
-            spawner::spawn_region(&self.map, &mut rng, area.1, self.depth, &mut self.spawn_list);
+            spawner::spawn_region(&self.map, rng, area.1, self.depth, &mut self.spawn_list);
         }
     }
 
-    #[allow(dead_code)]
     pub fn chebyshev(new_depth: i32) -> Self {
         VoronoiCellBuilder {
             map: Map::new(new_depth),
@@ -147,6 +150,7 @@ impl VoronoiCellBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: VoronoiCellSettings {
                 distance_algorithm: DistanceAlgorithm::Chebyshev,
                 n_seeds: 64,
@@ -161,6 +165,7 @@ impl VoronoiCellBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: VoronoiCellSettings {
                 distance_algorithm: DistanceAlgorithm::Manhattan,
                 n_seeds: 64,
@@ -175,6 +180,7 @@ impl VoronoiCellBuilder {
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
+            spawn_list: Vec::new(),
             settings: VoronoiCellSettings {
                 distance_algorithm: DistanceAlgorithm::Pythagoras,
                 n_seeds: 64,
@@ -184,15 +190,15 @@ impl VoronoiCellBuilder {
 }
 
 impl MapBuilder for VoronoiCellBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -204,13 +210,7 @@ impl MapBuilder for VoronoiCellBuilder {
         self.starting_position.clone()
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
-        }
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
     }
 }
@@ -168,7 +168,7 @@ pub fn patterns_to_constraints(patterns: Vec<Vec<TileType>>, chunk_size: i32) ->
                     if !has_any {
                         // There's no exits on this side, let's match only if
                         // the other edge also has no exits
-                        let matching_exit_count = potential.exits[opposite].iter().filter(|a| !**a).count();
+                        let matching_exit_count = potential.exits[opposite].iter().filter(|a| **a).count();
                         if matching_exit_count == 0 {
                             c.compatible_with[direction].push(j);
                         }
@@ -1,86 +1,195 @@
-use rltk::RandomNumberGenerator;
-use specs::prelude::*;
+use rltk::{console, rex::XpFile, RandomNumberGenerator};
 use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
 
-use super::{common as mbcommon, MapBuilder};
+use super::{common as mbcommon, MapBuilder, MapSnapshot};
 use super::super::components::Position;
 use super::super::map::{Map, TileType};
-use super::super::{spawner, SHOW_MAPGEN_VISUALIZER};
+use super::super::spawner;
 
 mod common;
 use common::MapChunk;
 mod constraints;
 mod image_loader;
 mod solver;
-use solver::Solver;
+use solver::{Solver, SolverStatus};
+
+/// Where the solver takes its sample patterns from.
+pub enum WaveformSource {
+    /// Run another builder and learn from its output
+    Builder(Box<dyn MapBuilder>),
+    /// A REX Paint file, where `#` is wall and space is floor
+    RexFile(XpFile),
+}
+
+/// Directory that REX Paint samples are loaded from
+pub const SAMPLE_DIR: &str = "resources";
+
+/// A REX Paint sample that could not be opened or read
+#[derive(Debug)]
+pub struct SampleError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to read WFC sample {}: {}", self.path.display(), self.error)
+    }
+}
+
+pub struct WaveformCollapseSettings {
+    /// Width and height of the chunks the sample is cut into. A size that doesn't fit on the
+    /// map leaves the sample map as it is.
+    pub chunk_size: i32,
+    pub include_flipping: bool,
+    /// How many decisions a single solver run may undo before giving up
+    pub max_backtracks: usize,
+    /// How many fresh solver runs to try before falling back to the sample map
+    pub max_attempts: usize,
+}
+
+impl Default for WaveformCollapseSettings {
+    fn default() -> Self {
+        WaveformCollapseSettings {
+            chunk_size: 8,
+            include_flipping: true,
+            max_backtracks: 500,
+            max_attempts: 5,
+        }
+    }
+}
 
 pub struct WaveformCollapseBuilder {
     map: Map,
     starting_position: Position,
     depth: i32,
-    history: Vec<Map>,
+    history: Vec<MapSnapshot>,
     noise_areas: HashMap<i32, Vec<usize>>,
-    derive_from: Box<dyn MapBuilder>,
+    spawn_list: Vec<(usize, String)>,
+    source: WaveformSource,
+    settings: WaveformCollapseSettings,
 }
 
 impl WaveformCollapseBuilder {
-    #[allow(dead_code)]
-    pub fn new(new_depth: i32, derive_from: Box<dyn MapBuilder>) -> Self {
+    pub fn new(new_depth: i32, source: WaveformSource, settings: WaveformCollapseSettings) -> Self {
         WaveformCollapseBuilder {
             map: Map::new(new_depth),
             starting_position: Position { x: 0, y: 0 },
             depth: new_depth,
             history: Vec::new(),
             noise_areas: HashMap::new(),
-            derive_from
+            spawn_list: Vec::new(),
+            source,
+            settings,
         }
     }
 
-    #[allow(clippy::map_entry)]
-    fn build(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
+    /// Loads the sample map, with any stairs removed since we will place our own.
+    fn sample_map(&mut self, rng: &mut RandomNumberGenerator) -> Map {
+        let mut sample = match &mut self.source {
+            WaveformSource::Builder(prebuilder) => {
+                prebuilder.build_map(rng);
+                self.history.extend(prebuilder.get_snapshot_history());
+                prebuilder.get_map()
+            }
+            WaveformSource::RexFile(xp_file) => image_loader::load_rex_map(self.depth, xp_file),
+        };
 
-        const CHUNK_SIZE: i32 = 8;
+        for t in sample.tiles.iter_mut() {
+            if *t == TileType::DownStairs { *t = TileType::Floor; }
+        }
+        sample
+    }
+
+    /// Runs the solver up to `max_attempts` times, returning the first solved map.
+    fn solve(&mut self, constraints: &[MapChunk], rng: &mut RandomNumberGenerator) -> Option<Map> {
+        for attempt in 1..=self.settings.max_attempts {
+            self.map = Map::new(self.depth);
+            let mut solver = Solver::new(
+                constraints.to_vec(),
+                self.settings.chunk_size,
+                &self.map,
+                self.settings.max_backtracks,
+            );
+            loop {
+                match solver.iteration(&mut self.map, rng) {
+                    SolverStatus::InProgress => {
+                        self.take_snapshot(&format!("Collapse wave (attempt {})", attempt))
+                    }
+                    SolverStatus::Solved => {
+                        self.take_snapshot("Solved");
+                        return Some(self.map.clone());
+                    }
+                    SolverStatus::Failed(e) => {
+                        console::log(format!("WFC attempt {} failed: {:?}", attempt, e));
+                        break;
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    #[allow(clippy::map_entry)]
+    fn build(&mut self, rng: &mut RandomNumberGenerator) {
+        let chunk_size = self.settings.chunk_size;
 
         // Prebuild map to get chunks
-        let prebuilder = &mut self.derive_from.as_mut();
-        prebuilder.build_map();
-        self.map = prebuilder.get_map();
+        let sample = self.sample_map(rng);
+        self.map = sample.clone();
+        self.take_snapshot("Sample map");
 
-        // Remove any stairs from prebuilt map since we will place them
-        for t in self.map.tiles.iter_mut() {
-            if *t == TileType::DownStairs { *t = TileType::Floor; }
+        // Chunks have to fit on the map, or there is nothing to learn from the sample
+        if chunk_size < 1 || chunk_size > i32::min(sample.width, sample.height) {
+            console::log(format!(
+                "WFC chunk size {} doesn't fit the map; using the sample map instead.",
+                chunk_size
+            ));
+            self.map = sample.clone();
+        } else {
+            let patterns = constraints::build_patterns(
+                &sample,
+                chunk_size,
+                self.settings.include_flipping,
+                true,
+            );
+            let constraints = constraints::patterns_to_constraints(patterns, chunk_size);
+            self.render_tile_gallery(&constraints, chunk_size);
+
+            self.map = match self.solve(&constraints, rng) {
+                Some(map) => map,
+                None => {
+                    console::log("WFC failed; using the sample map instead.".to_string());
+                    sample.clone()
+                }
+            };
         }
-        self.take_snapshot();
 
-        let patterns = constraints::build_patterns(&self.map, CHUNK_SIZE, true, true);
-        let constraints = constraints::patterns_to_constraints(patterns, CHUNK_SIZE);
-        self.render_tile_gallery(&constraints, CHUNK_SIZE);
+        // Chunks rarely line up with the map edges, so seal the border
+        seal_border(&mut self.map);
 
-        self.map = Map::new(self.depth);
-        loop {
-            let mut solver = Solver::new(constraints.clone(), CHUNK_SIZE, &self.map);
-            while !solver.iteration(&mut self.map, &mut rng) {
-                self.take_snapshot();
+        // Find a starting point; solved maps often contain small sealed pockets, so start in
+        // the largest open area. A map with no floor left falls back to the sample.
+        let start_idx = match mbcommon::start_in_largest_area(&mut self.map) {
+            Some(idx) => idx,
+            None => {
+                console::log("WFC left no floor; using the sample map instead.".to_string());
+                self.map = sample;
+                seal_border(&mut self.map);
+                mbcommon::start_in_largest_area(&mut self.map)
+                    .unwrap_or_else(|| open_middle(&mut self.map))
             }
-            self.take_snapshot();
-            if solver.possible { break; } // If it has hit an impossible condition, try again
-        }
-
-        // Find a starting point; start at the middle and walk left until we find an open tile
+        };
         self.starting_position = Position {
-            x: self.map.width / 2,
-            y: self.map.height / 2,
+            x: start_idx as i32 % self.map.width,
+            y: start_idx as i32 / self.map.width,
         };
-        let mut start_idx = self
-            .map
-            .xy_idx(self.starting_position.x, self.starting_position.y)
-            .unwrap();
-        while self.map.tiles[start_idx] != TileType::Floor {
-            self.starting_position.x -= 1;
-            start_idx = self.map.xy_idx(self.starting_position.x, self.starting_position.y).unwrap();
-        }
-        self.take_snapshot();
+        self.take_snapshot("Find starting point");
 
         // Find all tiles we can reach from the starting point
         let exit_tile =
@@ -88,17 +197,40 @@ impl WaveformCollapseBuilder {
 
         // Place the stairs
         self.map.tiles[exit_tile] = TileType::DownStairs;
-        self.take_snapshot();
+        self.take_snapshot("Place stairs");
 
         // Now we build a noise map for use in spawning entities later
-        self.noise_areas = mbcommon::generate_voronoi_spawn_regions(&self.map, &mut rng);
+        self.noise_areas = mbcommon::generate_voronoi_spawn_regions(&self.map, rng);
+
+        // Spawn entities
+        for area in self.noise_areas.iter() {
+            spawner::spawn_region(&self.map, rng, area.1, self.depth, &mut self.spawn_list);
+        }
     }
 
     pub fn derived_map(new_depth: i32, builder: Box<dyn MapBuilder>) -> Self {
-        WaveformCollapseBuilder::new(new_depth, builder)
+        WaveformCollapseBuilder::new(
+            new_depth,
+            WaveformSource::Builder(builder),
+            WaveformCollapseSettings::default(),
+        )
     }
 
-    fn render_tile_gallery(&mut self, constraints: &Vec<MapChunk>, chunk_size: i32) {
+    /// Learns from a REX Paint sample in `SAMPLE_DIR`. The file is read up front, so a missing
+    /// or corrupt sample is reported here and the caller can pick another builder.
+    pub fn rex_sample(new_depth: i32, file_name: &str) -> Result<Self, SampleError> {
+        let path = Path::new(SAMPLE_DIR).join(file_name);
+        let xp_file = File::open(&path)
+            .and_then(|mut file| XpFile::read(&mut file))
+            .map_err(|error| SampleError { path: path.clone(), error })?;
+        Ok(WaveformCollapseBuilder::new(
+            new_depth,
+            WaveformSource::RexFile(xp_file),
+            WaveformCollapseSettings::default(),
+        ))
+    }
+
+    fn render_tile_gallery(&mut self, constraints: &[MapChunk], chunk_size: i32) {
         self.map = Map::new(0);
         let mut counter = 0;
         let mut x = 1;
@@ -114,7 +246,7 @@ impl WaveformCollapseBuilder {
     
                 if y + chunk_size > self.map.height {
                     // Move to the next page
-                    self.take_snapshot();
+                    self.take_snapshot("Tile gallery");
                     self.map = Map::new(0);
     
                     x = 1;
@@ -124,20 +256,43 @@ impl WaveformCollapseBuilder {
     
             counter += 1;
         }
-        self.take_snapshot();
+        self.take_snapshot("Tile gallery");
     }
 }
 
+/// Walls in the edge of the map
+fn seal_border(map: &mut Map) {
+    for x in 0..map.width {
+        for y in [0, map.height - 1].iter() {
+            let idx = map.xy_idx(x, *y).unwrap();
+            map.tiles[idx] = TileType::Wall;
+        }
+    }
+    for y in 0..map.height {
+        for x in [0, map.width - 1].iter() {
+            let idx = map.xy_idx(*x, y).unwrap();
+            map.tiles[idx] = TileType::Wall;
+        }
+    }
+}
+
+/// Opens up the middle of a map without any floor, so there is somewhere to start
+fn open_middle(map: &mut Map) -> usize {
+    let idx = map.xy_idx(map.width / 2, map.height / 2).unwrap();
+    map.tiles[idx] = TileType::Floor;
+    idx
+}
+
 impl MapBuilder for WaveformCollapseBuilder {
-    fn build_map(&mut self) {
-        self.build();
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator) {
+        self.build(rng);
     }
 
     fn get_map(&mut self) -> Map {
         self.map.clone()
     }
 
-    fn get_snapshot_history(&self) -> Vec<Map> {
+    fn get_snapshot_history(&self) -> Vec<MapSnapshot> {
         self.history.clone()
     }
 
@@ -145,19 +300,51 @@ impl MapBuilder for WaveformCollapseBuilder {
         self.starting_position.clone()
     }
 
-    fn spawn_entities(&mut self, ecs: &mut World) {
-        for area in self.noise_areas.iter() {
-            spawner::spawn_region(ecs, area.1, self.depth);
-        }
+    fn get_spawn_list(&self) -> &Vec<(usize, String)> {
+        &self.spawn_list
     }
 
-    fn take_snapshot(&mut self) {
-        if SHOW_MAPGEN_VISUALIZER {
-            let mut snapshot = self.map.clone();
-            for v in snapshot.revealed_tiles.iter_mut() {
-                *v = true;
-            }
-            self.history.push(snapshot);
+    fn take_snapshot(&mut self, phase: &str) {
+        MapSnapshot::take(&mut self.history, &self.map, phase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cellular_automata::CellularAutomataBuilder;
+    use super::super::super::map::{MAPHEIGHT, MAPWIDTH};
+    use super::super::validation::validate_map;
+    use super::*;
+
+    #[test]
+    fn bad_rex_samples_are_errors() {
+        let not_rex = "prefabs/wfc_populated.prefab";
+        assert!(WaveformCollapseBuilder::rex_sample(1, "missing.xp").is_err());
+        assert!(WaveformCollapseBuilder::rex_sample(1, not_rex).is_err());
+    }
+
+    #[test]
+    fn bad_chunk_sizes_use_the_sample() {
+        for chunk_size in [0, -1, 100].iter() {
+            let settings = WaveformCollapseSettings {
+                chunk_size: *chunk_size,
+                ..Default::default()
+            };
+            let sample = WaveformSource::Builder(Box::new(CellularAutomataBuilder::new(1)));
+            let mut builder = WaveformCollapseBuilder::new(1, sample, settings);
+            builder.build_map(&mut RandomNumberGenerator::seeded(1));
+            let errors = validate_map(&builder.get_map(), &builder.get_starting_position());
+            assert!(errors.is_empty(), "chunk size {}: {:?}", chunk_size, errors);
         }
     }
+
+    #[test]
+    fn samples_without_floor_still_have_a_start() {
+        let sample = WaveformSource::RexFile(XpFile::new(MAPWIDTH, MAPHEIGHT));
+        let mut builder = WaveformCollapseBuilder::new(1, sample, Default::default());
+        builder.build_map(&mut RandomNumberGenerator::seeded(1));
+        let map = builder.get_map();
+        let start = builder.get_starting_position();
+        assert!(map.tiles[map.xy_idx(start.x, start.y).unwrap()] == TileType::Floor);
+    }
 }
@@ -1,234 +1,224 @@
-use std::collections::HashSet;
+use rltk::RandomNumberGenerator;
+use std::collections::VecDeque;
 
 use super::super::super::map::Map;
 
 use super::common::MapChunk;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolverStatus {
+    InProgress,
+    Solved,
+    Failed(SolverError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SolverError {
+    /// The constraints cannot produce any chunk at all
+    NoConstraints,
+    /// A cell ran out of options and there was no decision left to undo
+    Contradiction,
+    /// The solver gave up after undoing too many decisions
+    BacktrackLimit,
+}
+
+/// A choice the solver made, with the state to return to if it leads to a contradiction
+struct Decision {
+    domains: Vec<Vec<bool>>,
+    cell: usize,
+    option: usize,
+}
+
+/// Wave function collapse over map chunks. Every cell starts with every chunk as an option;
+/// each iteration collapses the lowest-entropy cell and propagates the result to its
+/// neighbors. Contradictions are resolved by undoing decisions, up to `max_backtracks` times.
 pub struct Solver {
     constraints: Vec<MapChunk>,
-    chunk_size : i32,
-    chunks : Vec<Option<usize>>,
-    chunks_x : usize,
-    chunks_y : usize,
-    remaining : Vec<(usize, i32)>, // (index, # neighbors)
-    pub possible: bool
+    chunk_size: i32,
+    chunks_x: usize,
+    chunks_y: usize,
+    domains: Vec<Vec<bool>>,
+    // allowed[direction][(a * n) + b] is true if chunk b may sit on the `direction` side of a
+    allowed: [Vec<bool>; 4],
+    decisions: Vec<Decision>,
+    backtracks: usize,
+    max_backtracks: usize,
 }
 
 impl Solver {
-    pub fn new(constraints: Vec<MapChunk>, chunk_size: i32, map : &Map) -> Solver {
+    pub fn new(constraints: Vec<MapChunk>, chunk_size: i32, map: &Map, max_backtracks: usize) -> Solver {
         let chunks_x = (map.width / chunk_size) as usize;
         let chunks_y = (map.height / chunk_size) as usize;
-        let mut remaining : Vec<(usize, i32)> = Vec::new();
-        for i in 0..(chunks_x*chunks_y) {
-            remaining.push((i, 0));
+        let n = constraints.len();
+
+        let mut allowed = [vec![false; n * n], vec![false; n * n], vec![false; n * n], vec![false; n * n]];
+        for (direction, allowed_dir) in allowed.iter_mut().enumerate() {
+            let opposite = opposite(direction);
+            for a in 0..n {
+                for b in constraints[a].compatible_with[direction].iter() {
+                    if constraints[*b].compatible_with[opposite].contains(&a) {
+                        allowed_dir[(a * n) + *b] = true;
+                    }
+                }
+            }
         }
 
         Solver {
             constraints,
             chunk_size,
-            chunks: vec![None; chunks_x * chunks_y],
             chunks_x,
             chunks_y,
-            remaining,
-            possible: true
+            domains: vec![vec![true; n]; chunks_x * chunks_y],
+            allowed,
+            decisions: Vec::new(),
+            backtracks: 0,
+            max_backtracks,
         }
     }
 
-    fn chunk_idx(&self, x:usize, y:usize) -> usize {
-        ((y * self.chunks_x) + x) as usize
+    fn chunk_idx(&self, x: usize, y: usize) -> usize {
+        (y * self.chunks_x) + x
     }
 
-    fn count_neighbors(&self, chunk_x:usize, chunk_y:usize) -> i32 {
-        let mut neighbors = 0;
-    
-        if chunk_x > 0 {
-            let left_idx = self.chunk_idx(chunk_x-1, chunk_y);
-            match self.chunks[left_idx] {
-                None => {}
-                Some(_) => {
-                    neighbors += 1;
-                }
-            }
-        }
-    
-        if chunk_x < self.chunks_x-1 {
-            let right_idx = self.chunk_idx(chunk_x+1, chunk_y);
-            match self.chunks[right_idx] {
-                None => {}
-                Some(_) => {
-                    neighbors += 1;
-                }
-            }
-        }
-    
-        if chunk_y > 0 {
-            let up_idx = self.chunk_idx(chunk_x, chunk_y-1);
-            match self.chunks[up_idx] {
-                None => {}
-                Some(_) => {
-                    neighbors += 1;
-                }
-            }
-        }
-    
-        if chunk_y < self.chunks_y-1 {
-            let down_idx = self.chunk_idx(chunk_x, chunk_y+1);
-            match self.chunks[down_idx] {
-                None => {}
-                Some(_) => {
-                    neighbors += 1;
-                }
-            }
-        }
+    /// Neighbors of a cell as (direction, index); directions are north, south, west, east
+    fn neighbors(&self, idx: usize) -> Vec<(usize, usize)> {
+        let x = idx % self.chunks_x;
+        let y = idx / self.chunks_x;
+        let mut neighbors = Vec::new();
+        if y > 0 { neighbors.push((0, self.chunk_idx(x, y - 1))); }
+        if y < self.chunks_y - 1 { neighbors.push((1, self.chunk_idx(x, y + 1))); }
+        if x > 0 { neighbors.push((2, self.chunk_idx(x - 1, y))); }
+        if x < self.chunks_x - 1 { neighbors.push((3, self.chunk_idx(x + 1, y))); }
         neighbors
     }
 
-    pub fn iteration(&mut self, map: &mut Map, rng : &mut super::RandomNumberGenerator) -> bool {
-        if self.remaining.is_empty() { return true; }
-    
-        // Populate the neighbor count of the remaining list
-        let mut remain_copy = self.remaining.clone();
-        let mut neighbors_exist = false;
-
-        for r in remain_copy.iter_mut() {
-            let idx = r.0;
-            let chunk_x = idx % self.chunks_x;
-            let chunk_y = idx / self.chunks_x;
-            let neighbor_count = self.count_neighbors(chunk_x, chunk_y);
-            if neighbor_count > 0 { neighbors_exist = true; }
-            *r = (r.0, neighbor_count);
-        }
+    fn option_count(&self, idx: usize) -> usize {
+        self.domains[idx].iter().filter(|o| **o).count()
+    }
 
-        // Sort `remaining` by # of neighbors descending
-        remain_copy.sort_by(|a,b| b.1.cmp(&a.1));
-        self.remaining = remain_copy;
-    
-        // If no tiles have been made, pick a random one; otherwise,
-        // pick the tile with the most neighbors
-        let remaining_index = if !neighbors_exist { 
-            (rng.roll_dice(1, self.remaining.len() as i32)-1) as usize
-        } else {
-            0usize
-        };
-        let chunk_index = self.remaining[remaining_index].0;
-        self.remaining.remove(remaining_index);
-    
-        let chunk_x = chunk_index % self.chunks_x;
-        let chunk_y = chunk_index / self.chunks_x;
-    
-        let mut neighbors = 0;
-        let mut options : Vec<Vec<usize>> = Vec::new();
-    
-        if chunk_x > 0 {
-            let left_idx = self.chunk_idx(chunk_x-1, chunk_y);
-            match self.chunks[left_idx] {
-                None => {}
-                Some(nt) => {
-                    neighbors += 1;
-                    options.push(self.constraints[nt].compatible_with[3].clone());
+    /// Removes options from neighboring cells that no longer have support, starting from
+    /// `start`. Returns false if any cell is left without options.
+    fn propagate(&mut self, start: usize) -> bool {
+        let n = self.constraints.len();
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        queue.push_back(start);
+
+        while let Some(idx) = queue.pop_front() {
+            for (direction, neighbor) in self.neighbors(idx) {
+                let mut changed = false;
+                for b in 0..n {
+                    if !self.domains[neighbor][b] { continue; }
+                    let supported = (0..n)
+                        .any(|a| self.domains[idx][a] && self.allowed[direction][(a * n) + b]);
+                    if !supported {
+                        self.domains[neighbor][b] = false;
+                        changed = true;
+                    }
                 }
-            }
-        }
-    
-        if chunk_x < self.chunks_x-1 {
-            let right_idx = self.chunk_idx(chunk_x+1, chunk_y);
-            match self.chunks[right_idx] {
-                None => {}
-                Some(nt) => {
-                    neighbors += 1;
-                    options.push(self.constraints[nt].compatible_with[2].clone());
+                if changed {
+                    if self.option_count(neighbor) == 0 { return false; }
+                    queue.push_back(neighbor);
                 }
             }
         }
-    
-        if chunk_y > 0 {
-            let up_idx = self.chunk_idx(chunk_x, chunk_y-1);
-            match self.chunks[up_idx] {
-                None => {}
-                Some(nt) => {
-                    neighbors += 1;
-                    options.push(self.constraints[nt].compatible_with[1].clone());
+
+        true
+    }
+
+    /// Picks the undecided cell with the lowest entropy, breaking ties randomly
+    fn lowest_entropy_cell(&self, rng: &mut RandomNumberGenerator) -> Option<usize> {
+        let mut best: Option<(usize, f32)> = None;
+        for idx in 0..self.domains.len() {
+            let count = self.option_count(idx);
+            if count > 1 {
+                // Every chunk is equally likely, so the entropy is just ln(options)
+                let entropy = (count as f32).ln() + (rng.roll_dice(1, 1000) as f32 / 100_000.0);
+                if best.is_none_or(|b| entropy < b.1) {
+                    best = Some((idx, entropy));
                 }
             }
         }
-    
-        if chunk_y < self.chunks_y-1 {
-            let down_idx = self.chunk_idx(chunk_x, chunk_y+1);
-            match self.chunks[down_idx] {
-                None => {}
-                Some(nt) => {
-                    neighbors += 1;
-                    options.push(self.constraints[nt].compatible_with[0].clone());
-                }
+        best.map(|b| b.0)
+    }
+
+    /// Undoes decisions until one of them can be retried with a different option
+    fn backtrack(&mut self) -> Result<(), SolverError> {
+        loop {
+            let decision = match self.decisions.pop() {
+                Some(decision) => decision,
+                None => return Err(SolverError::Contradiction),
+            };
+            self.backtracks += 1;
+            if self.backtracks > self.max_backtracks {
+                return Err(SolverError::BacktrackLimit);
             }
-        }
-    
-        if neighbors == 0 {
-            // There is nothing nearby, so we can have anything!
-            let new_chunk_idx = (rng.roll_dice(1, self.constraints.len() as i32)-1) as usize;
-            self.chunks[chunk_index] = Some(new_chunk_idx);
-            let left_x = chunk_x as i32 * self.chunk_size as i32;
-            let right_x = (chunk_x as i32+1) * self.chunk_size as i32;
-            let top_y = chunk_y as i32 * self.chunk_size as i32;
-            let bottom_y = (chunk_y as i32+1) * self.chunk_size as i32;
-    
-            let mut i : usize = 0;
-            for y in top_y .. bottom_y {
-                for x in left_x .. right_x {
-                    let mapidx = map.xy_idx(x, y).unwrap();
-                    let tile = self.constraints[new_chunk_idx].pattern[i];
-                    map.tiles[mapidx] = tile;
-                    i += 1;
-                }
+
+            self.domains = decision.domains;
+            self.domains[decision.cell][decision.option] = false;
+            if self.option_count(decision.cell) > 0 && self.propagate(decision.cell) {
+                return Ok(());
             }
         }
-        else {
-            // There are neighbors, so we try to be compatible with them
-            let mut options_to_check : HashSet<usize> = HashSet::new();
-            for o in options.iter() {
-                for i in o.iter() {
-                    options_to_check.insert(*i);
-                }
-            }
-    
-            let mut possible_options : Vec<usize> = Vec::new();
-            for new_chunk_idx in options_to_check.iter() {
-                let mut possible = true;
-                for o in options.iter() {
-                    if !o.contains(new_chunk_idx) { possible = false; }
+    }
+
+    /// Runs a single step of the solver and draws all decided chunks onto the map.
+    pub fn iteration(&mut self, map: &mut Map, rng: &mut RandomNumberGenerator) -> SolverStatus {
+        if self.constraints.is_empty() || self.domains.is_empty() {
+            return SolverStatus::Failed(SolverError::NoConstraints);
+        }
+
+        let status = match self.lowest_entropy_cell(rng) {
+            None => SolverStatus::Solved,
+            Some(cell) => {
+                let options: Vec<usize> =
+                    (0..self.constraints.len()).filter(|o| self.domains[cell][*o]).collect();
+                let option = options[(rng.roll_dice(1, options.len() as i32) - 1) as usize];
+
+                self.decisions.push(Decision { domains: self.domains.clone(), cell, option });
+                for (i, possible) in self.domains[cell].iter_mut().enumerate() {
+                    *possible = i == option;
                 }
-                if possible {
-                    possible_options.push(*new_chunk_idx);
+
+                if self.propagate(cell) {
+                    SolverStatus::InProgress
+                } else {
+                    match self.backtrack() {
+                        Ok(()) => SolverStatus::InProgress,
+                        Err(e) => SolverStatus::Failed(e),
+                    }
                 }
             }
-    
-            if possible_options.is_empty() {
-                rltk::console::log("Oh no! It's not possible!");
-                self.possible = false;
-                return true;
-            } else {
-                let new_chunk_idx = if possible_options.len() == 1 { 0 } 
-                    else { rng.roll_dice(1, possible_options.len() as i32)-1 };
-    
-                self.chunks[chunk_index] = Some(new_chunk_idx as usize);
-                let left_x = chunk_x as i32 * self.chunk_size as i32;
-                let right_x = (chunk_x as i32+1) * self.chunk_size as i32;
-                let top_y = chunk_y as i32 * self.chunk_size as i32;
-                let bottom_y = (chunk_y as i32+1) * self.chunk_size as i32;
-    
-    
-                let mut i : usize = 0;
-                for y in top_y .. bottom_y {
-                    for x in left_x .. right_x {
+        };
+
+        self.render(map);
+        status
+    }
+
+    fn render(&self, map: &mut Map) {
+        for (idx, domain) in self.domains.iter().enumerate() {
+            let left_x = (idx % self.chunks_x) as i32 * self.chunk_size;
+            let top_y = (idx / self.chunks_x) as i32 * self.chunk_size;
+            let decided = if self.option_count(idx) == 1 { domain.iter().position(|o| *o) } else { None };
+
+            if let Some(chunk) = decided {
+                let mut i: usize = 0;
+                for y in top_y..top_y + self.chunk_size {
+                    for x in left_x..left_x + self.chunk_size {
                         let mapidx = map.xy_idx(x, y).unwrap();
-                        let tile = self.constraints[new_chunk_idx as usize].pattern[i];
-                        map.tiles[mapidx] = tile;
+                        map.tiles[mapidx] = self.constraints[chunk].pattern[i];
                         i += 1;
                     }
                 }
             }
         }
-    
-        false
     }
-}
\ No newline at end of file
+}
+
+fn opposite(direction: usize) -> usize {
+    match direction {
+        0 => 1, // North, South
+        1 => 0, // South, North
+        2 => 3, // West, East
+        _ => 2, // East, West
+    }
+}
@@ -1,5 +1,5 @@
 use specs::prelude::*;
-use super::components::{BlocksTile, Position};
+use super::components::{BlocksTile, EntryTrigger, Hidden, Position};
 use super::map::Map;
 
 pub struct MapIndexingSystem {}
@@ -8,13 +8,16 @@ impl<'a> System<'a> for MapIndexingSystem {
     type SystemData = ( WriteExpect<'a, Map>,
                         ReadStorage<'a, Position>,
                         ReadStorage<'a, BlocksTile>,
-                        Entities<'a>, );
+                        Entities<'a>,
+                        ReadStorage<'a, EntryTrigger>,
+                        ReadStorage<'a, Hidden>, );
     
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, position, blockers, entities, triggers, hidden) = data;
 
         map.populate_blocked();
         map.clear_content_index();
+        map.known_traps.clear();
         for (entity, position) in (&entities, &position).join() {
             let idx = map.xy_idx(position.x, position.y).unwrap();
 
@@ -22,6 +25,11 @@ impl<'a> System<'a> for MapIndexingSystem {
             // let _p: Option<&BlocksTile> = blockers.get(entity);
             if let Some(_) = blockers.get(entity) { map.blocked[idx] = true; }
 
+            // Remember where the revealed traps are, so monsters can path around them
+            if triggers.get(entity).is_some() && hidden.get(entity).is_none() {
+                map.known_traps.insert(idx);
+            }
+
             // Push the entity to the appropriate index slot. It is a Copy 
             // type, so we do not need to clone it (we want to avoid moving
             // it out of the ECS!)
@@ -1,8 +1,9 @@
 use super::components::{
-    CombatStats, DefenseBonus, Equipped, HungerClock, HungerState, MeleePowerBonus, Name, Position,
-    SufferDamage, WantsToMelee,
+    CombatStats, DamageSource, DefenseBonus, Enchantment, Equipped, FireDamage, HungerClock,
+    HungerState, MeleePowerBonus, Name, Position, SufferDamage, WantsToMelee,
 };
 use super::gamelog::GameLog;
+use super::noise_system::{NoiseBuilder, COMBAT_VOLUME};
 use super::particle_system::ParticleBuilder;
 use specs::prelude::*;
 
@@ -11,6 +12,7 @@ pub struct MeleeCombatSystem {}
 impl<'a> System<'a> for MeleeCombatSystem {
     type SystemData = (
         Entities<'a>,
+        ReadExpect<'a, Entity>,
         WriteExpect<'a, GameLog>,
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
@@ -22,11 +24,15 @@ impl<'a> System<'a> for MeleeCombatSystem {
         WriteExpect<'a, ParticleBuilder>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, HungerClock>,
+        ReadStorage<'a, Enchantment>,
+        ReadStorage<'a, FireDamage>,
+        WriteExpect<'a, NoiseBuilder>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
             entities,
+            player_entity,
             mut log,
             mut wants_melee,
             names,
@@ -38,6 +44,9 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut particle_builder,
             positions,
             hunger_clock,
+            enchantments,
+            fire_damage,
+            mut noise_builder,
         ) = data;
 
         for (entity, wants_melee, name, stats) in
@@ -45,11 +54,22 @@ impl<'a> System<'a> for MeleeCombatSystem {
         {
             if stats.hp > 0 {
                 let mut total_offensive_bonus = 0;
-                for (_item_entity, power_bonus, equipped_by) in
+                let mut total_fire_damage = 0;
+                for (_item_entity, fire, equipped_by) in
+                    (&entities, &fire_damage, &equipped).join()
+                {
+                    if equipped_by.owner == entity {
+                        total_fire_damage += fire.damage;
+                    }
+                }
+                for (item_entity, power_bonus, equipped_by) in
                     (&entities, &melee_power_bonuses, &equipped).join()
                 {
                     if equipped_by.owner == entity {
                         total_offensive_bonus += power_bonus.power;
+                        if let Some(enchantment) = enchantments.get(item_entity) {
+                            total_offensive_bonus += enchantment.level;
+                        }
                     }
                 }
 
@@ -66,15 +86,18 @@ impl<'a> System<'a> for MeleeCombatSystem {
                     let target_name = names.get(wants_melee.target).unwrap();
 
                     let mut total_defense_bonus = 0;
-                    for (_item_entity, defense_bonus, equipped_by) in
+                    for (item_entity, defense_bonus, equipped_by) in
                         (&entities, &defense_bonuses, &equipped).join()
                     {
                         if equipped_by.owner == wants_melee.target {
                             total_defense_bonus += defense_bonus.defense;
+                            if let Some(enchantment) = enchantments.get(item_entity) {
+                                total_defense_bonus += enchantment.level;
+                            }
                         }
                     }
 
-                    // Add damage indicator
+                    // Add damage indicator, and let everything nearby hear the fight
                     let pos = positions.get(wants_melee.target);
                     if let Some(pos) = pos {
                         particle_builder.request(
@@ -85,13 +108,15 @@ impl<'a> System<'a> for MeleeCombatSystem {
                             rltk::to_cp437('‼'),
                             200.0,
                         );
+                        noise_builder.request(pos.x, pos.y, COMBAT_VOLUME);
                     }
 
+                    // Fire burns straight through armor
                     let damage = i32::max(
                         0,
                         (stats.power + total_offensive_bonus)
                             - (target_stats.defense + total_defense_bonus),
-                    );
+                    ) + total_fire_damage;
 
                     if damage == 0 {
                         log.entries.insert(
@@ -107,7 +132,13 @@ impl<'a> System<'a> for MeleeCombatSystem {
                             ),
                         );
                         inflict_damage
-                            .insert(wants_melee.target, SufferDamage { amount: damage })
+                            .insert(
+                                wants_melee.target,
+                                SufferDamage {
+                                    amount: damage,
+                                    source: DamageSource::new(entity == *player_entity, &name.name),
+                                },
+                            )
                             .expect("Unable to do damage");
                     }
                 }
@@ -1,7 +1,9 @@
 use rltk::Point;
 use specs::prelude::*;
 
-use super::components::{Confusion, EntityMoved, Monster, Position, Viewshed, WantsToMelee};
+use super::components::{
+    Asleep, Confusion, EntityMoved, Investigating, Monster, Position, Viewshed, WantsToMelee,
+};
 use super::map::Map;
 use super::RunState;
 use super::particle_system::ParticleBuilder;
@@ -23,6 +25,8 @@ impl<'a> System<'a> for MonsterAI {
         WriteStorage<'a, Confusion>,
         WriteExpect<'a, ParticleBuilder>,
         WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, Investigating>,
+        ReadStorage<'a, Asleep>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -39,6 +43,8 @@ impl<'a> System<'a> for MonsterAI {
             mut confused,
             mut particle_builder,
             mut entity_moved,
+            mut investigating,
+            asleep,
         ) = data;
 
         if *runstate != RunState::MonsterTurn {
@@ -48,6 +54,11 @@ impl<'a> System<'a> for MonsterAI {
         for (entity, mut viewshed, _monster, mut pos) in
             (&entities, &mut viewshed, &monster, &mut position).join()
         {
+            // Sleeping monsters wait for a noise to wake them
+            if asleep.get(entity).is_some() {
+                continue;
+            }
+
             let mut can_act = true;
             let is_confused = confused.get_mut(entity);
             if let Some(i_am_confused) = is_confused {
@@ -71,6 +82,7 @@ impl<'a> System<'a> for MonsterAI {
                 let distance =
                     rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
                 if viewshed.visible_tiles.contains(&*player_pos) {
+                    investigating.remove(entity);
                     if distance < 1.5 {
                         wants_to_melee
                             .insert(
@@ -98,6 +110,26 @@ impl<'a> System<'a> for MonsterAI {
                             viewshed.dirty = true;
                         }
                     }
+                } else if let Some(target) = investigating.get(entity) {
+                    // Head for whatever the monster heard
+                    let path = rltk::a_star_search(
+                        map.xy_idx(pos.x, pos.y).unwrap() as i32,
+                        map.xy_idx(target.x, target.y).unwrap() as i32,
+                        &mut *map,
+                    );
+                    if path.success && path.steps.len() > 1 {
+                        let mut idx = map.xy_idx(pos.x, pos.y).unwrap();
+                        map.blocked[idx] = false;
+                        pos.x = path.steps[1] as i32 % map.width;
+                        pos.y = path.steps[1] as i32 / map.width;
+                        entity_moved.insert(entity, EntityMoved{}).expect("Unable to insert EntityMoved marker");
+                        idx = map.xy_idx(pos.x, pos.y).unwrap();
+                        map.blocked[idx] = true;
+                        viewshed.dirty = true;
+                    }
+                    if !path.success || path.steps.len() <= 2 {
+                        investigating.remove(entity);
+                    }
                 }
             }
         }
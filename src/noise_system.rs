@@ -0,0 +1,150 @@
+use specs::prelude::*;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::components::{Asleep, Investigating, Monster, Name, Position};
+use super::gamelog::GameLog;
+use super::map::{Map, TileType};
+
+/// How much of a noise a wall soaks up, compared to one tile of open floor
+const WALL_DAMPING: i32 = 4;
+/// How loud a noise must still be when it reaches a sleeping monster to wake it
+const WAKE_VOLUME: i32 = 4;
+
+/// How loud a fight is
+pub const COMBAT_VOLUME: i32 = 8;
+/// How loud a footstep is before the walker's stealth is taken off
+pub const FOOTSTEP_VOLUME: i32 = 4;
+
+struct NoiseRequest {
+    x: i32,
+    y: i32,
+    volume: i32,
+}
+
+/// Noises made this turn, waiting for `NoiseSystem` to carry them to whoever can hear
+pub struct NoiseBuilder {
+    requests: Vec<NoiseRequest>,
+}
+
+impl NoiseBuilder {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> NoiseBuilder {
+        NoiseBuilder {
+            requests: Vec::new(),
+        }
+    }
+
+    /// Makes a noise at (x, y) that carries `volume` tiles across open floor.
+    pub fn request(&mut self, x: i32, y: i32, volume: i32) {
+        if volume > 0 {
+            self.requests.push(NoiseRequest { x, y, volume });
+        }
+    }
+}
+
+/// Spreads each noise made this turn through the map, losing volume with every tile and much
+/// more through walls. Monsters that hear a noise go to investigate it, and loud enough noises
+/// wake sleeping monsters.
+pub struct NoiseSystem {}
+
+impl<'a> System<'a> for NoiseSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, NoiseBuilder>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Asleep>,
+        WriteStorage<'a, Investigating>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            mut noise_builder,
+            mut log,
+            entities,
+            monsters,
+            positions,
+            names,
+            mut asleep,
+            mut investigating,
+        ) = data;
+
+        for noise in noise_builder.requests.iter() {
+            let heard = propagate(&map, noise);
+            for (monster, _monster, pos) in (&entities, &monsters, &positions).join() {
+                let idx = match map.xy_idx(pos.x, pos.y) {
+                    Some(idx) => idx,
+                    None => continue,
+                };
+                let volume = match heard.get(&idx) {
+                    Some(volume) => *volume,
+                    None => continue,
+                };
+
+                if asleep.get(monster).is_some() {
+                    if volume < WAKE_VOLUME {
+                        continue;
+                    }
+                    asleep.remove(monster);
+                    if map.visible_tiles[idx] {
+                        if let Some(name) = names.get(monster) {
+                            log.entries
+                                .insert(0, format!("The {} wakes up!", name.name));
+                        }
+                    }
+                }
+
+                investigating
+                    .insert(
+                        monster,
+                        Investigating {
+                            x: noise.x,
+                            y: noise.y,
+                        },
+                    )
+                    .expect("Unable to insert investigation");
+            }
+        }
+
+        noise_builder.requests.clear();
+    }
+}
+
+/// How loud a noise is on every tile it reaches
+fn propagate(map: &Map, noise: &NoiseRequest) -> HashMap<usize, i32> {
+    let mut heard: HashMap<usize, i32> = HashMap::new();
+    let start = match map.xy_idx(noise.x, noise.y) {
+        Some(idx) => idx,
+        None => return heard,
+    };
+
+    let mut open = BinaryHeap::new();
+    open.push((noise.volume, start));
+    while let Some((volume, idx)) = open.pop() {
+        if heard.get(&idx).is_some_and(|louder| *louder >= volume) {
+            continue;
+        }
+        heard.insert(idx, volume);
+
+        let x = idx as i32 % map.width;
+        let y = idx as i32 / map.width;
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+            if let Some(next) = map.xy_idx(x + dx, y + dy) {
+                let cost = if map.tiles[next] == TileType::Wall {
+                    WALL_DAMPING
+                } else {
+                    1
+                };
+                if volume - cost > 0 {
+                    open.push((volume - cost, next));
+                }
+            }
+        }
+    }
+    heard
+}
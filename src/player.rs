@@ -1,23 +1,65 @@
 use super::components::{
-    CombatStats, EntityMoved, HungerClock, HungerState, Item, Monster, Player, Position, Viewshed,
-    WantsToMelee, WantsToPickupItem,
+    Amulet, CombatStats, Confusion, Digger, EntityMoved, Equipped, HungerClock, HungerState,
+    InBackpack, Item, Monster, Npc, NpcRole, Player, Position, Sneaking, Stealth, Viewshed,
+    WantsToDig, WantsToMelee, WantsToPickupItem,
 };
+use super::containers;
+use super::dungeon_plan;
 use super::gamelog::GameLog;
+use super::gui::ShopMode;
 use super::map::{Map, TileType};
+use super::noise_system::{NoiseBuilder, FOOTSTEP_VOLUME};
+use super::traps;
 use super::{RunState, State};
-use rltk::{Point, Rltk, VirtualKeyCode};
+use rltk::{Point, RandomNumberGenerator, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 use std::cmp::{max, min};
 
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+/// A confused player stumbles in a random direction instead of the one they chose, until the
+/// confusion wears off.
+fn stumble(delta_x: i32, delta_y: i32, ecs: &mut World) -> (i32, i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut confused = ecs.write_storage::<Confusion>();
+    let confusion = match confused.get_mut(player_entity) {
+        Some(confusion) => confusion,
+        None => return (delta_x, delta_y),
+    };
+
+    confusion.turns -= 1;
+    if confusion.turns < 1 {
+        confused.remove(player_entity);
+        ecs.fetch_mut::<GameLog>()
+            .entries
+            .insert(0, "You no longer feel confused.".to_string());
+    }
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    loop {
+        let delta = (rng.roll_dice(1, 3) - 2, rng.roll_dice(1, 3) - 2);
+        if delta != (0, 0) {
+            return delta;
+        }
+    }
+}
+
+/// Moves the player, attacking or talking to whatever is in the way. Bumping into a vendor
+/// opens their shop, and bumping into a wall with a digging tool equipped digs at it.
+pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> RunState {
+    let (delta_x, delta_y) = stumble(delta_x, delta_y, ecs);
     let mut positions = ecs.write_storage::<Position>();
     let entities = ecs.entities();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let players = ecs.read_storage::<Player>();
-    let combat_stats = ecs.read_storage::<CombatStats>();
+    let mut combat_stats = ecs.write_storage::<CombatStats>();
+    let npcs = ecs.read_storage::<Npc>();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
     let mut entity_moved = ecs.write_storage::<EntityMoved>();
+    let stealth = ecs.read_storage::<Stealth>();
+    let sneaking = ecs.read_storage::<Sneaking>();
+    let equipped = ecs.read_storage::<Equipped>();
+    let diggers = ecs.read_storage::<Digger>();
+    let mut wants_to_dig = ecs.write_storage::<WantsToDig>();
     let map = ecs.fetch::<Map>();
+    let mut newrunstate = RunState::PlayerTurn;
 
     // TODO: Handle multiple player objects.
     for (entity, _player, viewshed, pos) in
@@ -25,6 +67,17 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     {
         if let Some(destination_idx) = map.xy_idx(pos.x + delta_x, pos.y + delta_y) {
             for potential_target in map.tile_content[destination_idx].iter() {
+                if let Some(npc) = npcs.get(*potential_target) {
+                    if npc.role == NpcRole::Vendor {
+                        newrunstate = RunState::ShowShop {
+                            vendor: *potential_target,
+                            mode: ShopMode::Buy,
+                        };
+                    }
+                    let mut gamelog = ecs.fetch_mut::<GameLog>();
+                    talk_to_npc(npc.role, combat_stats.get_mut(entity), &mut gamelog);
+                }
+
                 let target = combat_stats.get(*potential_target);
                 if let Some(_target) = target {
                     wants_to_melee
@@ -38,6 +91,21 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                 }
             }
 
+            let has_digger = (&equipped, &diggers)
+                .join()
+                .any(|(equipped, _)| equipped.owner == entity);
+            if has_digger && map.is_diggable(destination_idx) {
+                wants_to_dig
+                    .insert(
+                        entity,
+                        WantsToDig {
+                            x: pos.x + delta_x,
+                            y: pos.y + delta_y,
+                        },
+                    )
+                    .expect("Unable to insert dig");
+            }
+
             if !map.blocked[destination_idx] {
                 entity_moved.insert(entity, EntityMoved{}).expect("Unable to insert EntityMoved marker");
                 pos.x = min(map.width - 1, max(0, pos.x + delta_x));
@@ -47,10 +115,37 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                 ppos.x = pos.x;
                 ppos.y = pos.y;
 
+                // Footsteps carry less for a stealthy player, and less again when sneaking
+                let mut volume = FOOTSTEP_VOLUME - stealth.get(entity).map_or(0, |s| s.skill);
+                if sneaking.get(entity).is_some() {
+                    volume /= 2;
+                }
+                ecs.write_resource::<NoiseBuilder>()
+                    .request(pos.x, pos.y, volume);
+
                 viewshed.dirty = true;
             }
         }
     }
+
+    newrunstate
+}
+
+/// Bumping into a townsperson talks to them instead of attacking
+fn talk_to_npc(role: NpcRole, player_stats: Option<&mut CombatStats>, gamelog: &mut GameLog) {
+    let message = match role {
+        NpcRole::Healer => {
+            if let Some(stats) = player_stats {
+                stats.hp = stats.max_hp;
+            }
+            "The healer tends to your wounds. You feel much better."
+        }
+        NpcRole::QuestGiver => {
+            "\"Something stirs at the bottom of the dungeon. Come back when you know what it is.\""
+        }
+        NpcRole::Vendor => "\"Welcome! Have a look at my wares.\"",
+    };
+    gamelog.entries.insert(0, message.to_string());
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
@@ -60,38 +155,66 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
         Some(key) => match key {
             // Cardinal movement
             VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                try_move_player(-1, 0, &mut gs.ecs)
+                return try_move_player(-1, 0, &mut gs.ecs)
             }
             VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                try_move_player(1, 0, &mut gs.ecs)
+                return try_move_player(1, 0, &mut gs.ecs)
             }
             VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                try_move_player(0, -1, &mut gs.ecs)
+                return try_move_player(0, -1, &mut gs.ecs)
             }
             VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                try_move_player(0, 1, &mut gs.ecs)
+                return try_move_player(0, 1, &mut gs.ecs)
             }
 
             // Diagonals
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::U => try_move_player(1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::Y => try_move_player(-1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => try_move_player(1, 1, &mut gs.ecs),
+            VirtualKeyCode::Numpad9 | VirtualKeyCode::U => {
+                return try_move_player(1, -1, &mut gs.ecs)
+            }
+            VirtualKeyCode::Numpad7 | VirtualKeyCode::Y => {
+                return try_move_player(-1, -1, &mut gs.ecs)
+            }
+            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => {
+                return try_move_player(-1, 1, &mut gs.ecs)
+            }
+            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => {
+                return try_move_player(1, 1, &mut gs.ecs)
+            }
 
             // Actions
             VirtualKeyCode::G => get_item(&mut gs.ecs),
+            VirtualKeyCode::O => return open_nearby_container(&mut gs.ecs),
             VirtualKeyCode::I => return RunState::ShowInventory,
             VirtualKeyCode::D => return RunState::ShowDropItem,
             VirtualKeyCode::R => return RunState::ShowRemoveItem,
+            VirtualKeyCode::T => return RunState::ShowThrowItem,
+            VirtualKeyCode::S => return traps::search(&mut gs.ecs),
+            VirtualKeyCode::X => return disarm_nearby_trap(&mut gs.ecs),
+            VirtualKeyCode::Z => {
+                toggle_sneaking(&mut gs.ecs);
+                return RunState::AwaitingInput;
+            }
             VirtualKeyCode::Period => {
                 if try_next_level(&mut gs.ecs) {
+                    if gs.ecs.fetch::<Map>().depth >= dungeon_plan::final_depth() {
+                        return try_escape(&mut gs.ecs);
+                    }
                     return RunState::NextLevel;
                 }
             }
+            VirtualKeyCode::Comma => {
+                if try_previous_level(&mut gs.ecs) {
+                    return RunState::PreviousLevel;
+                }
+            }
             VirtualKeyCode::Numpad5 => return skip_turn(&mut gs.ecs),
             VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
             // Metagame Actions
             VirtualKeyCode::Escape => return RunState::SaveGame,
+            VirtualKeyCode::V => {
+                toggle_mapgen_visualizer(gs);
+                return RunState::AwaitingInput;
+            }
 
             _ => return RunState::AwaitingInput,
         },
@@ -99,6 +222,31 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
     RunState::PlayerTurn
 }
 
+fn toggle_sneaking(ecs: &mut World) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut sneaking = ecs.write_storage::<Sneaking>();
+    let message = if sneaking.remove(player_entity).is_some() {
+        "You stop sneaking."
+    } else {
+        sneaking
+            .insert(player_entity, Sneaking {})
+            .expect("Unable to insert sneaking");
+        "You begin to sneak, placing each step with care."
+    };
+    ecs.fetch_mut::<GameLog>().entries.insert(0, message.to_string());
+}
+
+fn toggle_mapgen_visualizer(gs: &mut State) {
+    gs.show_mapgen_visualizer = !gs.show_mapgen_visualizer;
+    let message = if gs.show_mapgen_visualizer {
+        "Map generation will be shown on the next level."
+    } else {
+        "Map generation will no longer be shown."
+    };
+    let mut gamelog = gs.ecs.fetch_mut::<GameLog>();
+    gamelog.entries.insert(0, message.to_string());
+}
+
 pub fn try_next_level(ecs: &mut World) -> bool {
     let player_pos = ecs.fetch::<Point>();
     let map = ecs.fetch::<Map>();
@@ -114,6 +262,39 @@ pub fn try_next_level(ecs: &mut World) -> bool {
     }
 }
 
+/// The stairs out of the final level only open for a player carrying the amulet.
+fn try_escape(ecs: &mut World) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let amulets = ecs.read_storage::<Amulet>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    if (&amulets, &backpack)
+        .join()
+        .any(|(_amulet, pack)| pack.owner == player_entity)
+    {
+        return RunState::Victory;
+    }
+    ecs.fetch_mut::<GameLog>().entries.insert(
+        0,
+        "A force bars the way. You can't leave without the Amulet of Ages.".to_string(),
+    );
+    RunState::PlayerTurn
+}
+
+pub fn try_previous_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y).unwrap();
+    if map.tiles[player_idx] == TileType::UpStairs {
+        true
+    } else {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog
+            .entries
+            .insert(0, "There is no way up from here.".to_string());
+        false
+    }
+}
+
 fn skip_turn(ecs: &mut World) -> RunState {
     let player_entity = ecs.fetch::<Entity>();
     let viewshed_components = ecs.read_storage::<Viewshed>();
@@ -155,6 +336,31 @@ fn skip_turn(ecs: &mut World) -> RunState {
     RunState::PlayerTurn
 }
 
+/// Opens a container under or next to the player, if there is one
+fn open_nearby_container(ecs: &mut World) -> RunState {
+    match containers::adjacent_container(ecs) {
+        Some(container) => RunState::OpenContainer { container },
+        None => {
+            ecs.fetch_mut::<GameLog>()
+                .entries
+                .insert(0, "There is nothing here to open.".to_string());
+            RunState::AwaitingInput
+        }
+    }
+}
+
+fn disarm_nearby_trap(ecs: &mut World) -> RunState {
+    match traps::adjacent_trap(ecs) {
+        Some(trap) => traps::disarm(ecs, trap),
+        None => {
+            ecs.fetch_mut::<GameLog>()
+                .entries
+                .insert(0, "There is no trap here to disarm.".to_string());
+            RunState::AwaitingInput
+        }
+    }
+}
+
 fn get_item(ecs: &mut World) {
     let player_pos = ecs.fetch::<Point>();
     let player_entity = ecs.fetch::<Entity>();
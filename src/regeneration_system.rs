@@ -0,0 +1,41 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+use super::components::{CombatStats, Equipped, Regeneration};
+use super::RunState;
+
+/// One turn in this many, equipment with `Regeneration` heals its wearer
+const REGENERATION_CHANCE: i32 = 5;
+
+pub struct RegenerationSystem {}
+
+impl<'a> System<'a> for RegenerationSystem {
+    type SystemData = (
+        ReadExpect<'a, Entity>, // The player
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        ReadStorage<'a, Regeneration>,
+        ReadStorage<'a, Equipped>,
+        WriteStorage<'a, CombatStats>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, runstate, mut rng, regeneration, equipped, mut combat_stats) = data;
+
+        for (regeneration, equipped_by) in (&regeneration, &equipped).join() {
+            // Each wearer regenerates once per turn: the player on theirs, monsters on theirs
+            let wearers_turn = match *runstate {
+                RunState::PlayerTurn => equipped_by.owner == *player_entity,
+                RunState::MonsterTurn => equipped_by.owner != *player_entity,
+                _ => false,
+            };
+            if !wearers_turn || rng.roll_dice(1, REGENERATION_CHANCE) != 1 {
+                continue;
+            }
+
+            if let Some(stats) = combat_stats.get_mut(equipped_by.owner) {
+                stats.hp = i32::min(stats.max_hp, stats.hp + regeneration.hp);
+            }
+        }
+    }
+}
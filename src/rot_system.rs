@@ -0,0 +1,75 @@
+use specs::prelude::*;
+
+use super::components::{InBackpack, InContainer, Name, Position, Rotting};
+use super::gamelog::GameLog;
+use super::RunState;
+
+/// Counts down rotting items, such as corpses, and removes them once they have rotted away.
+pub struct RotSystem {}
+
+impl<'a> System<'a> for RotSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Rotting>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, InContainer>,
+        ReadExpect<'a, Entity>, // The player
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut rotting,
+            backpack,
+            names,
+            mut positions,
+            mut in_container,
+            player_entity,
+            runstate,
+            mut log,
+        ) = data;
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        let mut rotted: Vec<(Entity, Option<Position>)> = Vec::new();
+        for (entity, rot) in (&entities, &mut rotting).join() {
+            rot.turns -= 1;
+            if rot.turns < 1 {
+                rotted.push((entity, positions.get(entity).cloned()));
+                let carried = backpack.get(entity).is_some_and(|b| b.owner == *player_entity);
+                if carried {
+                    if let Some(name) = names.get(entity) {
+                        log.entries.insert(0, format!("Your {} rots away.", name.name));
+                    }
+                }
+                entities.delete(entity).expect("Unable to delete rotted item");
+            }
+        }
+
+        // Whatever was inside is left lying where it was
+        for (container, pos) in rotted {
+            let spilled: Vec<Entity> = (&entities, &in_container)
+                .join()
+                .filter(|(_, inside)| inside.container == container)
+                .map(|(item, _)| item)
+                .collect();
+            for item in spilled {
+                in_container.remove(item);
+                match &pos {
+                    Some(pos) => {
+                        positions
+                            .insert(item, Position { x: pos.x, y: pos.y })
+                            .expect("Unable to spill item");
+                    }
+                    None => entities.delete(item).expect("Unable to delete item"),
+                }
+            }
+        }
+    }
+}
@@ -7,7 +7,9 @@ use std::fs::File;
 use std::path::Path;
 
 use super::components::*;
-use super::map::Map;
+use super::identification::ItemKnowledge;
+use super::map::{Map, StoredMaps};
+use super::stats_system::RunStats;
 
 macro_rules! serialize_individually {
     ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
@@ -26,9 +28,17 @@ macro_rules! serialize_individually {
 pub fn save_game(ecs: &mut World) {
     // Create helper
     let mapcopy = ecs.get_mut::<Map>().unwrap().clone();
+    let stored_maps = (*ecs.fetch::<StoredMaps>()).clone();
+    let item_knowledge = (*ecs.fetch::<ItemKnowledge>()).clone();
+    let run_stats = (*ecs.fetch::<RunStats>()).clone();
     let savehelper = ecs
         .create_entity()
-        .with(SerializationHelper { map: mapcopy })
+        .with(SerializationHelper {
+            map: mapcopy,
+            stored_maps,
+            item_knowledge,
+            run_stats,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
     // Actually serialize
@@ -74,7 +84,59 @@ pub fn save_game(ecs: &mut World) {
             Hidden,
             EntityMoved,
             EntryTrigger,
-            SingleActivation
+            SingleActivation,
+            Npc,
+            OtherLevelPosition,
+            Gold,
+            Purse,
+            Value,
+            ProvidesIdentification,
+            Equipped,
+            DefenseBonus,
+            MeleePowerBonus,
+            WantsToRemoveItem,
+            Enchantment,
+            Cursed,
+            ProvidesEnchantment,
+            ProvidesRemoveCurse,
+            Affixes,
+            FireDamage,
+            HealthBonus,
+            Regeneration,
+            LootTable,
+            LeavesCorpse,
+            Rotting,
+            Charges,
+            ProvidesRecharge,
+            Weight,
+            Encumbered,
+            Stackable,
+            Container,
+            InContainer,
+            Locked,
+            Key,
+            Trapped,
+            Shatters,
+            WantsToThrowItem,
+            Alarm,
+            Investigating,
+            Rearming,
+            ReleasesGas,
+            TeleportsVictim,
+            TrapDoor,
+            Sprung,
+            Fuel,
+            LightSource,
+            ProvidesFuel,
+            Asleep,
+            Sneaking,
+            Stealth,
+            SetsFire,
+            SpillsLiquid,
+            Digger,
+            DestroysWalls,
+            WantsToDig,
+            Amulet
         );
     }
 
@@ -157,7 +219,59 @@ pub fn load_game(ecs: &mut World) {
             Hidden,
             EntityMoved,
             EntryTrigger,
-            SingleActivation
+            SingleActivation,
+            Npc,
+            OtherLevelPosition,
+            Gold,
+            Purse,
+            Value,
+            ProvidesIdentification,
+            Equipped,
+            DefenseBonus,
+            MeleePowerBonus,
+            WantsToRemoveItem,
+            Enchantment,
+            Cursed,
+            ProvidesEnchantment,
+            ProvidesRemoveCurse,
+            Affixes,
+            FireDamage,
+            HealthBonus,
+            Regeneration,
+            LootTable,
+            LeavesCorpse,
+            Rotting,
+            Charges,
+            ProvidesRecharge,
+            Weight,
+            Encumbered,
+            Stackable,
+            Container,
+            InContainer,
+            Locked,
+            Key,
+            Trapped,
+            Shatters,
+            WantsToThrowItem,
+            Alarm,
+            Investigating,
+            Rearming,
+            ReleasesGas,
+            TeleportsVictim,
+            TrapDoor,
+            Sprung,
+            Fuel,
+            LightSource,
+            ProvidesFuel,
+            Asleep,
+            Sneaking,
+            Stealth,
+            SetsFire,
+            SpillsLiquid,
+            Digger,
+            DestroysWalls,
+            WantsToDig,
+            Amulet
         );
     }
 
@@ -171,6 +285,12 @@ pub fn load_game(ecs: &mut World) {
             let mut worldmap = ecs.write_resource::<super::map::Map>();
             *worldmap = h.map.clone();
             worldmap.tile_content = vec![Vec::new(); super::map::MAPCOUNT];
+            let mut stored_maps = ecs.write_resource::<StoredMaps>();
+            *stored_maps = h.stored_maps.clone();
+            let mut item_knowledge = ecs.write_resource::<ItemKnowledge>();
+            *item_knowledge = h.item_knowledge.clone();
+            let mut run_stats = ecs.write_resource::<RunStats>();
+            *run_stats = h.run_stats.clone();
             deleteme = Some(e);
         }
         for (e, _p, pos) in (&entities, &player, &position).join() {
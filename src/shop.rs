@@ -0,0 +1,171 @@
+use specs::prelude::*;
+
+use super::components::{Amulet, Enchantment, InBackpack, Purse, Value};
+use super::gamelog::GameLog;
+use super::identification::item_display_name;
+use super::inventory_system::has_free_slot;
+use super::stack_system::split_stack;
+
+/// What a vendor charges for an item worth `value` gold
+pub fn buy_price(value: i32) -> i32 {
+    i32::max(1, (value * 3) / 2)
+}
+
+/// What a vendor pays for an item worth `value` gold
+pub fn sell_price(value: i32) -> i32 {
+    i32::max(1, value / 2)
+}
+
+/// Gold value of each level of enchantment on an item
+const ENCHANTMENT_VALUE: i32 = 25;
+
+/// The base value of an item, adjusted for its enchantment. Items without a `Value` are
+/// worth a single coin.
+pub fn item_value(ecs: &World, item: Entity) -> i32 {
+    let base = ecs.read_storage::<Value>()
+        .get(item)
+        .map_or(1, |value| value.gold);
+    let enchantment = ecs.read_storage::<Enchantment>()
+        .get(item)
+        .map_or(0, |enchantment| enchantment.level);
+    i32::max(1, base + (enchantment * ENCHANTMENT_VALUE))
+}
+
+/// Moves an item from the vendor's stock to the player's backpack, if they can afford it.
+/// Only one item is bought from a stack.
+pub fn buy_item(ecs: &mut World, item: Entity) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let price = buy_price(item_value(ecs, item));
+    let problem = {
+        let purses = ecs.read_storage::<Purse>();
+        let backpack = ecs.write_storage::<InBackpack>();
+        match purses.get(player_entity) {
+            None => return,
+            Some(purse) if purse.gold < price => Some("You can't afford the"),
+            Some(_) if !has_free_slot(player_entity, &backpack) => {
+                Some("You have no room for the")
+            }
+            Some(_) => None,
+        }
+    };
+    if let Some(problem) = problem {
+        let name = item_display_name(ecs, item);
+        ecs.fetch_mut::<GameLog>()
+            .entries
+            .insert(0, format!("{} {}.", problem, name));
+        return;
+    }
+
+    // Only take the item off the vendor's stack once the sale is sure to go through
+    let item = split_stack(ecs, item, 1);
+    let name = item_display_name(ecs, item);
+    let mut purses = ecs.write_storage::<Purse>();
+    let mut backpack = ecs.write_storage::<InBackpack>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    if let Some(purse) = purses.get_mut(player_entity) {
+        purse.gold -= price;
+    }
+    backpack
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to buy item");
+    gamelog
+        .entries
+        .insert(0, format!("You buy the {} for {} gold.", name, price));
+}
+
+/// Moves an item from the player's backpack into the vendor's stock and pays for it. Only
+/// one item is sold from a stack, and no vendor will buy the amulet.
+pub fn sell_item(ecs: &mut World, vendor: Entity, item: Entity) {
+    if ecs.read_storage::<Amulet>().get(item).is_some() {
+        ecs.fetch_mut::<GameLog>()
+            .entries
+            .insert(0, "The vendor won't take the amulet off your hands.".to_string());
+        return;
+    }
+
+    let player_entity = *ecs.fetch::<Entity>();
+    let price = sell_price(item_value(ecs, item));
+    let item = split_stack(ecs, item, 1);
+    let name = item_display_name(ecs, item);
+
+    if let Some(purse) = ecs.write_storage::<Purse>().get_mut(player_entity) {
+        purse.gold += price;
+    }
+    ecs.write_storage::<InBackpack>()
+        .insert(item, InBackpack { owner: vendor })
+        .expect("Unable to sell item");
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("You sell the {} for {} gold.", name, price));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::components::Stackable;
+    use super::*;
+
+    #[test]
+    fn vendors_buy_low_and_sell_high() {
+        assert_eq!(buy_price(20), 30);
+        assert_eq!(sell_price(20), 10);
+        assert_eq!(buy_price(7), 10);
+        assert_eq!(sell_price(7), 3);
+        // Otherwise the player could make money buying and selling the same item
+        for value in 0..100 {
+            assert!(buy_price(value) >= sell_price(value), "value {}", value);
+        }
+    }
+
+    #[test]
+    fn prices_are_at_least_a_coin() {
+        assert_eq!(buy_price(0), 1);
+        assert_eq!(sell_price(0), 1);
+        assert_eq!(sell_price(1), 1);
+    }
+
+    #[test]
+    fn failed_purchases_leave_the_stock_alone() {
+        let mut ecs = crate::test_world(1);
+        let player_entity = *ecs.fetch::<Entity>();
+        let vendor = ecs.create_entity().build();
+        let stock = ecs
+            .create_entity()
+            .with(Value { gold: 20 })
+            .with(Stackable { count: 3 })
+            .with(InBackpack { owner: vendor })
+            .build();
+        ecs.write_storage::<Purse>()
+            .insert(player_entity, Purse { gold: 5 })
+            .unwrap();
+        let entities_before = ecs.entities().join().count();
+
+        buy_item(&mut ecs, stock);
+        ecs.maintain();
+
+        assert_eq!(ecs.entities().join().count(), entities_before);
+        assert_eq!(ecs.read_storage::<Stackable>().get(stock).unwrap().count, 3);
+        assert_eq!(ecs.read_storage::<Purse>().get(player_entity).unwrap().gold, 5);
+    }
+
+    #[test]
+    fn enchantment_changes_item_value() {
+        let mut ecs = crate::test_world(1);
+        let item = ecs.create_entity().with(Value { gold: 30 }).build();
+        assert_eq!(item_value(&ecs, item), 30);
+
+        ecs.write_storage::<Enchantment>()
+            .insert(item, Enchantment { level: 2 })
+            .unwrap();
+        assert_eq!(item_value(&ecs, item), 30 + 2 * ENCHANTMENT_VALUE);
+
+        ecs.write_storage::<Enchantment>()
+            .insert(item, Enchantment { level: -3 })
+            .unwrap();
+        assert_eq!(item_value(&ecs, item), 1);
+    }
+}
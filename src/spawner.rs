@@ -4,16 +4,31 @@ use specs::saveload::{MarkedBuilder, SimpleMarker};
 use std::collections::HashMap;
 
 use super::components::{
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DefenseBonus, EntryTrigger,
-    EquipmentSlot, Equippable, Hidden, HungerClock, HungerState, InflictsDamage, Item, MagicMapper,
-    MeleePowerBonus, Monster, Name, Player, Position, ProvidesFood, ProvidesHealing, Ranged,
-    Renderable, SerializeMe, SingleActivation, Viewshed,
+    Alarm, Amulet, AreaOfEffect, Asleep, BlocksTile, Charges, CombatStats, Confusion, Consumable,
+    Container, Cursed, DefenseBonus, DestroysWalls, Digger, Enchantment, EntryTrigger,
+    EquipmentSlot, Equippable, Fuel, Gold, Hidden, HungerClock, HungerState, InBackpack,
+    InflictsDamage, Item, Key, LeavesCorpse, LightSource, Locked, LootTable, MagicMapper,
+    MeleePowerBonus, Monster, Name, Npc, NpcRole, Player, Position, ProvidesEnchantment,
+    ProvidesFood, ProvidesFuel, ProvidesHealing, ProvidesIdentification, ProvidesRecharge,
+    ProvidesRemoveCurse, Purse, Ranged, Rearming, ReleasesGas, Renderable, Rotting, SerializeMe,
+    SetsFire, Shatters, SingleActivation, SpillsLiquid, Stackable, Stealth, TeleportsVictim,
+    TrapDoor, Trapped, Value, Viewshed, Weight,
 };
-use super::map::{Map, TileType, MAPWIDTH};
+use super::containers;
+use super::dungeon_plan;
+use super::loot;
+use super::map::{Liquid, Map, TileType, MAPWIDTH};
 use super::random_table::RandomTable;
 use super::rect::Rect;
 
 const MAX_SPAWNS: i32 = 4;
+const STARTING_GOLD: i32 = 25;
+/// Number of items a vendor has for sale on each visit
+const SHOP_STOCK: i32 = 6;
+/// Turns the player's lantern burns when full
+const LANTERN_FUEL: i32 = 400;
+/// Number of torches mounted on the walls of each dungeon level
+const WALL_TORCHES: i32 = 12;
 
 /// Spawns the player and returns their entity object.
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
@@ -47,6 +62,16 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
             state: HungerState::WellFed,
             duration: 20,
         })
+        .with(Purse { gold: STARTING_GOLD })
+        .with(LightSource {
+            color: RGB::from_f32(1.0, 0.85, 0.5),
+            range: 5,
+        })
+        .with(Fuel {
+            turns: LANTERN_FUEL,
+            max: LANTERN_FUEL,
+        })
+        .with(Stealth { skill: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
@@ -83,7 +108,10 @@ pub fn spawn_region(
     map_depth: i32,
     spawn_list: &mut Vec<(usize, String)>,
 ) {
-    let spawn_table = room_table(map_depth);
+    let spawn_table = match dungeon_plan::theme_for_depth(map_depth) {
+        Some(theme) => theme.spawn_table(map_depth),
+        None => room_table(map_depth),
+    };
     let mut spawn_points: HashMap<usize, String> = HashMap::new();
     let mut areas: Vec<usize> = Vec::from(area);
 
@@ -101,7 +129,7 @@ pub fn spawn_region(
                 (rng.roll_dice(1, areas.len() as i32) - 1) as usize
             };
             let map_idx = areas[array_index];
-            spawn_points.insert(map_idx, spawn_table.roll(&mut rng));
+            spawn_points.insert(map_idx, spawn_table.roll(rng));
             areas.remove(array_index);
         }
     }
@@ -112,24 +140,88 @@ pub fn spawn_region(
     }
 }
 
-pub fn spawn_entity(ecs: &mut World, spawn: &(&usize, &String)) {
+/// Spawns the named entity at a map index, returning it if the name is known.
+pub fn spawn_entity(ecs: &mut World, spawn: &(&usize, &String)) -> Option<Entity> {
     let x = (*spawn.0 % MAPWIDTH) as i32;
     let y = (*spawn.0 / MAPWIDTH) as i32;
 
-    match spawn.1.as_ref() {
-        "Goblin" => goblin(ecs, x, y),
-        "Orc" => orc(ecs, x, y),
-        "Health Potion" => health_potion(ecs, x, y),
-        "Fireball Scroll" => fireball_scroll(ecs, x, y),
-        "Confusion Scroll" => confusion_scroll(ecs, x, y),
-        "Magic Missile Scroll" => magic_missile_scroll(ecs, x, y),
-        "Dagger" => dagger(ecs, x, y),
-        "Shield" => shield(ecs, x, y),
-        "Longsword" => longsword(ecs, x, y),
-        "Tower Shield" => tower_shield(ecs, x, y),
-        "Rations" => rations(ecs, x, y),
-        "Magic Mapping Scroll" => magic_mapping_scroll(ecs, x, y),
-        "Bear Trap" => bear_trap(ecs, x, y),
+    let entity = match spawn.1.as_ref() {
+        "Goblin" => Some(goblin(ecs, x, y)),
+        "Orc" => Some(orc(ecs, x, y)),
+        "Health Potion" => Some(health_potion(ecs, x, y)),
+        "Confusion Potion" => Some(confusion_potion(ecs, x, y)),
+        "Fireball Scroll" => Some(fireball_scroll(ecs, x, y)),
+        "Confusion Scroll" => Some(confusion_scroll(ecs, x, y)),
+        "Magic Missile Scroll" => Some(magic_missile_scroll(ecs, x, y)),
+        "Dagger" => Some(dagger(ecs, x, y)),
+        "Shield" => Some(shield(ecs, x, y)),
+        "Longsword" => Some(longsword(ecs, x, y)),
+        "Pick-axe" => Some(pick_axe(ecs, x, y)),
+        "Tower Shield" => Some(tower_shield(ecs, x, y)),
+        "Rations" => Some(rations(ecs, x, y)),
+        "Magic Mapping Scroll" => Some(magic_mapping_scroll(ecs, x, y)),
+        "Identify Scroll" => Some(identify_scroll(ecs, x, y)),
+        "Enchant Scroll" => Some(enchant_scroll(ecs, x, y)),
+        "Remove Curse Scroll" => Some(remove_curse_scroll(ecs, x, y)),
+        "Recharge Scroll" => Some(recharge_scroll(ecs, x, y)),
+        "Wand of Magic Missile" => Some(magic_missile_wand(ecs, x, y)),
+        "Wand of Confusion" => Some(confusion_wand(ecs, x, y)),
+        "Bear Trap" => Some(bear_trap(ecs, x, y)),
+        "Teleport Trap" => Some(teleport_trap(ecs, x, y)),
+        "Alarm Trap" => Some(alarm_trap(ecs, x, y)),
+        "Gas Trap" => Some(gas_trap(ecs, x, y)),
+        "Trap Door" => Some(trap_door(ecs, x, y)),
+        "Chest" => Some(chest(ecs, x, y)),
+        "Barrel" => Some(barrel(ecs, x, y)),
+        "Key" => Some(key(ecs, x, y)),
+        "Amulet of Ages" => Some(amulet(ecs, x, y)),
+        "Orc Warlord" => Some(orc_warlord(ecs, x, y)),
+        "Lantern Oil" => Some(lantern_oil(ecs, x, y)),
+        "Brazier" => Some(brazier(ecs, x, y)),
+        "Wall Torch" => Some(wall_torch(ecs, x, y)),
+        "Trap Kit" => Some(trap_kit(ecs, x, y)),
+        "Gold" => Some(gold(ecs, x, y)),
+        "Treasure" => {
+            // The base item is spawned through here, which has already rolled its magic
+            let depth = ecs.fetch::<Map>().depth;
+            return loot::spawn_loot(ecs, x, y, depth);
+        }
+        "Vendor" => Some(npc(ecs, x, y, RGB::named(rltk::GOLD), "Vendor", NpcRole::Vendor)),
+        "Healer" => Some(npc(ecs, x, y, RGB::named(rltk::WHITE), "Healer", NpcRole::Healer)),
+        "Quest Giver" => Some(npc(ecs, x, y, RGB::named(rltk::CYAN), "Quest Giver", NpcRole::QuestGiver)),
+        _ => None,
+    };
+
+    if let Some(entity) = entity {
+        if ecs.read_storage::<Equippable>().get(entity).is_some() {
+            let depth = ecs.fetch::<Map>().depth;
+            roll_enchantment(ecs, entity);
+            loot::roll_affixes(ecs, entity, depth);
+        }
+    }
+    entity
+}
+
+/// Equipment sometimes turns up enchanted, and sometimes cursed with a negative enchantment.
+fn roll_enchantment(ecs: &mut World, item: Entity) {
+    let (roll, magnitude) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        (rng.roll_dice(1, 20), rng.roll_dice(1, 2))
+    };
+    match roll {
+        1..=3 => {
+            ecs.write_storage::<Cursed>()
+                .insert(item, Cursed {})
+                .expect("Unable to curse item");
+            ecs.write_storage::<Enchantment>()
+                .insert(item, Enchantment { level: -magnitude })
+                .expect("Unable to enchant item");
+        }
+        4..=6 => {
+            ecs.write_storage::<Enchantment>()
+                .insert(item, Enchantment { level: magnitude })
+                .expect("Unable to enchant item");
+        }
         _ => {}
     }
 }
@@ -139,28 +231,201 @@ fn room_table(map_depth: i32) -> RandomTable {
         .add("Goblin", 10)
         .add("Orc", 1 + map_depth)
         .add("Health Potion", 7)
+        .add("Confusion Potion", 2)
         .add("Fireball Scroll", 2 + map_depth)
         .add("Confusion Scroll", 2 + map_depth)
         .add("Magic Missile Scroll", 4)
         .add("Dagger", 3)
         .add("Shield", 3)
         .add("Longsword", map_depth - 1)
+        .add("Pick-axe", 2)
         .add("Tower Shield", map_depth - 1)
         .add("Rations", 10)
         .add("Magic Mapping Scroll", 20)
+        .add("Identify Scroll", 4)
+        .add("Enchant Scroll", 2)
+        .add("Remove Curse Scroll", 2)
+        .add("Recharge Scroll", 1)
+        .add("Wand of Magic Missile", map_depth - 2)
+        .add("Wand of Confusion", map_depth - 3)
         .add("Bear Trap", 4)
+        .add("Teleport Trap", 1)
+        .add("Alarm Trap", 1)
+        .add("Gas Trap", map_depth - 2)
+        .add("Trap Door", map_depth - 3)
+        .add("Chest", 2)
+        .add("Barrel", 3)
+        .add("Key", 2)
+        .add("Lantern Oil", map_depth - 6)
+        .add("Brazier", map_depth - 4)
+        .add("Gold", 8)
+        .add("Treasure", map_depth - 2)
 }
 
-fn orc(ecs: &mut World, x: i32, y: i32) {
+/// Mounts torches on random walls that face onto open floor.
+pub fn spawn_wall_torches(ecs: &mut World) {
+    let walls: Vec<usize> = {
+        let map = ecs.fetch::<Map>();
+        (0..map.tiles.len())
+            .filter(|idx| map.tiles[*idx] == TileType::Wall)
+            .filter(|idx| {
+                let x = *idx as i32 % map.width;
+                let y = *idx as i32 / map.width;
+                [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dy)| {
+                    map.xy_idx(x + dx, y + dy)
+                        .is_some_and(|neighbour| map.tiles[neighbour] == TileType::Floor)
+                })
+            })
+            .collect()
+    };
+    if walls.is_empty() {
+        return;
+    }
+
+    for _ in 0..WALL_TORCHES {
+        let idx = {
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            walls[(rng.roll_dice(1, walls.len() as i32) - 1) as usize]
+        };
+        spawn_entity(ecs, &(&idx, &"Wall Torch".to_string()));
+    }
+}
+
+/// Leaves the amulet on the final level's down stairs, with the Orc Warlord standing guard
+/// over it.
+pub fn spawn_boss(ecs: &mut World) {
+    let stairs = {
+        let map = ecs.fetch::<Map>();
+        map.tiles.iter().position(|tile| *tile == TileType::DownStairs)
+    };
+    let stairs = match stairs {
+        Some(idx) => idx,
+        None => return,
+    };
+    spawn_entity(ecs, &(&stairs, &"Orc Warlord".to_string()));
+
+    // The amulet lies somewhere else on the level, so killing the boss isn't the only way to it
+    let floor: Vec<usize> = {
+        let map = ecs.fetch::<Map>();
+        map.tiles
+            .iter()
+            .enumerate()
+            .filter(|(idx, tile)| **tile == TileType::Floor && *idx != stairs)
+            .map(|(idx, _tile)| idx)
+            .collect()
+    };
+    let amulet = if floor.is_empty() {
+        stairs
+    } else {
+        let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, floor.len() as i32);
+        floor[(roll - 1) as usize]
+    };
+    spawn_entity(ecs, &(&amulet, &"Amulet of Ages".to_string()));
+}
+
+/// What vendors stock when the level's theme doesn't say
+fn shop_table() -> RandomTable {
+    RandomTable::new()
+        .add("Health Potion", 10)
+        .add("Rations", 10)
+        .add("Magic Missile Scroll", 4)
+        .add("Magic Mapping Scroll", 3)
+        .add("Identify Scroll", 5)
+        .add("Remove Curse Scroll", 3)
+        .add("Recharge Scroll", 2)
+        .add("Lantern Oil", 3)
+        .add("Dagger", 3)
+        .add("Shield", 3)
+}
+
+/// Replaces the stock of every vendor on the current level with newly rolled items.
+pub fn restock_vendors(ecs: &mut World) {
+    let vendors: Vec<Entity> = {
+        let entities = ecs.entities();
+        let npcs = ecs.read_storage::<Npc>();
+        let positions = ecs.read_storage::<Position>();
+        (&entities, &npcs, &positions)
+            .join()
+            .filter(|(_, npc, _)| npc.role == NpcRole::Vendor)
+            .map(|(entity, _, _)| entity)
+            .collect()
+    };
+    if vendors.is_empty() {
+        return;
+    }
+
+    let depth = ecs.fetch::<Map>().depth;
+    let stock_table = match dungeon_plan::theme_for_depth(depth) {
+        Some(theme) if !theme.shop_stock.is_empty() => theme.shop_table(depth),
+        _ => shop_table(),
+    };
+
+    for vendor in vendors {
+        // Whatever was left over from the last visit is gone
+        let old_stock: Vec<Entity> = {
+            let entities = ecs.entities();
+            let backpack = ecs.read_storage::<InBackpack>();
+            (&entities, &backpack)
+                .join()
+                .filter(|(_, pack)| pack.owner == vendor)
+                .map(|(entity, _)| entity)
+                .collect()
+        };
+        for item in old_stock {
+            ecs.delete_entity(item).expect("Unable to delete old stock");
+        }
+
+        for _i in 0..SHOP_STOCK {
+            let name = {
+                let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+                stock_table.roll(&mut rng)
+            };
+            let item = match spawn_entity(ecs, &(&0, &name)) {
+                Some(item) => item,
+                None => continue,
+            };
+            if ecs.read_storage::<Item>().get(item).is_none() {
+                ecs.delete_entity(item).expect("Unable to delete non-item stock");
+                continue;
+            }
+            ecs.write_storage::<Position>().remove(item);
+            ecs.write_storage::<InBackpack>()
+                .insert(item, InBackpack { owner: vendor })
+                .expect("Unable to stock item");
+        }
+    }
+}
+
+fn orc(ecs: &mut World, x: i32, y: i32) -> Entity {
     monster(ecs, x, y, rltk::to_cp437('o'), "Orc")
 }
 
-fn goblin(ecs: &mut World, x: i32, y: i32) {
+fn goblin(ecs: &mut World, x: i32, y: i32) -> Entity {
     monster(ecs, x, y, rltk::to_cp437('g'), "Goblin")
 }
 
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S) {
-    ecs.create_entity()
+/// The boss of the final level. It is never found asleep.
+fn orc_warlord(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let boss = monster(ecs, x, y, rltk::to_cp437('O'), "Orc Warlord");
+    ecs.write_storage::<Asleep>().remove(boss);
+    ecs.write_storage::<CombatStats>()
+        .insert(
+            boss,
+            CombatStats {
+                max_hp: 60,
+                hp: 60,
+                defense: 3,
+                power: 9,
+            },
+        )
+        .expect("Unable to insert boss stats");
+    boss
+}
+
+fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S) -> Entity {
+    let asleep = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 3) == 1;
+    let mut builder = ecs
+        .create_entity()
         .with(Position { x, y })
         .with(Renderable {
             glyph: glyph,
@@ -177,18 +442,71 @@ fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S) {
         .with(Name {
             name: name.to_string(),
         })
+        .with(LootTable {
+            table: name.to_string(),
+        })
+        .with(LeavesCorpse {})
         .with(BlocksTile {})
         .with(CombatStats {
             max_hp: 16,
             hp: 16,
             defense: 1,
             power: 4,
+        });
+    if asleep {
+        builder = builder.with(Asleep {});
+    }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+/// Turns a fresh corpse takes to rot away
+const CORPSE_ROT_TURNS: i32 = 150;
+
+/// The remains of a dead creature, edible until it rots away. Whatever it dropped is left
+/// inside it.
+pub fn corpse(ecs: &mut World, x: i32, y: i32, name: &str) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::DARK_RED),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 3,
+        })
+        .with(Name {
+            name: format!("{} corpse", name),
+        })
+        .with(Container {})
+        .with(Item {})
+        .with(Weight { lbs: 20 })
+        .with(ProvidesFood {})
+        .with(Consumable {})
+        .with(Rotting {
+            turns: CORPSE_ROT_TURNS,
         })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
+fn npc<S: ToString>(ecs: &mut World, x: i32, y: i32, fg: RGB, name: S, role: NpcRole) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg,
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Npc { role })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(BlocksTile {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn health_potion(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -201,13 +519,40 @@ fn health_potion(ecs: &mut World, x: i32, y: i32) {
             name: "Health Potion".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 20 })
         .with(Consumable {})
+        .with(Stackable { count: 1 })
         .with(ProvidesHealing { heal_amount: 8 })
+        .with(Shatters {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
+fn confusion_potion(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: RGB::named(rltk::PINK),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Confusion Potion".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 30 })
+        .with(Consumable {})
+        .with(Stackable { count: 1 })
+        .with(Confusion { turns: 4 })
+        .with(Shatters {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn fireball_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -220,15 +565,20 @@ fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Fireball Scroll".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 60 })
         .with(Consumable {})
+        .with(Stackable { count: 1 })
         .with(Ranged { range: 6 })
         .with(InflictsDamage { damage: 20 })
         .with(AreaOfEffect { radius: 3 })
+        .with(SetsFire { turns: 2 })
+        .with(DestroysWalls {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
+fn confusion_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -241,14 +591,17 @@ fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Confusion Scroll".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 40 })
         .with(Consumable {})
+        .with(Stackable { count: 1 })
         .with(Ranged { range: 6 })
         .with(Confusion { turns: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
+fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -261,13 +614,104 @@ fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Magic Mapping Scroll".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 50 })
         .with(MagicMapper {})
         .with(Consumable {})
+        .with(Stackable { count: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn identify_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::WHEAT),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Identify Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 30 })
+        .with(ProvidesIdentification {})
+        .with(Consumable {})
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
+fn enchant_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::BLUE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Enchant Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 80 })
+        .with(ProvidesEnchantment {})
+        .with(Consumable {})
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn remove_curse_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::WHITE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Remove Curse Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 40 })
+        .with(ProvidesRemoveCurse {})
+        .with(Consumable {})
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn recharge_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::LIGHT_GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Recharge Scroll".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 60 })
+        .with(ProvidesRecharge {})
+        .with(Consumable {})
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -280,14 +724,69 @@ fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Magic Missile Scroll".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 30 })
         .with(Consumable {})
+        .with(Stackable { count: 1 })
         .with(Ranged { range: 6 })
         .with(InflictsDamage { damage: 8 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn rations(ecs: &mut World, x: i32, y: i32) {
+fn magic_missile_wand(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('/'),
+            fg: RGB::named(rltk::CYAN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Wand of Magic Missile".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 120 })
+        .with(Charges {
+            current: 5,
+            max: 5,
+            delete_when_empty: false,
+        })
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage { damage: 8 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn confusion_wand(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('/'),
+            fg: RGB::named(rltk::PINK),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Wand of Confusion".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 150 })
+        .with(Charges {
+            current: 3,
+            max: 3,
+            delete_when_empty: false,
+        })
+        .with(Ranged { range: 6 })
+        .with(Confusion { turns: 4 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn rations(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -300,13 +799,16 @@ fn rations(ecs: &mut World, x: i32, y: i32) {
             name: "Rations".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 2 })
+        .with(Value { gold: 5 })
         .with(ProvidesFood {})
         .with(Consumable {})
+        .with(Stackable { count: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn dagger(ecs: &mut World, x: i32, y: i32) {
+fn dagger(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -319,15 +821,41 @@ fn dagger(ecs: &mut World, x: i32, y: i32) {
             name: "Dagger".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 2 })
+        .with(Value { gold: 15 })
         .with(Equippable {
             slot: EquipmentSlot::Melee,
         })
         .with(MeleePowerBonus { power: 2 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn pick_axe(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('/'),
+            fg: RGB::named(rltk::BURLYWOOD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Pick-axe".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 8 })
+        .with(Value { gold: 50 })
+        .with(Equippable {
+            slot: EquipmentSlot::Melee,
+        })
+        .with(MeleePowerBonus { power: 1 })
+        .with(Digger { turns: 4 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
+fn shield(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -340,15 +868,17 @@ fn shield(ecs: &mut World, x: i32, y: i32) {
             name: "Shield".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 8 })
+        .with(Value { gold: 15 })
         .with(Equippable {
             slot: EquipmentSlot::Shield,
         })
         .with(DefenseBonus { defense: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
+fn longsword(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -361,15 +891,17 @@ fn longsword(ecs: &mut World, x: i32, y: i32) {
             name: "Longsword".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 6 })
+        .with(Value { gold: 50 })
         .with(Equippable {
             slot: EquipmentSlot::Melee,
         })
         .with(MeleePowerBonus { power: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
+fn tower_shield(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -382,15 +914,221 @@ fn tower_shield(ecs: &mut World, x: i32, y: i32) {
             name: "Tower Shield".to_string(),
         })
         .with(Item {})
+        .with(Weight { lbs: 15 })
+        .with(Value { gold: 50 })
         .with(Equippable {
             slot: EquipmentSlot::Shield,
         })
         .with(DefenseBonus { defense: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Chests hold a few items. Some are locked, and some are trapped.
+fn chest(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let (locked, trapped, rolls) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        (rng.roll_dice(1, 3) == 1, rng.roll_dice(1, 4) == 1, rng.roll_dice(1, 3))
+    };
+    let mut builder = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('■'),
+            fg: RGB::named(rltk::GOLDENROD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Chest".to_string(),
+        })
+        .with(Container {});
+    if locked {
+        builder = builder.with(Locked {});
+    }
+    if trapped {
+        builder = builder.with(Trapped {}).with(InflictsDamage { damage: 6 });
+    }
+    let chest = builder.marked::<SimpleMarker<SerializeMe>>().build();
+
+    let depth = ecs.fetch::<Map>().depth;
+    containers::fill_container(ecs, chest, "Chest", depth, rolls);
+    chest
+}
+
+fn barrel(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let barrel = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('o'),
+            fg: RGB::named(rltk::BURLYWOOD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Barrel".to_string(),
+        })
+        .with(Container {})
+        .marked::<SimpleMarker<SerializeMe>>()
         .build();
+
+    let depth = ecs.fetch::<Map>().depth;
+    containers::fill_container(ecs, barrel, "Barrel", depth, 1);
+    barrel
+}
+
+fn amulet(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('"'),
+            fg: RGB::named(rltk::MAGENTA),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Amulet of Ages".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Amulet {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn key(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('⌐'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Key".to_string(),
+        })
+        .with(Item {})
+        .with(Value { gold: 20 })
+        .with(Key {})
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn trap_kit(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('≡'),
+            fg: RGB::named(rltk::GREY),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Trap Kit".to_string(),
+        })
+        .with(Item {})
+        .with(Value { gold: 40 })
+        .with(Weight { lbs: 3 })
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn lantern_oil(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('!'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Lantern Oil".to_string(),
+        })
+        .with(Item {})
+        .with(Weight { lbs: 1 })
+        .with(Value { gold: 30 })
+        .with(ProvidesFuel { turns: 200 })
+        .with(Shatters {})
+        .with(SpillsLiquid {
+            liquid: Liquid::Oil,
+            amount: 3,
+        })
+        .with(Consumable {})
+        .with(Stackable { count: 1 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn brazier(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('Ω'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Brazier".to_string(),
+        })
+        .with(BlocksTile {})
+        .with(LightSource {
+            color: RGB::named(rltk::ORANGE),
+            range: 8,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn wall_torch(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('¥'),
+            fg: RGB::named(rltk::YELLOW),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Wall Torch".to_string(),
+        })
+        .with(LightSource {
+            color: RGB::from_f32(1.0, 0.7, 0.3),
+            range: 6,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn bear_trap(ecs: &mut World, x: i32, y: i32) {
+fn gold(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let amount = {
+        let depth = ecs.fetch::<Map>().depth;
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        rng.roll_dice(2, 6) * i32::max(1, depth)
+    };
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('$'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Gold".to_string(),
+        })
+        .with(Item {})
+        .with(Gold { amount })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn bear_trap(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -407,5 +1145,133 @@ fn bear_trap(ecs: &mut World, x: i32, y: i32) {
         .with(InflictsDamage { damage: 6 })
         .with(SingleActivation {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn teleport_trap(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::MAGENTA),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Teleport Trap".to_string(),
+        })
+        .with(Hidden {})
+        .with(EntryTrigger {})
+        .with(TeleportsVictim {})
+        .with(Rearming {
+            cooldown: 10,
+            remaining: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn alarm_trap(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::YELLOW),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Alarm Trap".to_string(),
+        })
+        .with(Hidden {})
+        .with(EntryTrigger {})
+        .with(Alarm { radius: 12 })
+        .with(Rearming {
+            cooldown: 30,
+            remaining: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn gas_trap(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Gas Trap".to_string(),
+        })
+        .with(Hidden {})
+        .with(EntryTrigger {})
+        .with(ReleasesGas { amount: 6 })
+        .with(Rearming {
+            cooldown: 20,
+            remaining: 0,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn trap_door(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::CHOCOLATE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Trap Door".to_string(),
+        })
+        .with(Hidden {})
+        .with(EntryTrigger {})
+        .with(TrapDoor {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::components::Affixes;
+    use super::*;
+
+    /// Deep enough that most treasure rolls magic or rare affixes
+    const DEPTH: i32 = 10;
+
+    #[test]
+    fn treasure_rolls_affixes_once() {
+        let mut ecs = crate::test_world(DEPTH);
+        let bases: Vec<String> = ["Dagger", "Shield", "Longsword", "Tower Shield"]
+            .iter()
+            .map(|base| base.to_string())
+            .collect();
+
+        for i in 0..200 {
+            let idx = MAPWIDTH + 1 + i % 10;
+            // The loot table occasionally rolls nothing at all
+            let item = match spawn_entity(&mut ecs, &(&idx, &"Treasure".to_string())) {
+                Some(item) => item,
+                None => continue,
+            };
+
+            // Taking off the one set of affixes must leave the base item's name
+            let name = ecs.read_storage::<Name>().get(item).unwrap().name.clone();
+            let mut base = name.clone();
+            if let Some(affixes) = ecs.read_storage::<Affixes>().get(item) {
+                if let Some(prefix) = &affixes.prefix {
+                    base = base.replacen(&format!("{} ", prefix), "", 1);
+                }
+                if let Some(suffix) = &affixes.suffix {
+                    base = base.replacen(&format!(" {}", suffix), "", 1);
+                }
+            }
+            assert!(bases.contains(&base), "{} has more than one set of affixes", name);
+        }
+    }
 }
@@ -0,0 +1,151 @@
+use specs::prelude::*;
+use std::collections::HashMap;
+
+use super::components::{InBackpack, Name, Position, Stackable};
+use super::spawner;
+
+/// Merges identical stackable items in each backpack into a single stack.
+pub struct StackSystem {}
+
+impl<'a> System<'a> for StackSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Stackable>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, backpack, names, mut stackables) = data;
+
+        let mut stacks: HashMap<(Entity, String), Entity> = HashMap::new();
+        let mut merges: Vec<(Entity, Entity)> = Vec::new();
+        for (item, pack, name, _stack) in (&entities, &backpack, &names, &stackables).join() {
+            match stacks.get(&(pack.owner, name.name.clone())) {
+                Some(stack) => merges.push((item, *stack)),
+                None => {
+                    stacks.insert((pack.owner, name.name.clone()), item);
+                }
+            }
+        }
+
+        for (item, stack) in merges.iter() {
+            let count = stackables.get(*item).unwrap().count;
+            stackables.get_mut(*stack).unwrap().count += count;
+            entities.delete(*item).expect("Unable to merge stack");
+        }
+    }
+}
+
+/// How many items an entity stands for; anything that doesn't stack is a single item.
+pub fn stack_count(ecs: &World, item: Entity) -> i32 {
+    ecs.read_storage::<Stackable>()
+        .get(item)
+        .map_or(1, |stack| stack.count)
+}
+
+/// Takes `amount` items off a backpack stack as an entity of their own, leaving the rest
+/// behind. Taking the whole stack just returns it.
+pub fn split_stack(ecs: &mut World, item: Entity, amount: i32) -> Entity {
+    if amount >= stack_count(ecs, item) {
+        return item;
+    }
+    let (name, owner) = {
+        let names = ecs.read_storage::<Name>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        match (names.get(item), backpack.get(item)) {
+            (Some(name), Some(pack)) => (name.name.clone(), pack.owner),
+            _ => return item,
+        }
+    };
+    let split = match spawner::spawn_entity(ecs, &(&0, &name)) {
+        Some(split) => split,
+        None => return item,
+    };
+
+    ecs.write_storage::<Position>().remove(split);
+    ecs.write_storage::<InBackpack>()
+        .insert(split, InBackpack { owner })
+        .expect("Unable to split stack");
+    let mut stackables = ecs.write_storage::<Stackable>();
+    stackables
+        .insert(split, Stackable { count: amount })
+        .expect("Unable to split stack");
+    if let Some(stack) = stackables.get_mut(item) {
+        stack.count -= amount;
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spawns an item by name straight into `owner`'s backpack
+    fn give(ecs: &mut World, owner: Entity, name: &str) -> Entity {
+        let item = spawner::spawn_entity(ecs, &(&0, &name.to_string())).unwrap();
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(item, InBackpack { owner })
+            .unwrap();
+        item
+    }
+
+    /// The size of every stack in `owner`'s backpack with the given name
+    fn stacks(ecs: &World, owner: Entity, name: &str) -> Vec<i32> {
+        let backpack = ecs.read_storage::<InBackpack>();
+        let names = ecs.read_storage::<Name>();
+        let stackables = ecs.read_storage::<Stackable>();
+        (&backpack, &names, &stackables)
+            .join()
+            .filter(|(pack, item_name, _)| pack.owner == owner && item_name.name == name)
+            .map(|(_, _, stack)| stack.count)
+            .collect()
+    }
+
+    #[test]
+    fn stacks_merge_by_owner_and_name() {
+        let mut ecs = crate::test_world(1);
+        let alice = ecs.create_entity().build();
+        let bob = ecs.create_entity().build();
+        for _ in 0..3 {
+            give(&mut ecs, alice, "Health Potion");
+        }
+        give(&mut ecs, alice, "Rations");
+        give(&mut ecs, bob, "Health Potion");
+
+        StackSystem {}.run_now(&ecs);
+        ecs.maintain();
+
+        assert_eq!(stacks(&ecs, alice, "Health Potion"), vec![3]);
+        assert_eq!(stacks(&ecs, alice, "Rations"), vec![1]);
+        assert_eq!(stacks(&ecs, bob, "Health Potion"), vec![1]);
+    }
+
+    #[test]
+    fn split_stack_takes_some_of_the_stack() {
+        let mut ecs = crate::test_world(1);
+        let owner = ecs.create_entity().build();
+        let stack = give(&mut ecs, owner, "Health Potion");
+        ecs.write_storage::<Stackable>().get_mut(stack).unwrap().count = 5;
+
+        let split = split_stack(&mut ecs, stack, 2);
+        assert_ne!(split, stack);
+        assert_eq!(stack_count(&ecs, stack), 3);
+        assert_eq!(stack_count(&ecs, split), 2);
+        assert_eq!(ecs.read_storage::<InBackpack>().get(split).unwrap().owner, owner);
+        assert!(ecs.read_storage::<Position>().get(split).is_none());
+    }
+
+    #[test]
+    fn split_stack_takes_the_whole_stack() {
+        let mut ecs = crate::test_world(1);
+        let owner = ecs.create_entity().build();
+        let stack = give(&mut ecs, owner, "Health Potion");
+        ecs.write_storage::<Stackable>().get_mut(stack).unwrap().count = 3;
+
+        assert_eq!(split_stack(&mut ecs, stack, 3), stack);
+        assert_eq!(split_stack(&mut ecs, stack, 10), stack);
+        assert_eq!(stack_count(&ecs, stack), 3);
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::BTreeMap;
+
+use super::components::{Name, WantsToThrowItem, WantsToUseItem};
+use super::map::Map;
+use super::RunState;
+
+/// Points for every level reached
+const DEPTH_SCORE: i32 = 100;
+/// Points for every monster killed
+const KILL_SCORE: i32 = 10;
+/// Points for escaping with the amulet
+const VICTORY_SCORE: i32 = 5000;
+
+/// How the current run has gone so far, for the morgue file and the Hall of Fame
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct RunStats {
+    /// The seed the run's random numbers were started from
+    pub seed: u64,
+    pub turns: i32,
+    pub deepest_depth: i32,
+    /// Monsters the player has killed, by name
+    pub kills: BTreeMap<String, i32>,
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    /// Items the player has used, by name
+    pub items_used: BTreeMap<String, i32>,
+    pub cause_of_death: Option<String>,
+    /// Set once the run has been written to the morgue and the Hall of Fame
+    pub recorded: bool,
+}
+
+impl RunStats {
+    pub fn new(seed: u64) -> RunStats {
+        RunStats {
+            seed,
+            deepest_depth: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn total_kills(&self) -> i32 {
+        self.kills.values().sum()
+    }
+
+    /// Deeper levels and more kills score more, as does every coin carried out
+    pub fn score(&self, gold: i32, victory: bool) -> i32 {
+        let mut score = self.deepest_depth * DEPTH_SCORE + self.total_kills() * KILL_SCORE + gold;
+        if victory {
+            score += VICTORY_SCORE;
+        }
+        score
+    }
+}
+
+/// Keeps count of the turns the player takes, the items they use and how deep they get.
+/// Damage and kills are counted by `DamageSystem`. Runs before `ItemUseSystem` and
+/// `ThrowSystem`, which use up the items.
+pub struct StatsSystem {}
+
+impl<'a> System<'a> for StatsSystem {
+    type SystemData = (
+        WriteExpect<'a, RunStats>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Map>,
+        Entities<'a>,
+        ReadStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, WantsToThrowItem>,
+        ReadStorage<'a, Name>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut stats, player_entity, runstate, map, entities, wants_use, wants_throw, names) =
+            data;
+
+        stats.deepest_depth = i32::max(stats.deepest_depth, map.depth);
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+        stats.turns += 1;
+
+        // Thrown items count as used too
+        let used = (&entities, &wants_use).join().map(|(entity, u)| (entity, u.item));
+        let thrown = (&entities, &wants_throw).join().map(|(entity, t)| (entity, t.item));
+        for (entity, item) in used.chain(thrown) {
+            if entity != *player_entity {
+                continue;
+            }
+            if let Some(name) = names.get(item) {
+                *stats.items_used.entry(name.name.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_counts_depth_kills_and_gold() {
+        let mut stats = RunStats::new(1);
+        stats.deepest_depth = 4;
+        stats.kills.insert("Goblin".to_string(), 3);
+        stats.kills.insert("Orc".to_string(), 2);
+        assert_eq!(stats.score(25, false), 4 * DEPTH_SCORE + 5 * KILL_SCORE + 25);
+    }
+
+    #[test]
+    fn thrown_items_count_as_used() {
+        let mut ecs = crate::test_world(1);
+        let player = *ecs.fetch::<Entity>();
+        let potion = ecs
+            .create_entity()
+            .with(Name {
+                name: "Health Potion".to_string(),
+            })
+            .build();
+        ecs.write_storage::<WantsToThrowItem>()
+            .insert(
+                player,
+                WantsToThrowItem {
+                    item: potion,
+                    target: rltk::Point::new(3, 3),
+                },
+            )
+            .expect("Unable to insert intent");
+
+        StatsSystem {}.run_now(&ecs);
+        let stats = ecs.fetch::<RunStats>();
+        assert_eq!(stats.items_used.get("Health Potion"), Some(&1));
+    }
+
+    #[test]
+    fn victory_adds_to_the_score() {
+        let mut stats = RunStats::new(1);
+        stats.deepest_depth = 12;
+        assert_eq!(stats.score(0, true) - stats.score(0, false), VICTORY_SCORE);
+    }
+}
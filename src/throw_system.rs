@@ -0,0 +1,236 @@
+use rltk::{LineAlg, Point};
+use specs::prelude::*;
+
+use super::components::{
+    CombatStats, Confusion, DamageSource, Enchantment, InBackpack, InflictsDamage, MeleePowerBonus,
+    Name, Position, ProvidesHealing, Shatters, SpillsLiquid, SufferDamage, WantsToThrowItem, Weight,
+};
+use super::gamelog::GameLog;
+use super::identification::ItemKnowledge;
+use super::map::{Map, Puddle, TileType};
+use super::noise_system::{NoiseBuilder, COMBAT_VOLUME};
+use super::particle_system::ParticleBuilder;
+
+/// How far anything can be thrown
+pub const THROW_RANGE: i32 = 6;
+
+/// Throws items along a line towards their target. The first creature in the way is hit,
+/// taking damage from a weapon's power or the item's weight, and the item lands where it
+/// stopped, loudly. Items that shatter break instead, applying their effects around where
+/// they land.
+pub struct ThrowSystem {}
+
+impl<'a> System<'a> for ThrowSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, Map>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToThrowItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, InBackpack>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, ProvidesHealing>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, Shatters>,
+        ReadStorage<'a, Weight>,
+        ReadStorage<'a, MeleePowerBonus>,
+        ReadStorage<'a, Enchantment>,
+        WriteExpect<'a, ParticleBuilder>,
+        WriteExpect<'a, ItemKnowledge>,
+        WriteExpect<'a, NoiseBuilder>,
+        ReadStorage<'a, SpillsLiquid>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut gamelog,
+            mut map,
+            entities,
+            mut wants_throw,
+            names,
+            mut positions,
+            mut backpack,
+            mut combat_stats,
+            mut suffer_damage,
+            mut confused,
+            healing,
+            inflict_damage,
+            shatters,
+            weights,
+            power_bonuses,
+            enchantments,
+            mut particle_builder,
+            mut item_knowledge,
+            mut noise_builder,
+            spills,
+        ) = data;
+
+        for (thrower, throw) in (&entities, &wants_throw).join() {
+            let start = match positions.get(thrower) {
+                Some(pos) => Point::new(pos.x, pos.y),
+                None => continue,
+            };
+            let is_player = thrower == *player_entity;
+            let item_name = names.get(throw.item).unwrap().name.clone();
+            let shown_name = item_knowledge.display_name(&item_name);
+
+            // Follow the line until it reaches the target, a wall or a creature
+            let mut landing = start;
+            let mut victim: Option<Entity> = None;
+            for step in rltk::line2d(LineAlg::Bresenham, start, throw.target) {
+                if step == start {
+                    continue;
+                }
+                let idx = match map.xy_idx(step.x, step.y) {
+                    Some(idx) => idx,
+                    None => break,
+                };
+                if map.tiles[idx] == TileType::Wall {
+                    break;
+                }
+                landing = step;
+                victim = map.tile_content[idx]
+                    .iter()
+                    .find(|mob| **mob != thrower && combat_stats.get(**mob).is_some())
+                    .copied();
+                if victim.is_some() {
+                    break;
+                }
+            }
+
+            backpack.remove(throw.item);
+            noise_builder.request(landing.x, landing.y, COMBAT_VOLUME);
+
+            if shatters.get(throw.item).is_some() {
+                if is_player {
+                    gamelog.entries.insert(0, format!("The {} shatters!", shown_name));
+                    if item_knowledge.identify(&item_name) {
+                        gamelog
+                            .entries
+                            .insert(0, format!("The {} was a {}!", shown_name, item_name));
+                    }
+                }
+
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        let idx = match map.xy_idx(landing.x + dx, landing.y + dy) {
+                            Some(idx) => idx,
+                            None => continue,
+                        };
+                        particle_builder.request(
+                            landing.x + dx,
+                            landing.y + dy,
+                            rltk::RGB::named(rltk::LIGHT_BLUE),
+                            rltk::RGB::named(rltk::BLACK),
+                            rltk::to_cp437('░'),
+                            200.0,
+                        );
+                        for mob in map.tile_content[idx].iter() {
+                            if let Some(heal) = healing.get(throw.item) {
+                                if let Some(stats) = combat_stats.get_mut(*mob) {
+                                    stats.hp = i32::min(stats.max_hp, stats.hp + heal.heal_amount);
+                                }
+                            }
+                            if let Some(damage) = inflict_damage.get(throw.item) {
+                                if combat_stats.get(*mob).is_some() {
+                                    suffer_damage
+                                        .insert(
+                                            *mob,
+                                            SufferDamage {
+                                                amount: damage.damage,
+                                                source: DamageSource::new(is_player, &item_name),
+                                            },
+                                        )
+                                        .expect("Unable to insert damage");
+                                }
+                            }
+                            if let Some(confusion) = confused.get(throw.item).cloned() {
+                                if combat_stats.get(*mob).is_some() {
+                                    confused
+                                        .insert(*mob, confusion)
+                                        .expect("Unable to insert status");
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Whatever was inside splashes onto the floor
+                if let (Some(spill), Some(idx)) =
+                    (spills.get(throw.item), map.xy_idx(landing.x, landing.y))
+                {
+                    map.liquids.insert(
+                        idx,
+                        Puddle {
+                            liquid: spill.liquid,
+                            depth: spill.amount,
+                        },
+                    );
+                }
+
+                entities.delete(throw.item).expect("Unable to delete thrown item");
+                continue;
+            }
+
+            if let Some(victim) = victim {
+                let damage = match power_bonuses.get(throw.item) {
+                    Some(bonus) => {
+                        let enchantment = enchantments.get(throw.item).map_or(0, |e| e.level);
+                        i32::max(1, 1 + bonus.power + enchantment)
+                    }
+                    None => 1 + (weights.get(throw.item).map_or(0, |w| w.lbs) / 4),
+                };
+                suffer_damage
+                    .insert(
+                        victim,
+                        SufferDamage {
+                            amount: damage,
+                            source: DamageSource::new(is_player, &item_name),
+                        },
+                    )
+                    .expect("Unable to insert damage");
+                if is_player {
+                    gamelog.entries.insert(
+                        0,
+                        format!(
+                            "The {} hits the {}, inflicting {} hp.",
+                            shown_name,
+                            names.get(victim).unwrap().name,
+                            damage
+                        ),
+                    );
+                }
+                particle_builder.request(
+                    landing.x,
+                    landing.y,
+                    rltk::RGB::named(rltk::RED),
+                    rltk::RGB::named(rltk::BLACK),
+                    rltk::to_cp437('‼'),
+                    200.0,
+                );
+            } else if is_player {
+                gamelog
+                    .entries
+                    .insert(0, format!("You throw the {}.", shown_name));
+            }
+
+            positions
+                .insert(
+                    throw.item,
+                    Position {
+                        x: landing.x,
+                        y: landing.y,
+                    },
+                )
+                .expect("Unable to land thrown item");
+        }
+
+        wants_throw.clear();
+    }
+}
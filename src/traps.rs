@@ -0,0 +1,149 @@
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+use super::components::{EntryTrigger, Hidden, Name, Position, Sprung, Stealth};
+use super::gamelog::GameLog;
+use super::map::{Map, MAPWIDTH};
+use super::{spawner, RunState};
+
+/// How far around the player an active search looks
+const SEARCH_RADIUS: f32 = 3.0;
+/// Out of 6, the chance of a search finding each hidden thing in range
+const SEARCH_CHANCE: i32 = 2;
+/// Out of 20, the chance of disarming a trap before skill is added
+const DISARM_CHANCE: i32 = 12;
+
+fn trap_name(ecs: &World, trap: Entity) -> String {
+    ecs.read_storage::<Name>()
+        .get(trap)
+        .map_or("trap".to_string(), |name| name.name.clone())
+}
+
+/// Spends a turn searching the area around the player, with a much better chance of finding
+/// hidden things than just looking.
+pub fn search(ecs: &mut World) -> RunState {
+    let player_pos = *ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let names = ecs.read_storage::<Name>();
+    let mut hidden = ecs.write_storage::<Hidden>();
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+
+    let mut found: Vec<Entity> = Vec::new();
+    for (entity, _hidden, pos) in (&entities, &hidden, &positions).join() {
+        let idx = match map.xy_idx(pos.x, pos.y) {
+            Some(idx) => idx,
+            None => continue,
+        };
+        let distance =
+            rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), player_pos);
+        if distance <= SEARCH_RADIUS
+            && map.visible_tiles[idx]
+            && rng.roll_dice(1, 6) <= SEARCH_CHANCE
+        {
+            found.push(entity);
+        }
+    }
+
+    if found.is_empty() {
+        gamelog
+            .entries
+            .insert(0, "You search the area but find nothing.".to_string());
+    }
+    for entity in found.iter() {
+        if let Some(name) = names.get(*entity) {
+            gamelog
+                .entries
+                .insert(0, format!("You spotted a {}.", &name.name));
+        }
+        hidden.remove(*entity);
+    }
+
+    RunState::PlayerTurn
+}
+
+/// The nearest trap under or next to the player that they know about
+pub fn adjacent_trap(ecs: &World) -> Option<Entity> {
+    let player_pos = ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let triggers = ecs.read_storage::<EntryTrigger>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let positions = ecs.read_storage::<Position>();
+    (&entities, &triggers, &positions, !&hidden)
+        .join()
+        .filter(|(_, _, pos, _)| {
+            i32::abs(pos.x - player_pos.x) <= 1 && i32::abs(pos.y - player_pos.y) <= 1
+        })
+        .min_by_key(|(_, _, pos, _)| {
+            i32::abs(pos.x - player_pos.x) + i32::abs(pos.y - player_pos.y)
+        })
+        .map(|(entity, _, _, _)| entity)
+}
+
+/// Whether a d20 disarm roll succeeds. Each point of stealth skill makes it one more likely,
+/// but a 20 always fails.
+fn disarm_succeeds(roll: i32, skill: i32) -> bool {
+    roll < 20 && roll <= DISARM_CHANCE + skill
+}
+
+/// Tries to disarm a trap, helped by the player's stealth. Success leaves a trap kit where
+/// the trap was; failure sets the trap off on the player.
+pub fn disarm(ecs: &mut World, trap: Entity) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = trap_name(ecs, trap);
+    let skill = ecs.read_storage::<Stealth>()
+        .get(player_entity)
+        .map_or(0, |stealth| stealth.skill);
+
+    let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 20);
+    if !disarm_succeeds(roll, skill) {
+        ecs.fetch_mut::<GameLog>()
+            .entries
+            .insert(0, format!("You fumble with the {}.", name));
+        ecs.write_storage::<Sprung>()
+            .insert(
+                trap,
+                Sprung {
+                    victim: player_entity,
+                },
+            )
+            .expect("Unable to spring trap");
+        return RunState::PlayerTurn;
+    }
+
+    let idx = ecs
+        .read_storage::<Position>()
+        .get(trap)
+        .map(|pos| ((pos.y * MAPWIDTH as i32) + pos.x) as usize);
+    ecs.delete_entity(trap).expect("Unable to delete trap");
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("You disarm the {}.", name));
+    if let Some(idx) = idx {
+        spawner::spawn_entity(ecs, &(&idx, &"Trap Kit".to_string()));
+    }
+
+    RunState::PlayerTurn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skill_helps_disarm() {
+        assert!(disarm_succeeds(DISARM_CHANCE, 0));
+        assert!(!disarm_succeeds(DISARM_CHANCE + 1, 0));
+        assert!(disarm_succeeds(DISARM_CHANCE + 1, 1));
+        assert!(!disarm_succeeds(DISARM_CHANCE + 2, 1));
+    }
+
+    #[test]
+    fn twenty_always_fails_to_disarm() {
+        assert!(!disarm_succeeds(20, 0));
+        assert!(!disarm_succeeds(20, 20));
+        assert!(disarm_succeeds(19, 20));
+    }
+}
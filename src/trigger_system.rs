@@ -1,21 +1,33 @@
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
 use super::components::{
-    EntityMoved, EntryTrigger, Hidden, InflictsDamage, Name, Position, SingleActivation,
-    SufferDamage,
+    Alarm, DamageSource, EntityMoved, EntryTrigger, Hidden, InflictsDamage, Monster, Name, Position,
+    Rearming, ReleasesGas, SingleActivation, Sprung, SufferDamage, TeleportsVictim, TrapDoor,
+    Viewshed,
 };
+use super::dungeon_plan;
 use super::gamelog::GameLog;
-use super::map::Map;
+use super::map::{Map, TileType};
+use super::noise_system::NoiseBuilder;
 use super::particle_system::ParticleBuilder;
+use super::RunState;
 
+/// Sets off traps that something walked onto or that were sprung some other way. Traps can
+/// hurt, teleport or drop their victim down a level, raise an alarm or fill the area with gas.
+/// Rearming traps are inert until their cooldown runs out.
 pub struct TriggerSystem {}
 
 impl<'a> System<'a> for TriggerSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = (
-        ReadExpect<'a, Map>,
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, Point>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
         WriteStorage<'a, EntityMoved>,
-        ReadStorage<'a, Position>,
+        WriteStorage<'a, Position>,
         ReadStorage<'a, EntryTrigger>,
         WriteStorage<'a, Hidden>,
         ReadStorage<'a, Name>,
@@ -25,13 +37,26 @@ impl<'a> System<'a> for TriggerSystem {
         WriteExpect<'a, ParticleBuilder>,
         WriteStorage<'a, SufferDamage>,
         ReadStorage<'a, SingleActivation>,
+        WriteStorage<'a, Rearming>,
+        ReadStorage<'a, TeleportsVictim>,
+        ReadStorage<'a, Alarm>,
+        ReadStorage<'a, TrapDoor>,
+        ReadStorage<'a, ReleasesGas>,
+        ReadStorage<'a, Monster>,
+        WriteExpect<'a, NoiseBuilder>,
+        WriteStorage<'a, Viewshed>,
+        WriteStorage<'a, Sprung>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
-            map,
+            mut map,
+            mut player_pos,
+            player_entity,
+            mut runstate,
+            mut rng,
             mut entity_moved,
-            position,
+            mut position,
             entry_trigger,
             mut hidden,
             names,
@@ -41,68 +66,168 @@ impl<'a> System<'a> for TriggerSystem {
             mut particle_builder,
             mut suffer_damage,
             single_activation,
+            mut rearming,
+            teleports,
+            alarms,
+            trap_doors,
+            releases_gas,
+            monsters,
+            mut noise_builder,
+            mut viewsheds,
+            mut sprung,
         ) = data;
 
-        // Iterate the entities that moved and their final position
-        let mut remove_entities: Vec<Entity> = Vec::new();
+        // Rearming traps count down once per turn
+        if *runstate == RunState::PlayerTurn {
+            for rearm in (&mut rearming).join() {
+                if rearm.remaining > 0 {
+                    rearm.remaining -= 1;
+                }
+            }
+        }
 
-        for (entity, mut _entity_moved, pos) in (&entities, &mut entity_moved, &position).join() {
+        // Find which traps the entities that moved have stepped on
+        let mut triggered: Vec<(Entity, Entity, Point)> = Vec::new();
+        for (entity, _entity_moved, pos) in (&entities, &entity_moved, &position).join() {
             let idx = map.xy_idx(pos.x, pos.y).unwrap();
-
             for entity_id in map.tile_content[idx].iter() {
-                if entity != *entity_id {
-                    // Do not bother to check yourself for being a trap!
-                    let maybe_trigger = entry_trigger.get(*entity_id);
-                    match maybe_trigger {
-                        None => {}
-                        Some(_trigger) => {
-                            // TODO: Log name of trap.
-                            // Something triggered it
-                            let name = names.get(*entity_id);
-                            if let Some(name) = name {
-                                log.entries.insert(0, format!("{} triggers!", &name.name));
-                            }
-
-                            hidden.remove(*entity_id); // The trap is no longer hidden
-
-                            // If the trap does damage, inflict it.
-                            let damage = inflicts_damage.get(*entity_id);
-                            if let Some(damage) = damage {
-                                particle_builder.request(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::ORANGE),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    rltk::to_cp437('‼'),
-                                    200.0,
-                                );
-                                suffer_damage
-                                    .insert(
-                                        entity,
-                                        SufferDamage {
-                                            amount: damage.damage,
-                                        },
-                                    )
-                                    .expect("Unable to do damage");
-
-                                // If it is a single activation, remove it.
-                                let sa = single_activation.get(*entity_id);
-                                if let Some(_sa) = sa {
-                                    remove_entities.push(*entity_id);
-                                }
-                            }
+                // Do not bother to check yourself for being a trap!
+                if entity == *entity_id || entry_trigger.get(*entity_id).is_none() {
+                    continue;
+                }
+                if rearming.get(*entity_id).is_some_and(|r| r.remaining > 0) {
+                    continue;
+                }
+                triggered.push((entity, *entity_id, Point::new(pos.x, pos.y)));
+            }
+        }
+
+        // Traps sprung on someone nearby go off too
+        for (trap, sprung, pos) in (&entities, &sprung, &position).join() {
+            if rearming.get(trap).is_some_and(|r| r.remaining > 0) {
+                continue;
+            }
+            triggered.push((sprung.victim, trap, Point::new(pos.x, pos.y)));
+        }
+
+        // Remove all entity movement markers
+        entity_moved.clear();
+        sprung.clear();
+
+        let mut remove_entities: Vec<Entity> = Vec::new();
+        for (victim, trap, pos) in triggered {
+            let is_player = victim == *player_entity;
+            if let Some(name) = names.get(trap) {
+                log.entries.insert(0, format!("{} triggers!", &name.name));
+            }
+
+            hidden.remove(trap); // The trap is no longer hidden
+
+            // If the trap does damage, inflict it.
+            if let Some(damage) = inflicts_damage.get(trap) {
+                particle_builder.request(
+                    pos.x,
+                    pos.y,
+                    rltk::RGB::named(rltk::ORANGE),
+                    rltk::RGB::named(rltk::BLACK),
+                    rltk::to_cp437('‼'),
+                    200.0,
+                );
+                suffer_damage
+                    .insert(
+                        victim,
+                        SufferDamage {
+                            amount: damage.damage,
+                            source: DamageSource::Other(
+                                names.get(trap).map_or("trap".to_string(), |n| n.name.clone()),
+                            ),
+                        },
+                    )
+                    .expect("Unable to do damage");
+            }
+
+            // Fill the area with gas; EnvironmentSystem spreads it out from here
+            if let Some(gas) = releases_gas.get(trap) {
+                let idx = map.xy_idx(pos.x, pos.y).unwrap();
+                *map.gas.entry(idx).or_insert(0) += gas.amount;
+            }
+
+            // Every monster within earshot comes to see what happened
+            if let Some(alarm) = alarms.get(trap) {
+                log.entries
+                    .insert(0, "A loud bell rings out across the level!".to_string());
+                noise_builder.request(pos.x, pos.y, alarm.radius);
+            }
+
+            if teleports.get(trap).is_some() {
+                if let Some(destination) = random_open_tile(&map, &mut rng) {
+                    if let Some(victim_pos) = position.get_mut(victim) {
+                        let old_idx = map.xy_idx(victim_pos.x, victim_pos.y).unwrap();
+                        map.blocked[old_idx] = false;
+                        map.blocked[destination] = true;
+                        victim_pos.x = destination as i32 % map.width;
+                        victim_pos.y = destination as i32 / map.width;
+                        if is_player {
+                            *player_pos = Point::new(victim_pos.x, victim_pos.y);
+                            log.entries
+                                .insert(0, "The world lurches around you!".to_string());
                         }
                     }
+                    if let Some(viewshed) = viewsheds.get_mut(victim) {
+                        viewshed.dirty = true;
+                    }
+                }
+            }
+
+            if trap_doors.get(trap).is_some() {
+                if is_player && map.depth >= dungeon_plan::final_depth() {
+                    log.entries
+                        .insert(0, "The trap door opens onto solid rock.".to_string());
+                } else if is_player {
+                    log.entries
+                        .insert(0, "You fall through a trap door!".to_string());
+                    *runstate = RunState::NextLevel;
+                } else if monsters.get(victim).is_some() {
+                    if let Some(name) = names.get(victim) {
+                        log.entries
+                            .insert(0, format!("The {} falls through a trap door!", name.name));
+                    }
+                    if let Some(victim_pos) = position.get(victim) {
+                        let idx = map.xy_idx(victim_pos.x, victim_pos.y).unwrap();
+                        map.blocked[idx] = false;
+                    }
+                    entities.delete(victim).expect("Unable to delete monster");
                 }
             }
+
+            if let Some(rearm) = rearming.get_mut(trap) {
+                rearm.remaining = rearm.cooldown;
+            }
+
+            // If it is a single activation, remove it.
+            if single_activation.get(trap).is_some() {
+                remove_entities.push(trap);
+            }
         }
 
         // Remove any single activation traps
         for trap in remove_entities.iter() {
             entities.delete(*trap).expect("Unable to delete trap");
         }
+    }
+}
 
-        // Remove all entity movement markers
-        entity_moved.clear();
+/// A random floor tile that nothing is standing on
+fn random_open_tile(map: &Map, rng: &mut RandomNumberGenerator) -> Option<usize> {
+    let open: Vec<usize> = map
+        .tiles
+        .iter()
+        .enumerate()
+        .filter(|(idx, tile)| **tile == TileType::Floor && !map.blocked[*idx])
+        .map(|(idx, _)| idx)
+        .collect();
+    if open.is_empty() {
+        return None;
     }
+    Some(open[(rng.roll_dice(1, open.len() as i32) - 1) as usize])
 }
@@ -5,6 +5,11 @@ use super::components::{Hidden, Name, Player, Position, Viewshed};
 use super::gamelog::GameLog;
 use super::map::Map;
 
+/// How far the player can see without light on dark levels
+const DARK_SIGHT: f32 = 1.5;
+/// How bright a tile must be for the player to see it on dark levels
+const MIN_LIGHT: f32 = 0.1;
+
 pub struct VisibilitySystem {}
 
 impl<'a> System<'a> for VisibilitySystem {
@@ -34,6 +39,18 @@ impl<'a> System<'a> for VisibilitySystem {
                     .visible_tiles
                     .retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
 
+                // In the dark, the player only sees lit tiles and what is right beside them
+                if map.dark && player.get(ent).is_some() {
+                    let origin = Point::new(pos.x, pos.y);
+                    viewshed.visible_tiles.retain(|p| {
+                        let lit = map
+                            .xy_idx(p.x, p.y)
+                            .and_then(|idx| map.light.get(idx))
+                            .is_some_and(|l| f32::max(l.r, f32::max(l.g, l.b)) >= MIN_LIGHT);
+                        lit || rltk::DistanceAlg::Pythagoras.distance2d(origin, *p) <= DARK_SIGHT
+                    });
+                }
+
                 // If this is the player, reveal what they can see
                 let p: Option<&Player> = player.get(ent);
                 if let Some(_p) = p {